@@ -0,0 +1,347 @@
+//! Computer-controlled gorillas.
+//!
+//! An `AiPlayer` looks at the same things a human does: the wind, gravity,
+//! the skyline and where the other gorilla stands. Weaker players guess a
+//! throw and then bracket in on the target using where their bananas landed.
+//! Stronger players also rehearse throws in their head by simulating them,
+//! and the strongest asks the solver for a throw that connects.
+
+use crate::solver::{Search, Solution, SolverOptions};
+use crate::{fly_shot, GameState, Rng, ShotState};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Advanced,
+    Expert,
+}
+
+// How sloppy a difficulty level is.
+struct Skill {
+    // Spread of the angle in degrees on the first throw of a round.
+    aim_noise: f32,
+    // Spread of the velocity, as a fraction, on the first throw of a round.
+    power_noise: f32,
+    // Throws simulated in the head before committing to one.
+    rehearsals: u32,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Beginner,
+        Difficulty::Intermediate,
+        Difficulty::Advanced,
+        Difficulty::Expert,
+    ];
+
+    /// Looks up a level as typed on the setup screen, 1 to 4.
+    pub fn from_level(level: u32) -> Option<Self> {
+        Self::ALL.get((level as usize).checked_sub(1)?).copied()
+    }
+
+    pub fn level(self) -> u32 {
+        self as u32 + 1
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Intermediate => "Intermediate",
+            Difficulty::Advanced => "Advanced",
+            Difficulty::Expert => "Expert",
+        }
+    }
+
+    fn skill(self) -> Skill {
+        match self {
+            Difficulty::Beginner => Skill {
+                aim_noise: 8.0,
+                power_noise: 0.3,
+                rehearsals: 0,
+            },
+            Difficulty::Intermediate => Skill {
+                aim_noise: 4.0,
+                power_noise: 0.12,
+                rehearsals: 3,
+            },
+            Difficulty::Advanced => Skill {
+                aim_noise: 1.5,
+                power_noise: 0.04,
+                rehearsals: 10,
+            },
+            Difficulty::Expert => Skill {
+                aim_noise: 0.5,
+                power_noise: 0.01,
+                rehearsals: 20,
+            },
+        }
+    }
+}
+
+/// Each throw's noise is this fraction of the previous one, so players home
+/// in on the target over a round instead of spraying forever.
+const NOISE_DECAY: f32 = 0.55;
+
+const MIN_ANGLE: f32 = 10.0;
+const MAX_ANGLE: f32 = 80.0;
+
+/// Angles the Expert's search tries in each call to `AiPlayer::think`.
+const ANGLES_PER_THINK: usize = 8;
+
+/// A throw being worked out; see `AiPlayer::start_thinking`.
+pub struct Thinking {
+    shooter: usize,
+    target: usize,
+    backwards: bool,
+    // The Expert's hunt for a throw that connects, while it lasts.
+    search: Option<Search>,
+}
+
+pub struct AiPlayer {
+    pub difficulty: Difficulty,
+    rng: Rng,
+    // Seed of the map the memories below belong to.
+    round_seed: Option<u32>,
+    angle: f32,
+    last_velocity: f32,
+    // Fastest throw known to fall short and slowest known to fly long.
+    short: Option<f32>,
+    long: Option<f32>,
+    throws: i32,
+}
+
+impl AiPlayer {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Self {
+        AiPlayer {
+            difficulty,
+            rng: Rng::new(seed),
+            round_seed: None,
+            angle: 45.0,
+            last_velocity: 0.0,
+            short: None,
+            long: None,
+            throws: 0,
+        }
+    }
+
+    /// Picks the next throw for `shooter`, as it would be typed into the
+    /// angle and velocity prompts.
+    pub fn choose_throw(&mut self, state: &GameState, shooter: usize) -> (f32, f32) {
+        let mut thinking = self.start_thinking(state, shooter);
+        loop {
+            if let Some(throw) = self.think(&mut thinking, state) {
+                return throw;
+            }
+        }
+    }
+
+    /// Starts on the next throw for `shooter`, for callers that can't wait
+    /// for `choose_throw`. Call `think` with the same state until it answers.
+    pub fn start_thinking(&mut self, state: &GameState, shooter: usize) -> Thinking {
+        if self.round_seed != Some(state.seed) {
+            self.forget_round(state.seed);
+        }
+        let target = state.nearest_opponent(shooter);
+        let backwards = behind(state, shooter, target);
+        let search = (self.difficulty == Difficulty::Expert)
+            .then(|| Search::new(state, shooter, target, &hit_options(backwards)));
+        Thinking {
+            shooter,
+            target,
+            backwards,
+            search,
+        }
+    }
+
+    /// Thinks a little more, and returns the throw `choose_throw` would have
+    /// once there is one.
+    pub fn think(&mut self, thinking: &mut Thinking, state: &GameState) -> Option<(f32, f32)> {
+        if let Some(search) = &mut thinking.search {
+            for _ in 0..ANGLES_PER_THINK {
+                search.step();
+            }
+            if !search.is_done() {
+                return None;
+            }
+        }
+        let hit = thinking
+            .search
+            .take()
+            .and_then(|search| best_hit(&search.finish().ok()?, thinking.backwards));
+        if let Some(throw) = hit {
+            self.throws += 1;
+            self.last_velocity = throw.1;
+            return Some(throw);
+        }
+        Some(self.bracket(state, thinking.shooter, thinking.target, thinking.backwards))
+    }
+
+    // Guesses and rehearses a throw from what the misses so far have shown.
+    fn bracket(
+        &mut self,
+        state: &GameState,
+        shooter: usize,
+        target: usize,
+        backwards: bool,
+    ) -> (f32, f32) {
+        let mut sim = state.clone();
+        let skill = self.difficulty.skill();
+        let angle = self.angle;
+        let mut velocity = match (self.short, self.long) {
+            (Some(short), Some(long)) => (short + long) / 2.0,
+            (Some(short), None) => short * 1.2,
+            (None, Some(long)) => long * 0.8,
+            (None, None) => estimate_velocity(state, shooter, target, angle),
+        };
+
+        let mut low = self.short.unwrap_or(velocity * 0.5);
+        let mut high = self.long.unwrap_or(velocity * 2.0);
+        for _ in 0..skill.rehearsals {
+            let shot = fly_shot(&mut sim, shooter, typed_angle(angle, backwards), velocity);
+            if shot.player_hit == Some(target) {
+                break;
+            }
+            if miss_distance(state, shooter, target, &shot) < 0.0 {
+                low = velocity;
+            } else {
+                high = velocity;
+            }
+            velocity = (low + high) / 2.0;
+        }
+
+        let spread = NOISE_DECAY.powi(self.throws);
+        let angle = angle + self.jitter() * skill.aim_noise * spread;
+        let velocity = velocity * (1.0 + self.jitter() * skill.power_noise * spread);
+
+        self.throws += 1;
+        let (angle, velocity) = (
+            typed_angle(angle.clamp(MIN_ANGLE, MAX_ANGLE).round(), backwards),
+            (velocity.max(1.0) * 10.0).round() / 10.0,
+        );
+        self.last_velocity = velocity;
+        (angle, velocity)
+    }
+
+    /// Learns from where our last banana ended up.
+    pub fn observe(&mut self, state: &GameState, shooter: usize, shot_state: &ShotState) {
+        let target = state.nearest_opponent(shooter);
+        if shot_state.player_hit == Some(target) {
+            return;
+        }
+
+        let miss = miss_distance(state, shooter, target, shot_state);
+        let distance = (target_x(state, target) - state.gorilla_x[shooter]).abs();
+        let progress = 1.0 + miss / distance.max(1.0);
+        let target_y = state.gorilla_y[target];
+        let blocked = shot_state
+            .player_hit
+            .is_some_and(|hit| !state.is_opponent(shooter, hit))
+            || (shot_state.impact
+                && miss < 0.0
+                && (progress < 0.5 || shot_state.y > target_y + 30.0));
+        let stuck = matches!((self.short, self.long), (Some(s), Some(l)) if l - s < 0.2);
+        if (blocked || stuck) && self.angle < MAX_ANGLE {
+            // Something is in the way. Lob it higher and start bracketing over.
+            self.angle = (self.angle + if stuck { 3.0 } else { 10.0 }).min(MAX_ANGLE);
+            self.short = None;
+            self.long = None;
+            return;
+        }
+
+        if miss < 0.0 {
+            self.short = Some(
+                self.short
+                    .map_or(self.last_velocity, |v| v.max(self.last_velocity)),
+            );
+            if self.long.is_some_and(|long| long <= self.last_velocity) {
+                self.long = None;
+            }
+        } else {
+            self.long = Some(
+                self.long
+                    .map_or(self.last_velocity, |v| v.min(self.last_velocity)),
+            );
+            if self.short.is_some_and(|short| short >= self.last_velocity) {
+                self.short = None;
+            }
+        }
+    }
+
+    fn forget_round(&mut self, seed: u32) {
+        self.round_seed = Some(seed);
+        self.angle = 45.0;
+        self.short = None;
+        self.long = None;
+        self.throws = 0;
+    }
+
+    // Uniform noise in [-1, 1).
+    fn jitter(&mut self) -> f32 {
+        self.rng.next_f32() * 2.0 - 1.0
+    }
+}
+
+// Middle of a gorilla's hitbox.
+fn target_x(state: &GameState, target: usize) -> f32 {
+    state.gorilla_x[target] + 10.0
+}
+
+/// How far past (positive) or short of (negative) the target a shot ended,
+/// measured from the shooter towards the target.
+fn miss_distance(state: &GameState, shooter: usize, target: usize, shot_state: &ShotState) -> f32 {
+    let direction = if target_x(state, target) < state.gorilla_x[shooter] {
+        -1.0
+    } else {
+        1.0
+    };
+    (shot_state.x - target_x(state, target)) * direction
+}
+
+// Whether the target stands behind the shooter, as happens to gorillas in the
+// middle of a free-for-all. Those throws go in past 90 degrees.
+fn behind(state: &GameState, shooter: usize, target: usize) -> bool {
+    let to_the_left = target_x(state, target) < state.gorilla_x[shooter];
+    to_the_left != state.faces_left(shooter)
+}
+
+// What to type for a throw `elevation` degrees up towards the target.
+fn typed_angle(elevation: f32, backwards: bool) -> f32 {
+    if backwards {
+        180.0 - elevation
+    } else {
+        elevation
+    }
+}
+
+// First guess from the range of a throw over flat ground, ignoring the wind.
+fn estimate_velocity(state: &GameState, shooter: usize, target: usize, angle: f32) -> f32 {
+    let distance = (target_x(state, target) - state.gorilla_x[shooter]).abs();
+    let lift = (2.0 * angle.to_radians()).sin().max(0.1);
+    (distance * state.gravity / lift).sqrt()
+}
+
+// The solver's bounds for throws the computer players would make.
+fn hit_options(backwards: bool) -> SolverOptions {
+    let (min_angle, max_angle) = if backwards {
+        (180.0 - MAX_ANGLE, 180.0 - MIN_ANGLE)
+    } else {
+        (MIN_ANGLE, MAX_ANGLE)
+    };
+    SolverOptions {
+        min_angle,
+        max_angle,
+        ..SolverOptions::default()
+    }
+}
+
+// The hitting throw nearest 45 degrees, aimed at the middle of its band of
+// velocities so small errors still connect.
+fn best_hit(solutions: &[Solution], backwards: bool) -> Option<(f32, f32)> {
+    let off_45 = |angle: f32| (typed_angle(angle, backwards) - 45.0).abs();
+    let best = solutions
+        .iter()
+        .min_by(|a, b| off_45(a.angle).total_cmp(&off_45(b.angle)))?;
+    let velocity = ((best.min_velocity + best.max_velocity) / 2.0 * 10.0).round() / 10.0;
+    Some((best.angle, velocity))
+}
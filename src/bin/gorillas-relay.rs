@@ -0,0 +1,159 @@
+//! Meeting point for browser matches.
+//!
+//! Browser tabs cannot listen for connections, so two of them meet here
+//! instead. Each opens a WebSocket and names a room; once two have named the
+//! same room, everything one sends is passed on to the other. The relay
+//! never looks inside those messages: both tabs run the game, and the
+//! handshake is described in `gorillas::net`.
+//!
+//! ```bash
+//! cargo run --release --features relay --bin gorillas-relay -- [port]
+//! ```
+
+use gorillas::net::DEFAULT_RELAY_PORT;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::process::exit;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tungstenite::{Error, Message, WebSocket};
+
+const MAX_ROOM_CODE_LEN: usize = 16;
+// How long a connection waits for its own tab before passing on messages
+// from the other one.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// For each room, the connections in it in the order they joined, with a
+// way to hand each one messages to send.
+type Rooms = Arc<Mutex<HashMap<String, Vec<(usize, Sender<String>)>>>>;
+
+fn main() {
+    let port = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("usage: gorillas-relay [port]");
+            exit(2);
+        }),
+        None => DEFAULT_RELAY_PORT,
+    };
+    let listener = TcpListener::bind(("0.0.0.0", port)).unwrap_or_else(|err| {
+        eprintln!("Could not listen on port {}: {}", port, err);
+        exit(1);
+    });
+    // Port 0 lets the system pick one, so say which it picked.
+    let port = listener.local_addr().map_or(port, |addr| addr.port());
+    println!("Relaying on port {}", port);
+
+    let rooms = Rooms::default();
+    for (id, stream) in listener.incoming().enumerate() {
+        match stream {
+            Ok(stream) => {
+                let rooms = rooms.clone();
+                std::thread::spawn(move || serve(stream, id, &rooms));
+            }
+            Err(err) => eprintln!("Accept failed: {}", err),
+        }
+    }
+}
+
+fn serve(stream: TcpStream, id: usize, rooms: &Rooms) {
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+    let Some(code) = read_room_code(&mut socket) else {
+        let _ = socket.close(None);
+        return;
+    };
+
+    let (outbox, inbox) = mpsc::channel();
+    if join(rooms, &code, id, outbox) {
+        relay(&mut socket, rooms, &code, id, &inbox);
+        leave(rooms, &code, id);
+    } else {
+        let _ = socket.send(Message::text("FULL"));
+    }
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+// The first message must be `ROOM <code>`.
+fn read_room_code(socket: &mut WebSocket<TcpStream>) -> Option<String> {
+    let Ok(Message::Text(text)) = socket.read() else {
+        return None;
+    };
+    let code = text.strip_prefix("ROOM ")?.trim();
+    let valid = !code.is_empty()
+        && code.len() <= MAX_ROOM_CODE_LEN
+        && code.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then(|| code.to_ascii_uppercase())
+}
+
+// Adds the connection to the room, and pairs the room up if it is the
+// second. False if the room is full.
+fn join(rooms: &Rooms, code: &str, id: usize, outbox: Sender<String>) -> bool {
+    let mut rooms = rooms.lock().unwrap();
+    let members = rooms.entry(code.to_string()).or_default();
+    if members.len() >= 2 {
+        return false;
+    }
+    members.push((id, outbox));
+    if let [(_, host), (_, guest)] = members.as_slice() {
+        let _ = host.send("PAIRED HOST".to_string());
+        let _ = guest.send("PAIRED GUEST".to_string());
+        println!("Room {} paired", code);
+    }
+    true
+}
+
+fn leave(rooms: &Rooms, code: &str, id: usize) {
+    let mut rooms = rooms.lock().unwrap();
+    let Some(members) = rooms.get_mut(code) else {
+        return;
+    };
+    members.retain(|(member, _)| *member != id);
+    for (_, other) in members.iter() {
+        let _ = other.send("BYE".to_string());
+    }
+    if members.is_empty() {
+        rooms.remove(code);
+    }
+}
+
+// Passes messages both ways until the tab goes away.
+fn relay(
+    socket: &mut WebSocket<TcpStream>,
+    rooms: &Rooms,
+    code: &str,
+    id: usize,
+    inbox: &Receiver<String>,
+) {
+    if socket
+        .get_mut()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .is_err()
+    {
+        return;
+    }
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let rooms = rooms.lock().unwrap();
+                let others = rooms.get(code).into_iter().flatten();
+                for (_, other) in others.filter(|(member, _)| *member != id) {
+                    let _ = other.send(text.to_string());
+                }
+            }
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+        while let Ok(text) = inbox.try_recv() {
+            if socket.send(Message::text(text)).is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Flies a single banana without a window and prints its whole path.
+//!
+//! The map is dealt from the seed as in the game, the throw goes through
+//! `trace_shot`, and every step of the flight is printed with how it ended.
+//! Handy for checking a bug report or scripting sweeps over the inputs:
+//!
+//! ```bash
+//! cargo run --release --bin gorillas-sim -- --seed 42 --wind 0 45 60
+//! for v in $(seq 40 5 90); do gorillas-sim --seed 42 --format csv 45 $v | tail -n 1; done
+//! ```
+
+use gorillas::{
+    trace_shot, CitySizing, GameState, ShotState, MAX_FLIGHT_TIME, MAX_PLAYERS, MIN_PLAYERS,
+};
+use std::io::{self, Write};
+use std::process::exit;
+
+const USAGE: &str = "usage: gorillas-sim [--seed S] [--gravity G] [--wind W] [--players N] \
+[--shooter N] [--city ega|cga] [--format json|csv] <angle> <velocity>
+
+Prints the banana's position at each step (t, x, y in the 640x350 playfield,
+y growing downwards) and what it hit. --wind replaces the map's own wind;
+--city cga deals the map with the CGA's building sizes.
+Players and buildings are numbered from 1.";
+
+#[derive(PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+struct Settings {
+    seed: u32,
+    gravity: f32,
+    wind: Option<i32>,
+    players: usize,
+    shooter: usize,
+    city: CitySizing,
+    format: Format,
+    angle: f32,
+    velocity: f32,
+}
+
+impl Settings {
+    fn from_args() -> Result<Self, String> {
+        let mut settings = Settings {
+            seed: 0,
+            gravity: 9.8,
+            wind: None,
+            players: MIN_PLAYERS,
+            shooter: 1,
+            city: CitySizing::Ega,
+            format: Format::Json,
+            angle: 0.0,
+            velocity: 0.0,
+        };
+        let mut throw = Vec::new();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--seed" => settings.seed = parse(&arg, &value(&arg)?)?,
+                "--gravity" => settings.gravity = parse_finite(&arg, &value(&arg)?)?,
+                "--wind" => settings.wind = Some(parse(&arg, &value(&arg)?)?),
+                "--players" => settings.players = parse(&arg, &value(&arg)?)?,
+                "--shooter" => settings.shooter = parse(&arg, &value(&arg)?)?,
+                "--city" => {
+                    let name = value(&arg)?;
+                    settings.city = CitySizing::from_name(&name)
+                        .ok_or_else(|| format!("unknown city '{}'", name))?
+                }
+                "--format" => {
+                    settings.format = match value(&arg)?.as_str() {
+                        "json" => Format::Json,
+                        "csv" => Format::Csv,
+                        other => return Err(format!("unknown format '{}'", other)),
+                    }
+                }
+                "-h" | "--help" => return Err(String::new()),
+                // Negative numbers are values, not options.
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => throw.push(arg),
+            }
+        }
+
+        let [angle, velocity] = throw.as_slice() else {
+            return Err("expected an angle and a velocity".to_string());
+        };
+        settings.angle = parse_finite("angle", angle)?;
+        settings.velocity = parse_finite("velocity", velocity)?;
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&settings.players) {
+            return Err(format!(
+                "--players must be from {} to {}",
+                MIN_PLAYERS, MAX_PLAYERS
+            ));
+        }
+        if !(1..=settings.players).contains(&settings.shooter) {
+            return Err(format!("--shooter must be from 1 to {}", settings.players));
+        }
+        Ok(settings)
+    }
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("bad value '{}' for {}", value, option))
+}
+
+// Like `parse`, but NaN and infinities are refused too: they can't be flown,
+// and JSON has no way to write them.
+fn parse_finite(option: &str, value: &str) -> Result<f32, String> {
+    match parse::<f32>(option, value)? {
+        number if number.is_finite() => Ok(number),
+        _ => Err(format!("bad value '{}' for {}", value, option)),
+    }
+}
+
+// How a throw ended, in the words of both output formats.
+fn outcome(shot: &ShotState) -> (&'static str, Option<usize>) {
+    if let Some(player) = shot.player_hit {
+        ("gorilla", Some(player + 1))
+    } else if let Some(building) = shot.building_hit {
+        ("building", Some(building + 1))
+    } else if shot.t > MAX_FLIGHT_TIME {
+        // Given up in the air, which `plot_shot` also counts as off screen.
+        ("lost", None)
+    } else if !shot.on_screen {
+        ("offscreen", None)
+    } else {
+        ("lost", None)
+    }
+}
+
+// Writes the flight in the chosen format.
+fn print(
+    out: &mut impl Write,
+    settings: &Settings,
+    state: &GameState,
+    steps: &[(f32, f32, f32)],
+    shot: &ShotState,
+) -> io::Result<()> {
+    let (ending, what) = outcome(shot);
+    match settings.format {
+        Format::Csv => {
+            // The outcome goes on the last row only, so sweeps can `tail -n 1`.
+            writeln!(out, "t,x,y,outcome,hit,sun")?;
+            for (i, (t, x, y)) in steps.iter().enumerate() {
+                if i + 1 < steps.len() {
+                    writeln!(out, "{},{},{},,,", t, x, y)?;
+                } else {
+                    let hit = what.map_or(String::new(), |n| n.to_string());
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{}",
+                        t, x, y, ending, hit, state.sun_hit
+                    )?;
+                }
+            }
+        }
+        Format::Json => {
+            let trajectory: Vec<String> = steps
+                .iter()
+                .map(|(t, x, y)| format!("{{\"t\":{},\"x\":{},\"y\":{}}}", t, x, y))
+                .collect();
+            let hit = match (ending, what) {
+                ("gorilla", Some(n)) => format!(",\"player\":{}", n),
+                ("building", Some(n)) => format!(",\"building\":{}", n),
+                _ => String::new(),
+            };
+            writeln!(
+                out,
+                "{{\"seed\":{},\"gravity\":{},\"wind\":{},\"shooter\":{},\"angle\":{},\
+                 \"velocity\":{},\"trajectory\":[{}],\"outcome\":\"{}\"{},\"sun\":{}}}",
+                settings.seed,
+                state.gravity,
+                state.wind,
+                settings.shooter,
+                settings.angle,
+                settings.velocity,
+                trajectory.join(","),
+                ending,
+                hit,
+                state.sun_hit
+            )?;
+        }
+    }
+    out.flush()
+}
+
+fn main() {
+    let settings = Settings::from_args().unwrap_or_else(|err| {
+        if !err.is_empty() {
+            eprintln!("{}\n", err);
+        }
+        eprintln!("{}", USAGE);
+        exit(2);
+    });
+
+    let mut state = GameState::new();
+    state.set_num_players(settings.players);
+    state.gravity = settings.gravity;
+    state.seed = settings.seed;
+    state.city = settings.city;
+    state.new_round();
+    if let Some(wind) = settings.wind {
+        state.wind = wind;
+    }
+
+    let shooter = settings.shooter - 1;
+    let mut steps = Vec::new();
+    let shot = trace_shot(
+        &mut state,
+        shooter,
+        settings.angle,
+        settings.velocity,
+        |t, shot| steps.push((t, shot.x, shot.y)),
+    );
+    match print(&mut io::stdout().lock(), &settings, &state, &steps, &shot) {
+        Ok(()) => {}
+        // Piped into `head` and cut off: nothing left to say.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("gorillas-sim: {}", err);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gorillas::fly_shot;
+
+    #[test]
+    fn a_banana_that_never_comes_down_is_lost() {
+        let mut state = GameState::new();
+        state.gravity = 0.0;
+        state.new_round();
+        state.wind = 0;
+        let shot = fly_shot(&mut state, 0, 90.0, 0.0);
+        assert_eq!(outcome(&shot), ("lost", None));
+
+        state.gravity = 9.8;
+        let shot = fly_shot(&mut state, 0, 30.0, 1000.0);
+        assert_eq!(outcome(&shot), ("offscreen", None));
+    }
+
+    #[test]
+    fn numbers_that_are_not_finite_are_refused() {
+        for value in ["NaN", "inf", "-inf", "1e39"] {
+            assert!(parse_finite("--gravity", value).is_err(), "{}", value);
+        }
+        assert_eq!(parse_finite("angle", "-12.5"), Ok(-12.5));
+    }
+}
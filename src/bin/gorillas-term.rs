@@ -0,0 +1,402 @@
+//! Gorillas in a text terminal, for playing over SSH or anywhere else
+//! without a window.
+//!
+//! The city, the gorillas, the sun and the banana are drawn in half-block
+//! characters by `gorillas::term`, and the picture follows the terminal as
+//! it is resized. Throws are typed as in the window: an angle, Enter, a
+//! velocity, Enter. The match is kept by the same `Match` the other front
+//! ends use, and bananas fly on the same fixed clock.
+//!
+//! ```bash
+//! cargo run --release --features terminal --bin gorillas-term -- --games 5 Alice Bob
+//! ```
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, style, terminal};
+use gorillas::display::DisplayMode;
+use gorillas::game::Match;
+use gorillas::input::{DEFAULT_ANGLE, DEFAULT_VELOCITY};
+use gorillas::term::{self, Scene};
+use gorillas::{
+    explosion_point, step_shot, GameState, ShotPhase, ShotState, SimClock, EXPLOSION_RADIUS,
+    MAX_PLAYERS, MIN_PLAYERS,
+};
+use std::io::{self, Stdout, Write};
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: gorillas-term [--players N] [--games N] [--gravity G] [--seed S] \
+[--display ega|cga|mono] [<name>...]
+
+Players without a name on the command line are called Player 1, Player 2
+and so on. Type each throw's angle and velocity, pressing Enter after each;
+Escape quits.";
+
+// How often the picture is redrawn while something moves.
+const FRAME: Duration = Duration::from_millis(16);
+const EXPLOSION_TIME: Duration = Duration::from_millis(400);
+// How long the winner of a round is announced before the next map.
+const ROUND_OVER_TIME: Duration = Duration::from_millis(2000);
+
+struct Settings {
+    players: usize,
+    games: i32,
+    gravity: f32,
+    seed: Option<u32>,
+    display: DisplayMode,
+    names: Vec<String>,
+}
+
+impl Settings {
+    fn from_args() -> Result<Self, String> {
+        let mut settings = Settings {
+            players: MIN_PLAYERS,
+            games: 3,
+            gravity: 9.8,
+            seed: None,
+            display: DisplayMode::Ega,
+            names: Vec::new(),
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--players" => settings.players = parse(&arg, &value(&arg)?)?,
+                "--games" => settings.games = parse(&arg, &value(&arg)?)?,
+                "--gravity" => settings.gravity = parse(&arg, &value(&arg)?)?,
+                "--seed" => settings.seed = Some(parse(&arg, &value(&arg)?)?),
+                "--display" => {
+                    let name = value(&arg)?;
+                    settings.display = DisplayMode::from_name(&name)
+                        .ok_or_else(|| format!("unknown display '{}'", name))?
+                }
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => settings.names.push(arg),
+            }
+        }
+        settings.players = settings.players.max(settings.names.len());
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&settings.players) {
+            return Err(format!(
+                "--players must be from {} to {}",
+                MIN_PLAYERS, MAX_PLAYERS
+            ));
+        }
+        if settings.games < 1 {
+            return Err("--games must be at least 1".to_string());
+        }
+        Ok(settings)
+    }
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("bad value '{}' for {}", value, option))
+}
+
+// The terminal in raw mode on its alternate screen, put back as it was when
+// dropped, even if the game panics.
+struct Terminal {
+    out: Stdout,
+    mode: DisplayMode,
+    // Size at the last frame, to notice it being resized.
+    size: (u16, u16),
+}
+
+impl Terminal {
+    fn open(mode: DisplayMode) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Terminal {
+            out,
+            mode,
+            size: (0, 0),
+        })
+    }
+
+    // Draws the scores along the top, the scene below them and `status` on
+    // the bottom line, at the terminal's current size.
+    fn draw(&mut self, scene: &Scene, status: &str) -> io::Result<()> {
+        let size = terminal::size()?;
+        if size != self.size {
+            // Lines the terminal rewrapped would otherwise linger.
+            queue!(self.out, terminal::Clear(terminal::ClearType::All))?;
+            self.size = size;
+        }
+        let (columns, rows) = (size.0 as usize, size.1 as usize);
+        let picture = term::render(scene, columns, rows.saturating_sub(2));
+        queue!(self.out, cursor::MoveTo(0, 0), style::ResetColor)?;
+        write!(
+            self.out,
+            "{}",
+            fit(&scoreline(scene.state, columns), columns)
+        )?;
+        for (row, line) in picture.iter().enumerate() {
+            queue!(self.out, cursor::MoveTo(0, row as u16 + 1))?;
+            write!(self.out, "{}", line)?;
+        }
+        queue!(self.out, cursor::MoveTo(0, rows.saturating_sub(1) as u16))?;
+        write!(self.out, "{}", fit(status, columns))?;
+        self.out.flush()
+    }
+
+    fn scene<'a>(&self, state: &'a GameState) -> Scene<'a> {
+        Scene {
+            state,
+            mode: self.mode,
+            banana: None,
+            explosion: None,
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// `text` cut or padded to exactly `columns` characters, so it overwrites
+// whatever the last frame left on its line.
+fn fit(text: &str, columns: usize) -> String {
+    format!("{:<1$.1$}", text, columns)
+}
+
+// Names and scores: in the corners for a duel, as the original had them,
+// and in a row otherwise.
+fn scoreline(state: &GameState, columns: usize) -> String {
+    if state.num_players() == 2 {
+        let (left, right) = (&state.player_names[0], &state.player_names[1]);
+        let score = format!("{}>Score<{}", state.total_wins[0], state.total_wins[1]);
+        let middle = columns.saturating_sub(left.len() + right.len() + 2);
+        format!(" {}{:^width$}{} ", left, score, right, width = middle)
+    } else {
+        let scores: Vec<String> = (0..state.num_players())
+            .map(|player| {
+                let team = state.teams[player];
+                format!("{} {}", state.player_names[player], state.total_wins[team])
+            })
+            .collect();
+        format!("{:^1$}", scores.join("   "), columns)
+    }
+}
+
+fn wind_arrow(wind: i32) -> String {
+    let shaft = "-".repeat((wind.unsigned_abs() as usize).div_ceil(3));
+    match wind {
+        0 => "none".to_string(),
+        w if w > 0 => format!("{}>", shaft),
+        _ => format!("<{}", shaft),
+    }
+}
+
+// Key presses since the last call, waiting up to `timeout` for the first.
+// Escape and Ctrl+C come back as `None`.
+fn keys(timeout: Duration) -> io::Result<Option<Vec<KeyEvent>>> {
+    let mut keys = Vec::new();
+    let mut wait = timeout;
+    while event::poll(wait)? {
+        wait = Duration::ZERO;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Esc || ctrl_c {
+            return Ok(None);
+        }
+        keys.push(key);
+    }
+    Ok(Some(keys))
+}
+
+// Reads the next throw as the window does: digits and a point, Backspace to
+// correct, Enter to go on. An empty answer means `DEFAULT_ANGLE` or
+// `DEFAULT_VELOCITY`.
+fn read_throw(term: &mut Terminal, game: &Match) -> io::Result<Option<(f32, f32)>> {
+    let state = &game.state;
+    let name = &state.player_names[game.current_player];
+    let mut angle = None;
+    let mut input = String::new();
+    let started = Instant::now();
+    loop {
+        let cursor = if (started.elapsed().as_secs_f64() * 2.0) as i32 % 2 == 0 {
+            "_"
+        } else {
+            " "
+        };
+        let status = match angle {
+            None => format!(" {}  Angle: {}{}", name, input, cursor),
+            Some(angle) => format!(" {}  Angle: {}  Velocity: {}{}", name, angle, input, cursor),
+        };
+        let status = format!("{}   Wind: {}", status, wind_arrow(state.wind));
+        term.draw(&term.scene(state), &status)?;
+
+        let Some(keys) = keys(Duration::from_millis(250))? else {
+            return Ok(None);
+        };
+        for key in keys {
+            match key.code {
+                KeyCode::Char(c) if c.is_ascii_digit() || c == '.' => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => match angle {
+                    None => {
+                        angle = Some(input.parse().unwrap_or(DEFAULT_ANGLE));
+                        input.clear();
+                    }
+                    Some(angle) => {
+                        return Ok(Some((angle, input.parse().unwrap_or(DEFAULT_VELOCITY))))
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+// Flies the throw a step per tick of the game clock, drawing the banana
+// between steps, and then lets off its explosion. Returns the finished shot,
+// or `None` if the player quit.
+fn fly(
+    term: &mut Terminal,
+    game: &mut Match,
+    angle: f32,
+    velocity: f32,
+) -> io::Result<Option<ShotState>> {
+    let shooter = game.current_player;
+    let state = &mut game.state;
+    let mut shot = ShotState::new();
+    shot.aim(state.faces_left(shooter), angle, velocity);
+    state.sun_hit = false;
+    let name = state.player_names[shooter].clone();
+    let status = format!(" {}  Angle: {}  Velocity: {}", name, angle, velocity);
+
+    let mut clock = SimClock::default();
+    let mut last = Instant::now();
+    while shot.phase == ShotPhase::Animating {
+        let now = Instant::now();
+        step_shot(
+            state,
+            &mut shot,
+            shooter,
+            clock.advance((now - last).as_secs_f64()),
+        );
+        last = now;
+
+        let mut scene = term.scene(state);
+        if shot.banana_visible() {
+            scene.banana = Some(shot.lerp_position(clock.alpha()));
+        }
+        term.draw(&scene, &status)?;
+        if keys(FRAME)?.is_none() {
+            return Ok(None);
+        }
+    }
+
+    if let Some((x, y)) = explosion_point(&shot) {
+        let started = Instant::now();
+        while started.elapsed() < EXPLOSION_TIME {
+            let grown = started.elapsed().as_secs_f32() / EXPLOSION_TIME.as_secs_f32();
+            let mut scene = term.scene(state);
+            scene.explosion = Some((x, y, EXPLOSION_RADIUS * grown.min(1.0)));
+            term.draw(&scene, &status)?;
+            if keys(FRAME)?.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(shot))
+}
+
+// Plays the match until it's over or the player quits.
+fn play(term: &mut Terminal, game: &mut Match) -> io::Result<()> {
+    while !game.is_over() {
+        game.deal();
+        let Some((angle, velocity)) = read_throw(term, game)? else {
+            return Ok(());
+        };
+        let Some(shot) = fly(term, game, angle, velocity)? else {
+            return Ok(());
+        };
+        let Some(winner) = game.resolve(&shot) else {
+            continue;
+        };
+
+        let winners: Vec<&str> = game
+            .state
+            .team_members(winner)
+            .map(|player| game.state.player_names[player].as_str())
+            .collect();
+        let status = format!(" {} won the round", winners.join(" and "));
+        let shown = Instant::now();
+        while shown.elapsed() < ROUND_OVER_TIME {
+            term.draw(&term.scene(&game.state), &status)?;
+            match keys(Duration::from_millis(100))? {
+                None => return Ok(()),
+                Some(keys) if !keys.is_empty() => break,
+                Some(_) => {}
+            }
+        }
+    }
+
+    loop {
+        term.draw(&term.scene(&game.state), " GAME OVER!  Press any key")?;
+        match keys(Duration::from_millis(250))? {
+            Some(keys) if keys.is_empty() => {}
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn main() {
+    let settings = Settings::from_args().unwrap_or_else(|err| {
+        if !err.is_empty() {
+            eprintln!("{}\n", err);
+        }
+        eprintln!("{}", USAGE);
+        exit(2);
+    });
+
+    let mut state = GameState::new();
+    state.set_num_players(settings.players);
+    for (player, name) in settings.names.iter().enumerate() {
+        state.player_names[player] = name.clone();
+    }
+    state.num_games = settings.games;
+    state.gravity = settings.gravity;
+    state.city = settings.display.city();
+    state.seed = settings.seed.unwrap_or_else(|| {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        now.subsec_nanos() ^ now.as_secs() as u32
+    });
+    let mut game = Match::new(state);
+
+    let played = Terminal::open(settings.display).and_then(|mut term| play(&mut term, &mut game));
+    match played {
+        Ok(()) => {
+            let state = &game.state;
+            for team in 0..state.num_teams() {
+                let names: Vec<&str> = state
+                    .team_members(team)
+                    .map(|player| state.player_names[player].as_str())
+                    .collect();
+                println!("{}: {}", names.join(" and "), state.total_wins[team]);
+            }
+        }
+        Err(err) => {
+            eprintln!("Terminal error: {}", err);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Round-robin tournaments between bots, without a window.
+//!
+//! Every entrant plays every other one a few times, swapping sides between
+//! matches, and the standings are printed at the end. Entrants are commands
+//! speaking the JSON-lines protocol in `gorillas::bot`, or one of the
+//! built-in computer players as `ai:beginner` up to `ai:expert`.
+//!
+//! ```bash
+//! cargo run --release --bin gorillas-tournament -- --games 5 \
+//!     "python3 my_bot.py" "./other-bot --fast" ai:expert
+//! ```
+
+use gorillas::ai::{AiPlayer, Difficulty};
+use gorillas::bot::{BotError, ExternalBot, DEFAULT_TIMEOUT};
+use gorillas::fairness::DEFAULT_MIN_FAIRNESS;
+use gorillas::game::Match;
+use gorillas::{GameState, Rng};
+use std::process::exit;
+use std::time::Duration;
+
+// A round nobody can win is called off after this many throws, and the
+// match is decided on the rounds played so far.
+const MAX_THROWS_PER_ROUND: u32 = 200;
+
+const USAGE: &str = "usage: gorillas-tournament [--matches N] [--games N] [--gravity G] \
+[--seed S] [--timeout SECS] [--fair] <bot> <bot> [<bot>...]
+
+Each <bot> is a command line (quote it if it has arguments) or one of
+ai:beginner, ai:intermediate, ai:advanced and ai:expert.";
+
+struct Settings {
+    matches: u32,
+    games: i32,
+    gravity: f32,
+    seed: Option<u32>,
+    timeout: Duration,
+    fair: bool,
+    entrants: Vec<String>,
+}
+
+impl Settings {
+    fn from_args() -> Result<Self, String> {
+        let mut settings = Settings {
+            matches: 2,
+            games: 3,
+            gravity: 9.8,
+            seed: None,
+            timeout: DEFAULT_TIMEOUT,
+            fair: false,
+            entrants: Vec::new(),
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--matches" => settings.matches = parse(&arg, &value(&arg)?)?,
+                "--games" => settings.games = parse(&arg, &value(&arg)?)?,
+                "--gravity" => settings.gravity = parse(&arg, &value(&arg)?)?,
+                "--seed" => settings.seed = Some(parse(&arg, &value(&arg)?)?),
+                "--timeout" => {
+                    let secs: f32 = parse(&arg, &value(&arg)?)?;
+                    settings.timeout = Duration::from_secs_f32(secs.max(0.0));
+                }
+                "--fair" => settings.fair = true,
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => settings.entrants.push(arg),
+            }
+        }
+        if settings.entrants.len() < 2 {
+            return Err("need at least two bots".to_string());
+        }
+        if settings.matches == 0 || settings.games < 1 {
+            return Err("--matches and --games must be at least 1".to_string());
+        }
+        Ok(settings)
+    }
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("bad value '{}' for {}", value, option))
+}
+
+// The built-in level named by `ai:<level>`, by name or number.
+fn builtin(entrant: &str) -> Option<Difficulty> {
+    let level = entrant.strip_prefix("ai:")?;
+    Difficulty::ALL.into_iter().find(|difficulty| {
+        difficulty.name().eq_ignore_ascii_case(level) || difficulty.level().to_string() == level
+    })
+}
+
+enum Seat {
+    Builtin(AiPlayer),
+    External(ExternalBot),
+}
+
+impl Seat {
+    fn new(entrant: &str, seed: u64, timeout: Duration) -> Result<Self, BotError> {
+        match builtin(entrant) {
+            Some(difficulty) => Ok(Seat::Builtin(AiPlayer::new(difficulty, seed))),
+            None => ExternalBot::spawn(entrant, timeout).map(Seat::External),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Standing {
+    played: u32,
+    won: u32,
+    drawn: u32,
+    lost: u32,
+    rounds_for: i32,
+    rounds_against: i32,
+    errors: u32,
+}
+
+impl Standing {
+    fn points(&self) -> u32 {
+        self.won * 2 + self.drawn
+    }
+}
+
+struct MatchResult {
+    rounds: [i32; 2],
+    // The side whose bot failed, and how. It loses the match.
+    failure: Option<(usize, BotError)>,
+}
+
+fn play_match(entrants: [&str; 2], settings: &Settings, seed: u32) -> MatchResult {
+    let mut state = GameState::new();
+    state.seed = seed;
+    state.num_games = settings.games;
+    state.gravity = settings.gravity;
+    state.player_names = entrants.iter().map(|e| e.to_string()).collect();
+    if settings.fair {
+        state.min_fairness = Some(DEFAULT_MIN_FAIRNESS);
+    }
+
+    let mut seats = Vec::new();
+    for (side, entrant) in entrants.iter().enumerate() {
+        match Seat::new(entrant, seed as u64 + side as u64, settings.timeout) {
+            Ok(seat) => seats.push(seat),
+            Err(err) => {
+                return MatchResult {
+                    rounds: [0, 0],
+                    failure: Some((side, err)),
+                }
+            }
+        }
+    }
+
+    let mut game = Match::new(state);
+    let mut throws = 0;
+    while !game.is_over() && throws < MAX_THROWS_PER_ROUND {
+        // Deal the next round's map before anyone aims at it.
+        game.deal();
+        let shooter = game.next_thrower();
+        let throw = match &mut seats[shooter] {
+            Seat::Builtin(ai) => Ok(ai.choose_throw(&game.state, shooter)),
+            Seat::External(bot) => bot.choose_throw(&game.state, shooter),
+        };
+        let (angle, velocity) = match throw {
+            Ok(throw) => throw,
+            Err(err) => {
+                return MatchResult {
+                    rounds: [game.state.total_wins[0], game.state.total_wins[1]],
+                    failure: Some((shooter, err)),
+                }
+            }
+        };
+
+        let Some((shot, winner)) = game.throw(angle, velocity) else {
+            break;
+        };
+        match &mut seats[shooter] {
+            Seat::Builtin(ai) => ai.observe(&game.state, shooter, &shot),
+            Seat::External(bot) => bot.observe(&shot),
+        }
+        throws = if winner.is_some() { 0 } else { throws + 1 };
+    }
+
+    MatchResult {
+        rounds: [game.state.total_wins[0], game.state.total_wins[1]],
+        failure: None,
+    }
+}
+
+fn main() {
+    let settings = Settings::from_args().unwrap_or_else(|err| {
+        if !err.is_empty() {
+            eprintln!("{}\n", err);
+        }
+        eprintln!("{}", USAGE);
+        exit(2);
+    });
+
+    let seed = settings.seed.unwrap_or_else(|| {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        now.subsec_nanos() ^ now.as_secs() as u32
+    });
+    println!("Tournament seed {}", seed);
+    let mut rng = Rng::new(seed as u64);
+
+    let count = settings.entrants.len();
+    let mut standings: Vec<Standing> = (0..count).map(|_| Standing::default()).collect();
+    let mut match_number = 0;
+    for first in 0..count {
+        for second in first + 1..count {
+            for leg in 0..settings.matches {
+                // Swap sides every match so nobody always throws first.
+                let sides = if leg % 2 == 0 {
+                    [first, second]
+                } else {
+                    [second, first]
+                };
+                let names = sides.map(|i| settings.entrants[i].as_str());
+                let map_seed = rng.next_u32();
+                let result = play_match(names, &settings, map_seed);
+
+                match_number += 1;
+                print!(
+                    "Match {}: {} {} - {} {} (seed {})",
+                    match_number, names[0], result.rounds[0], result.rounds[1], names[1], map_seed
+                );
+                let winner = match &result.failure {
+                    Some((side, err)) => {
+                        print!(", {} forfeits: {}", names[*side], err);
+                        standings[sides[*side]].errors += 1;
+                        Some(1 - side)
+                    }
+                    None if result.rounds[0] > result.rounds[1] => Some(0),
+                    None if result.rounds[1] > result.rounds[0] => Some(1),
+                    None => None,
+                };
+                println!();
+
+                for side in 0..2 {
+                    let standing = &mut standings[sides[side]];
+                    standing.played += 1;
+                    standing.rounds_for += result.rounds[side];
+                    standing.rounds_against += result.rounds[1 - side];
+                    match winner {
+                        Some(w) if w == side => standing.won += 1,
+                        Some(_) => standing.lost += 1,
+                        None => standing.drawn += 1,
+                    }
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by_key(|&i| {
+        let s = &standings[i];
+        std::cmp::Reverse((s.points(), s.rounds_for - s.rounds_against, s.rounds_for))
+    });
+
+    let width = settings
+        .entrants
+        .iter()
+        .map(|e| e.len())
+        .max()
+        .unwrap_or(0)
+        .max(3);
+    println!();
+    println!(
+        "{:>2}  {:<width$}  {:>3} {:>3} {:>3} {:>3} {:>4} {:>7} {:>6}",
+        "#", "Bot", "P", "W", "D", "L", "Pts", "Rounds", "Errors"
+    );
+    for (rank, &i) in order.iter().enumerate() {
+        let s = &standings[i];
+        println!(
+            "{:>2}  {:<width$}  {:>3} {:>3} {:>3} {:>3} {:>4} {:>7} {:>6}",
+            rank + 1,
+            settings.entrants[i],
+            s.played,
+            s.won,
+            s.drawn,
+            s.lost,
+            s.points(),
+            format!("{}-{}", s.rounds_for, s.rounds_against),
+            s.errors
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    // The value of `"<name>":` in some JSON, arrays and objects included.
+    fn member<'a>(json: &'a str, name: &str) -> &'a str {
+        let key = format!("\"{}\":", name);
+        let rest = &json[json.find(&key).unwrap() + key.len()..];
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '[' | '{' => depth += 1,
+                ']' | '}' if depth == 0 => return &rest[..i],
+                ']' | '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return &rest[..=i];
+                    }
+                }
+                ',' if depth == 0 => return &rest[..i],
+                _ => {}
+            }
+        }
+        rest
+    }
+
+    // The elements of a JSON array.
+    fn elements(array: &str) -> Vec<&str> {
+        let inner = &array[1..array.len() - 1];
+        let mut elements = Vec::new();
+        let (mut depth, mut start) = (0, 0);
+        for (i, c) in inner.char_indices() {
+            match c {
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    elements.push(&inner[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if !inner.is_empty() {
+            elements.push(&inner[start..]);
+        }
+        elements
+    }
+
+    fn number(json: &str, name: &str) -> f32 {
+        member(json, name).parse().unwrap()
+    }
+
+    #[test]
+    fn bots_see_the_map_they_throw_on() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let log = dir.join(format!("gorillas-bot-{}.log", id));
+        let script = dir.join(format!("gorillas-bot-{}.sh", id));
+        // Writes down every question and always throws the same banana.
+        std::fs::write(
+            &script,
+            format!(
+                "while read line; do\n  echo \"$line\" >> {}\n  \
+                 echo '{{\"angle\": 45, \"velocity\": 60}}'\ndone\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        let bot = format!("sh {}", script.display());
+        let settings = Settings {
+            matches: 1,
+            games: 2,
+            gravity: 9.8,
+            seed: None,
+            timeout: DEFAULT_TIMEOUT,
+            fair: false,
+            entrants: Vec::new(),
+        };
+        // A beginner misses often, so later questions show craters.
+        let result = play_match([&bot, "ai:beginner"], &settings, 42);
+        let questions = std::fs::read_to_string(&log).unwrap();
+        std::fs::remove_file(&log).unwrap();
+        std::fs::remove_file(&script).unwrap();
+        assert!(result.failure.is_none());
+        assert_eq!(result.rounds.iter().sum::<i32>(), 2);
+
+        // Play the match again, checking each question against the state
+        // the bot was asked about. Both players throw just as they did.
+        let mut state = GameState::new();
+        state.seed = 42;
+        state.num_games = settings.games;
+        state.gravity = settings.gravity;
+        let mut game = Match::new(state);
+        let mut opponent = AiPlayer::new(Difficulty::Beginner, 43);
+        let mut questions = questions.lines();
+        let mut maps = Vec::new();
+        while !game.is_over() {
+            game.deal();
+            let shooter = game.next_thrower();
+            let (angle, velocity) = if shooter == 0 {
+                let question = questions.next().expect("the bot was asked");
+                let state = &game.state;
+                assert_eq!(member(question, "map"), state.seed.to_string());
+                let buildings = elements(member(question, "buildings"));
+                assert_eq!(buildings.len(), state.buildings.len());
+                for (seen, building) in buildings.iter().zip(&state.buildings) {
+                    assert_eq!(
+                        [
+                            number(seen, "x"),
+                            number(seen, "y"),
+                            number(seen, "width"),
+                            number(seen, "height")
+                        ],
+                        [building.x, building.y, building.width, building.height]
+                    );
+                    let craters = elements(member(seen, "craters"));
+                    assert_eq!(craters.len(), building.craters.len());
+                    for (seen, crater) in craters.iter().zip(&building.craters) {
+                        assert_eq!(
+                            [number(seen, "x"), number(seen, "y"), number(seen, "radius")],
+                            [crater.x, crater.y, crater.radius]
+                        );
+                    }
+                }
+                if !maps.contains(&state.seed) {
+                    maps.push(state.seed);
+                }
+                (45.0, 60.0)
+            } else {
+                opponent.choose_throw(&game.state, shooter)
+            };
+            let (shot, _) = game.throw(angle, velocity).unwrap();
+            if shooter == 1 {
+                opponent.observe(&game.state, shooter, &shot);
+            }
+        }
+        assert_eq!(questions.next(), None);
+        // Both rounds were asked about, each on its own map.
+        assert_eq!(maps.len(), 2);
+    }
+}
@@ -0,0 +1,408 @@
+//! Gorillas played by outside programs.
+//!
+//! A bot is any program that reads JSON lines on stdin and writes JSON lines
+//! on stdout. When it is the bot's turn it gets one line describing the
+//! match as the player would see it:
+//!
+//! ```text
+//! {"you":1,"gravity":9.8,"wind":-4,"width":640,"height":350,"map":1234567,
+//!  "gorillas":[{"x":97,"y":183,"team":0,"name":"Alice"},...],
+//!  "buildings":[{"x":0,"y":221,"width":48,"height":129,"craters":[]},...],
+//!  "scores":[0,1],"history":[{"map":1234567,"angle":45,"velocity":60,
+//!  "hit":null,"x":412.5,"y":240.1},...]}
+//! ```
+//!
+//! (all on one line). Coordinates are in the 640x350 playfield with y
+//! growing downwards; gorillas are 25 units wide and tall, measured from
+//! their top-left corner. `history` holds this bot's own throws in the
+//! match, each with the seed of the map it was thrown on, the player it hit
+//! if any, and where the banana came down (`null` if it flew off screen).
+//!
+//! The bot answers with a line like `{"angle": 45, "velocity": 60}`, as the
+//! throw would be typed into the prompts. Extra members are ignored. A bot
+//! that takes longer than its timeout to answer, or answers with something
+//! else, has failed; what happens then is up to the caller.
+
+use crate::{explosion_point, GameState, ShotState, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum BotError {
+    Spawn(std::io::Error),
+    Timeout(Duration),
+    /// The bot closed its stdout or could not be written to.
+    Exited,
+    BadReply(String),
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BotError::Spawn(err) => write!(f, "could not start bot: {}", err),
+            BotError::Timeout(timeout) => {
+                write!(f, "bot did not answer within {:.1}s", timeout.as_secs_f32())
+            }
+            BotError::Exited => write!(f, "bot exited"),
+            BotError::BadReply(reason) => write!(f, "bad reply from bot: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+// One of the bot's own throws and how it went.
+struct Throw {
+    map: u32,
+    angle: f32,
+    velocity: f32,
+    hit: Option<usize>,
+    landed: Option<(f32, f32)>,
+}
+
+pub struct ExternalBot {
+    pub command: String,
+    pub timeout: Duration,
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+    // When the question in flight must be answered by, and the map it is
+    // about.
+    deadline: Option<Instant>,
+    asked_map: u32,
+    // The throw answered last, waiting for `observe` to say how it went.
+    thrown: Option<Throw>,
+    history: Vec<Throw>,
+}
+
+impl ExternalBot {
+    /// Starts `command`, split on whitespace into the program and its
+    /// arguments. The bot's stderr goes to ours.
+    pub fn spawn(command: &str, timeout: Duration) -> Result<Self, BotError> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or_else(|| {
+            BotError::Spawn(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "empty command",
+            ))
+        })?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(BotError::Spawn)?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(ExternalBot {
+            command: command.to_string(),
+            timeout,
+            child,
+            stdin,
+            replies,
+            deadline: None,
+            asked_map: 0,
+            thrown: None,
+            history: Vec::new(),
+        })
+    }
+
+    /// Asks for `shooter`'s next throw and waits for the answer.
+    pub fn choose_throw(
+        &mut self,
+        state: &GameState,
+        shooter: usize,
+    ) -> Result<(f32, f32), BotError> {
+        self.ask(state, shooter)?;
+        let reply = match self.replies.recv_timeout(self.timeout) {
+            Ok(reply) => reply,
+            Err(RecvTimeoutError::Timeout) => return Err(BotError::Timeout(self.timeout)),
+            Err(RecvTimeoutError::Disconnected) => return Err(BotError::Exited),
+        };
+        self.answer(&reply)
+    }
+
+    /// Sends the observation for `shooter`'s turn without waiting, for
+    /// callers that can't block. Collect the answer with `poll_throw`.
+    pub fn ask(&mut self, state: &GameState, shooter: usize) -> Result<(), BotError> {
+        // Anything left over is the late answer to an earlier question.
+        while self.replies.try_recv().is_ok() {}
+
+        let mut line = observation(state, shooter, &self.history);
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| BotError::Exited)?;
+        self.deadline = Some(Instant::now() + self.timeout);
+        self.asked_map = state.seed;
+        Ok(())
+    }
+
+    /// The answer to the last `ask`, once it has arrived.
+    pub fn poll_throw(&mut self) -> Option<Result<(f32, f32), BotError>> {
+        let deadline = self.deadline?;
+        match self.replies.try_recv() {
+            Ok(reply) => Some(self.answer(&reply)),
+            Err(TryRecvError::Empty) if Instant::now() > deadline => {
+                self.deadline = None;
+                Some(Err(BotError::Timeout(self.timeout)))
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.deadline = None;
+                Some(Err(BotError::Exited))
+            }
+        }
+    }
+
+    /// Whether a question is waiting for its answer.
+    pub fn is_thinking(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    /// Records where the bot's last throw ended up, for its history.
+    pub fn observe(&mut self, shot_state: &ShotState) {
+        if let Some(mut throw) = self.thrown.take() {
+            throw.hit = shot_state.player_hit;
+            throw.landed = explosion_point(shot_state);
+            self.history.push(throw);
+        }
+    }
+
+    fn answer(&mut self, reply: &str) -> Result<(f32, f32), BotError> {
+        self.deadline = None;
+        let (angle, velocity) = parse_reply(reply).map_err(BotError::BadReply)?;
+        self.thrown = Some(Throw {
+            map: self.asked_map,
+            angle,
+            velocity,
+            hit: None,
+            landed: None,
+        });
+        Ok((angle, velocity))
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn observation(state: &GameState, shooter: usize, history: &[Throw]) -> String {
+    let gorillas: Vec<String> = (0..state.num_players())
+        .map(|i| {
+            format!(
+                "{{\"x\":{},\"y\":{},\"team\":{},\"name\":{}}}",
+                state.gorilla_x[i],
+                state.gorilla_y[i],
+                state.teams[i],
+                json_string(&state.player_names[i])
+            )
+        })
+        .collect();
+    let buildings: Vec<String> = state
+        .buildings
+        .iter()
+        .map(|building| {
+            let craters: Vec<String> = building
+                .craters
+                .iter()
+                .map(|c| format!("{{\"x\":{},\"y\":{},\"radius\":{}}}", c.x, c.y, c.radius))
+                .collect();
+            format!(
+                "{{\"x\":{},\"y\":{},\"width\":{},\"height\":{},\"craters\":[{}]}}",
+                building.x,
+                building.y,
+                building.width,
+                building.height,
+                craters.join(",")
+            )
+        })
+        .collect();
+    let scores: Vec<String> = state.total_wins.iter().map(i32::to_string).collect();
+    let throws: Vec<String> = history
+        .iter()
+        .map(|throw| {
+            let hit = throw.hit.map_or("null".to_string(), |hit| hit.to_string());
+            let (x, y) = throw
+                .landed
+                .map_or(("null".to_string(), "null".to_string()), |(x, y)| {
+                    (x.to_string(), y.to_string())
+                });
+            format!(
+                "{{\"map\":{},\"angle\":{},\"velocity\":{},\"hit\":{},\"x\":{},\"y\":{}}}",
+                throw.map, throw.angle, throw.velocity, hit, x, y
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"you\":{},\"gravity\":{},\"wind\":{},\"width\":{},\"height\":{},\"map\":{},\
+         \"gorillas\":[{}],\"buildings\":[{}],\"scores\":[{}],\"history\":[{}]}}",
+        shooter,
+        state.gravity,
+        state.wind,
+        VIRTUAL_WIDTH,
+        VIRTUAL_HEIGHT,
+        state.seed,
+        gorillas.join(","),
+        buildings.join(","),
+        scores.join(","),
+        throws.join(",")
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Reads `{"angle": 45, "velocity": 60}`. Only a flat object is accepted;
+// members other than the two numbers may hold strings, numbers, booleans or
+// null and are skipped.
+fn parse_reply(line: &str) -> Result<(f32, f32), String> {
+    let mut rest = line.trim();
+    let mut angle = None;
+    let mut velocity = None;
+
+    rest = rest
+        .strip_prefix('{')
+        .ok_or("expected a JSON object")?
+        .trim_start();
+    if let Some(after) = rest.strip_prefix('}') {
+        rest = after;
+    } else {
+        loop {
+            let (key, after) = json_token(rest)?;
+            let key = key
+                .strip_prefix('"')
+                .and_then(|key| key.strip_suffix('"'))
+                .ok_or("expected a member name")?;
+            rest = after
+                .trim_start()
+                .strip_prefix(':')
+                .ok_or("expected ':'")?
+                .trim_start();
+            let (value, after) = json_token(rest)?;
+            match key {
+                "angle" => angle = value.parse::<f32>().ok(),
+                "velocity" => velocity = value.parse::<f32>().ok(),
+                _ => {}
+            }
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            } else if let Some(after) = rest.strip_prefix('}') {
+                rest = after;
+                break;
+            } else {
+                return Err("expected ',' or '}'".to_string());
+            }
+        }
+    }
+    if !rest.trim().is_empty() {
+        return Err("unexpected text after the object".to_string());
+    }
+
+    match (angle, velocity) {
+        (Some(angle), Some(velocity)) if angle.is_finite() && velocity.is_finite() => {
+            Ok((angle, velocity))
+        }
+        _ => Err(format!(
+            "expected numbers for \"angle\" and \"velocity\" in {}",
+            line.trim()
+        )),
+    }
+}
+
+// Splits a string, number or literal off the front of `text`.
+fn json_token(text: &str) -> Result<(&str, &str), String> {
+    if text.starts_with('"') {
+        let mut escaped = false;
+        for (i, c) in text.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return Ok(text.split_at(i + 1)),
+                _ => {}
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    let end = text
+        .find(|c: char| c == ',' || c == '}' || c == ':' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let (token, rest) = text.split_at(end);
+    let simple = matches!(token, "true" | "false" | "null") || token.parse::<f64>().is_ok();
+    if simple {
+        Ok((token, rest))
+    } else {
+        Err(format!("unexpected '{}'", token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_with_two_numbers_are_throws() {
+        assert_eq!(
+            parse_reply(r#"{"angle": 45, "velocity": 60.5}"#),
+            Ok((45.0, 60.5))
+        );
+        assert_eq!(
+            parse_reply(r#" {"note": "lob", "velocity": 30, "angle": 70, "ok": true} "#),
+            Ok((70.0, 30.0))
+        );
+    }
+
+    #[test]
+    fn replies_without_two_finite_numbers_are_refused() {
+        for reply in [
+            r#"{"angle": NaN, "velocity": 60}"#,
+            r#"{"angle": 45, "velocity": nan}"#,
+            r#"{"angle": 45, "velocity": 1e999}"#,
+            r#"{"angle": -inf, "velocity": 60}"#,
+            r#"{"angle": 45}"#,
+            r#"{"velocity": 60}"#,
+            r#"{}"#,
+            r#"{"angle": "45", "velocity": 60}"#,
+            r#"{"angle": null, "velocity": 60}"#,
+            r#"{"angle": 45, "velocity": 60"#,
+            r#"{"angle": 45, "velocity": 60} 7"#,
+            "45 60",
+            "",
+        ] {
+            assert!(parse_reply(reply).is_err(), "accepted {:?}", reply);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Fixed-timestep clock for the banana's flight.
+//!
+//! The original stepped the trajectory once per drawn frame, so flights ran
+//! faster on faster monitors. `SimClock` turns wall time into a whole number
+//! of simulation ticks instead, and reports how far into the next tick we are
+//! so renderers can interpolate between the last two positions.
+
+/// Simulation ticks per second of wall time at game speed 1.0. Each tick
+/// advances the shot by 0.1 time units, which matches the original pace on
+/// 60 Hz screens.
+pub const TICKS_PER_SECOND: f64 = 60.0;
+
+/// Longest frame we will catch up on. Anything longer (a dragged window, a
+/// background tab) is treated as a pause rather than a burst of ticks.
+const MAX_FRAME_TIME: f64 = 0.25;
+
+pub struct SimClock {
+    accumulator: f64,
+    /// Game-speed multiplier on top of the fixed tick rate.
+    pub speed: f64,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl SimClock {
+    pub fn new(speed: f64) -> Self {
+        SimClock {
+            accumulator: 0.0,
+            speed,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+
+    /// Feeds in the wall time of the last frame and returns how many ticks to
+    /// run now.
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        let tick = 1.0 / TICKS_PER_SECOND;
+        self.accumulator += frame_time.clamp(0.0, MAX_FRAME_TIME) * self.speed;
+        let ticks = (self.accumulator / tick).floor();
+        self.accumulator -= ticks * tick;
+        ticks as u32
+    }
+
+    /// How far we are into the next tick, from 0.0 to 1.0.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator * TICKS_PER_SECOND).clamp(0.0, 1.0) as f32
+    }
+}
@@ -0,0 +1,281 @@
+//! The video modes the original ran in.
+//!
+//! QBasic Gorillas drew in EGA's SCREEN 9 when the machine had one, and fell
+//! back to CGA's four-colour SCREEN 1 when it didn't. Each mode had its own
+//! palette and its own building sizes. Monochrome monitors showed the EGA
+//! picture by brightness alone.
+//!
+//! The simulation always runs in the 640x350 playfield, whatever the mode:
+//! the CGA's building sizes are scaled up to it from the original's 320x200
+//! pixels, and a front end scales the picture back down.
+
+use crate::{BUILDING_COLOR_COUNT, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+/// Red, green and blue.
+pub type Rgb = [u8; 3];
+
+/// The EGA's sixteen default colours, in attribute order.
+pub const EGA_COLORS: [Rgb; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xaa],
+    [0x00, 0xaa, 0x00],
+    [0x00, 0xaa, 0xaa],
+    [0xaa, 0x00, 0x00],
+    [0xaa, 0x00, 0xaa],
+    [0xaa, 0x55, 0x00],
+    [0xaa, 0xaa, 0xaa],
+    [0x55, 0x55, 0x55],
+    [0x55, 0x55, 0xff],
+    [0x55, 0xff, 0x55],
+    [0x55, 0xff, 0xff],
+    [0xff, 0x55, 0x55],
+    [0xff, 0x55, 0xff],
+    [0xff, 0xff, 0x55],
+    [0xff, 0xff, 0xff],
+];
+
+/// CGA palette 1 at high intensity: black, cyan, magenta and white.
+pub const CGA_COLORS: [Rgb; 4] = [
+    [0x00, 0x00, 0x00],
+    [0x55, 0xff, 0xff],
+    [0xff, 0x55, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+/// The greys a monochrome monitor shows: off, dim, normal and bright.
+pub const MONO_COLORS: [Rgb; 4] = [
+    [0x00, 0x00, 0x00],
+    [0x55, 0x55, 0x55],
+    [0xaa, 0xaa, 0xaa],
+    [0xff, 0xff, 0xff],
+];
+
+/// Colours of what's in the playfield.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Scheme {
+    pub sky: Rgb,
+    /// Gorillas.
+    pub object: Rgb,
+    pub explosion: Rgb,
+    pub sun: Rgb,
+    /// Indexed by `Building::color`.
+    pub buildings: [Rgb; BUILDING_COLOR_COUNT],
+    pub window_lit: Rgb,
+    pub window_dark: Rgb,
+    pub banana: Rgb,
+}
+
+// The original remapped the EGA palette for the game: `PALETTE 0, 1` for
+// the sky, 46 for the gorillas, 44 for explosions, 54 for the sun and 7, 4
+// and 3 for the buildings. Windows kept the default yellow and dark grey.
+const EGA_SCHEME: Scheme = Scheme {
+    sky: ega64(1),
+    object: ega64(46),
+    explosion: ega64(44),
+    sun: ega64(54),
+    buildings: [ega64(7), ega64(4), ega64(3)],
+    window_lit: EGA_COLORS[14],
+    window_dark: EGA_COLORS[8],
+    banana: EGA_COLORS[14],
+};
+
+// On the CGA every building is magenta, and the windows are white or black.
+// Explosions are cyan so they show up against the buildings they blow up.
+const CGA_SCHEME: Scheme = Scheme {
+    sky: CGA_COLORS[0],
+    object: CGA_COLORS[1],
+    explosion: CGA_COLORS[1],
+    sun: CGA_COLORS[3],
+    buildings: [CGA_COLORS[2]; BUILDING_COLOR_COUNT],
+    window_lit: CGA_COLORS[3],
+    window_dark: CGA_COLORS[0],
+    banana: CGA_COLORS[1],
+};
+
+// The EGA's explosion red comes out the same dim grey as some of the
+// buildings, so explosions are bright instead.
+const MONO_SCHEME: Scheme = Scheme {
+    sky: mono(EGA_SCHEME.sky),
+    object: mono(EGA_SCHEME.object),
+    explosion: MONO_COLORS[3],
+    sun: mono(EGA_SCHEME.sun),
+    buildings: [
+        mono(EGA_SCHEME.buildings[0]),
+        mono(EGA_SCHEME.buildings[1]),
+        mono(EGA_SCHEME.buildings[2]),
+    ],
+    window_lit: mono(EGA_SCHEME.window_lit),
+    window_dark: mono(EGA_SCHEME.window_dark),
+    banana: mono(EGA_SCHEME.banana),
+};
+
+// One of the EGA's 64 colours, as `PALETTE` numbered them: the low three
+// bits are blue, green and red at two thirds, the next three add a third.
+const fn ega64(color: u8) -> Rgb {
+    [
+        ega64_channel(color, 2, 5),
+        ega64_channel(color, 1, 4),
+        ega64_channel(color, 0, 3),
+    ]
+}
+
+const fn ega64_channel(color: u8, high: u8, low: u8) -> u8 {
+    ((color >> high) & 1) * 0xaa + ((color >> low) & 1) * 0x55
+}
+
+// The grey a monochrome monitor shows for a colour, by its brightness.
+const fn mono(rgb: Rgb) -> Rgb {
+    let luma = (299 * rgb[0] as u32 + 587 * rgb[1] as u32 + 114 * rgb[2] as u32) / 1000;
+    MONO_COLORS[((luma + 42) / 85) as usize]
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DisplayMode {
+    #[default]
+    Ega,
+    Cga,
+    Mono,
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [DisplayMode::Ega, DisplayMode::Cga, DisplayMode::Mono];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayMode::Ega => "ega",
+            DisplayMode::Cga => "cga",
+            DisplayMode::Mono => "mono",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DisplayMode::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Pixels across and down.
+    pub fn resolution(self) -> (u32, u32) {
+        match self {
+            DisplayMode::Cga => (320, 200),
+            DisplayMode::Ega | DisplayMode::Mono => (640, 350),
+        }
+    }
+
+    /// Every colour the mode can show.
+    pub fn colors(self) -> &'static [Rgb] {
+        match self {
+            DisplayMode::Ega => &EGA_COLORS,
+            DisplayMode::Cga => &CGA_COLORS,
+            DisplayMode::Mono => &MONO_COLORS,
+        }
+    }
+
+    pub fn scheme(self) -> &'static Scheme {
+        match self {
+            DisplayMode::Ega => &EGA_SCHEME,
+            DisplayMode::Cga => &CGA_SCHEME,
+            DisplayMode::Mono => &MONO_SCHEME,
+        }
+    }
+
+    /// The building sizes the original used in this mode.
+    pub fn city(self) -> CitySizing {
+        match self {
+            DisplayMode::Cga => CitySizing::Cga,
+            DisplayMode::Ega | DisplayMode::Mono => CitySizing::Ega,
+        }
+    }
+
+    /// The colour the mode shows in place of `rgb`: the nearest it has, or
+    /// for monochrome the grey of the same brightness.
+    pub fn nearest(self, rgb: Rgb) -> Rgb {
+        if self == DisplayMode::Mono {
+            return mono(rgb);
+        }
+        let distance = |color: &&Rgb| -> u32 {
+            (0..3)
+                .map(|i| (color[i] as i32 - rgb[i] as i32).pow(2) as u32)
+                .sum()
+        };
+        *self.colors().iter().min_by_key(distance).unwrap()
+    }
+}
+
+/// How big `generate_cityscape` makes buildings and their windows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CitySizing {
+    #[default]
+    Ega,
+    Cga,
+}
+
+/// The original's numbers for building a city, in the pixels of its mode.
+#[derive(Clone, Copy, Debug)]
+pub struct CityDimensions {
+    /// Where the buildings stand.
+    pub bottom_line: f32,
+    /// How much taller or shorter each building gets along the slope.
+    pub height_step: f32,
+    /// Heights the first building starts from, for upward and downward
+    /// slopes.
+    pub low_start: f32,
+    pub high_start: f32,
+    /// Narrowest building; up to as much again is added at random.
+    pub building_width: i32,
+    /// Up to this much height is added at random.
+    pub random_height: i32,
+    pub window_width: f32,
+    pub window_height: f32,
+    pub window_spacing_v: f32,
+    pub window_spacing_h: f32,
+    /// Size of a pixel of this mode in the playfield.
+    pub pixel: (f32, f32),
+}
+
+impl CitySizing {
+    pub fn name(self) -> &'static str {
+        match self {
+            CitySizing::Ega => "ega",
+            CitySizing::Cga => "cga",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [CitySizing::Ega, CitySizing::Cga]
+            .into_iter()
+            .find(|sizing| sizing.name() == name)
+    }
+
+    pub fn dimensions(self) -> CityDimensions {
+        match self {
+            CitySizing::Ega => CityDimensions {
+                bottom_line: 335.0,
+                height_step: 10.0,
+                low_start: 15.0,
+                high_start: 130.0,
+                building_width: 37,
+                random_height: 120,
+                window_width: 3.0,
+                window_height: 6.0,
+                window_spacing_v: 15.0,
+                window_spacing_h: 10.0,
+                pixel: (1.0, 1.0),
+            },
+            // `NewHt = NewHt * 20 \ 35` in the original.
+            CitySizing::Cga => CityDimensions {
+                bottom_line: 190.0,
+                height_step: 6.0,
+                low_start: (15 * 20 / 35) as f32,
+                high_start: (130 * 20 / 35) as f32,
+                building_width: 18,
+                random_height: 54,
+                window_width: 1.0,
+                window_height: 2.0,
+                window_spacing_v: 5.0,
+                window_spacing_h: 4.0,
+                pixel: (VIRTUAL_WIDTH / 320.0, VIRTUAL_HEIGHT / 200.0),
+            },
+        }
+    }
+}
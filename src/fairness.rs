@@ -0,0 +1,53 @@
+//! How evenly a map treats the gorillas.
+//!
+//! The original dealt cities blind, and now and then one gorilla ends up
+//! walled in behind a skyscraper while the other has a clear line. To catch
+//! that, a map is scored by asking the solver for every throw that hits, from
+//! every gorilla at its nearest opponent, and comparing how much room for
+//! error each one gets.
+
+use crate::solver::{self, SolverOptions};
+use crate::GameState;
+
+/// Default for `GameState::min_fairness` when the check is switched on.
+pub const DEFAULT_MIN_FAIRNESS: f32 = 0.5;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fairness {
+    /// How forgiving the map is for each gorilla: the total width of the
+    /// velocity bands that hit, summed over the candidate angles.
+    pub chances: Vec<f32>,
+    /// The weakest gorilla's chances over the strongest's, from 0.0 (someone
+    /// cannot hit at all) to 1.0 (perfectly even).
+    pub score: f32,
+}
+
+// Coarser than the solver's defaults. We only need to compare the sides,
+// and this runs for every map dealt.
+const CANDIDATES: SolverOptions = SolverOptions {
+    min_angle: 5.0,
+    max_angle: 85.0,
+    angle_step: 5.0,
+    max_velocity: 200.0,
+    velocity_step: 0.5,
+};
+
+/// Scores the map in `state` as it stands.
+pub fn evaluate(state: &GameState) -> Fairness {
+    let chances: Vec<f32> = (0..state.num_players())
+        .map(|shooter| {
+            let target = state.nearest_opponent(shooter);
+            solver::solve_with(state, shooter, target, &CANDIDATES).map_or(0.0, |solutions| {
+                solutions
+                    .iter()
+                    .map(|s| s.max_velocity - s.min_velocity + CANDIDATES.velocity_step)
+                    .sum()
+            })
+        })
+        .collect();
+
+    let low = chances.iter().copied().fold(f32::INFINITY, f32::min);
+    let high = chances.iter().copied().fold(0.0, f32::max);
+    let score = if high > 0.0 { low / high } else { 0.0 };
+    Fairness { chances, score }
+}
@@ -0,0 +1,84 @@
+//! Whole matches, played without a window.
+//!
+//! `GameState` knows how to deal maps and fly bananas, but not whose turn it
+//! is or when the match is over; the front-end keeps track of that between
+//! frames. `Match` does the same bookkeeping for code that just wants to feed
+//! in throws, in the same order, so a match played here and one played on
+//! screen end up in the same state.
+
+use crate::{fly_shot, resolve_shot, GameState, ShotState};
+
+#[derive(Clone)]
+pub struct Match {
+    pub state: GameState,
+    pub current_player: usize,
+    /// Rounds played so far.
+    pub current_game: i32,
+    // The last throw ended a round. The next map is dealt when the next
+    // throw comes in, as the front-end does after the victory dance.
+    round_over: bool,
+}
+
+impl Match {
+    /// Starts a match set up like `state` and deals its first map.
+    pub fn new(mut state: GameState) -> Self {
+        state.total_wins.fill(0);
+        state.new_round();
+        Match {
+            state,
+            current_player: 0,
+            current_game: 0,
+            round_over: false,
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.current_game >= self.state.num_games
+    }
+
+    /// Who throws next.
+    pub fn next_thrower(&self) -> usize {
+        if self.round_over {
+            0
+        } else {
+            self.current_player
+        }
+    }
+
+    /// Plays the next throw, dealing a new map first if the last throw ended
+    /// a round. Returns the flown shot and the team that won the round, if
+    /// it ended, or `None` if the match is already over.
+    pub fn throw(&mut self, angle: f32, velocity: f32) -> Option<(ShotState, Option<usize>)> {
+        if self.is_over() {
+            return None;
+        }
+        self.deal();
+        let shot_state = fly_shot(&mut self.state, self.current_player, angle, velocity);
+        let winner = self.resolve(&shot_state);
+        Some((shot_state, winner))
+    }
+
+    /// Deals the next map now if the last throw ended a round, for front
+    /// ends that show the map before the next throw comes in.
+    pub fn deal(&mut self) {
+        if self.round_over {
+            self.round_over = false;
+            self.current_player = 0;
+            self.state.next_round();
+        }
+    }
+
+    /// Applies a throw by `current_player` that the caller flew itself with
+    /// `plot_shot`, after `deal`, and passes the turn on. Returns the team
+    /// that won the round, if it ended.
+    pub fn resolve(&mut self, shot_state: &ShotState) -> Option<usize> {
+        let winner = resolve_shot(&mut self.state, shot_state, self.current_player);
+        if winner.is_some() {
+            self.current_game += 1;
+            self.round_over = true;
+        } else {
+            self.current_player = self.state.next_player(self.current_player);
+        }
+        winner
+    }
+}
@@ -0,0 +1,331 @@
+//! A reinforcement-learning environment in the style of OpenAI Gym.
+//!
+//! `Env` deals a map with `reset(seed)` and takes one throw per `step`,
+//! using the same physics as the game. An episode is one round, so it ends
+//! when a gorilla is hit or after `EnvConfig::max_throws` steps. The agent
+//! plays the left-hand gorilla. With an opponent configured, the built-in
+//! computer player answers every throw that doesn't end the round. Without
+//! one the agent throws for both sides, and each observation says whose
+//! turn it is.
+//!
+//! Rewards are from the point of view of whoever threw the step's banana:
+//! `1.0` for hitting the other gorilla and `-1.0` for hitting yourself or
+//! being hit back. A throw that misses earns a shaping penalty of up to
+//! `-EnvConfig::miss_penalty`, scaled by how far from the target it came
+//! down.
+//!
+//! `VecEnv` steps a batch of environments at once and starts a fresh
+//! episode wherever one ends. The `python` feature exposes both as a Python
+//! extension module; see `python.rs`.
+
+use crate::ai::{AiPlayer, Difficulty};
+use crate::game::Match;
+use crate::{explosion_point, GameState, Rng, ShotState, G_HEIGHT, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+#[cfg(feature = "python")]
+mod python;
+
+/// Points across the playfield at which `Observation::skyline` samples the
+/// buildings.
+pub const SKYLINE_COLUMNS: usize = 64;
+
+// Distance from a gorilla's left edge to the middle of its hit box. Up and
+// down the middle is half of `G_HEIGHT`.
+const G_HALF_WIDTH: f32 = 10.0;
+
+/// Length of `Observation::to_vec`.
+pub const OBSERVATION_LEN: usize = 8 + SKYLINE_COLUMNS;
+
+#[derive(Clone, Debug)]
+pub struct EnvConfig {
+    pub gravity: f32,
+    /// Who throws back, or `None` for self-play.
+    pub opponent: Option<Difficulty>,
+    /// Steps after which the episode is cut off. That is the agent's throws
+    /// against an opponent, and both sides' throws together in self-play.
+    pub max_throws: u32,
+    /// Biggest shaping penalty for a miss. `0.0` leaves only the win and
+    /// loss rewards.
+    pub miss_penalty: f32,
+    /// Passed on to `GameState::min_fairness`.
+    pub min_fairness: Option<f32>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            gravity: 9.8,
+            opponent: None,
+            max_throws: 20,
+            miss_penalty: 0.1,
+            min_fairness: None,
+        }
+    }
+}
+
+/// A throw, as it would be typed into the prompts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Action {
+    pub angle: f32,
+    pub velocity: f32,
+}
+
+/// What the thrower can see before a throw. Coordinates are in the 640x350
+/// playfield with y growing downwards.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Observation {
+    /// The gorilla about to throw.
+    pub player: usize,
+    /// Whether its angles are mirrored, as for the right-hand gorilla.
+    pub faces_left: bool,
+    pub gravity: f32,
+    pub wind: f32,
+    /// Centre of the thrower and of its target.
+    pub thrower: (f32, f32),
+    pub target: (f32, f32),
+    /// Top of the skyline at `SKYLINE_COLUMNS` evenly spaced points, holes
+    /// blown into it included.
+    pub skyline: Vec<f32>,
+}
+
+impl Observation {
+    fn new(state: &GameState, player: usize) -> Self {
+        let centre = |p: usize| {
+            (
+                state.gorilla_x[p] + G_HALF_WIDTH,
+                state.gorilla_y[p] + G_HEIGHT / 2.0,
+            )
+        };
+        let skyline = (0..SKYLINE_COLUMNS)
+            .map(|column| {
+                let x = (column as f32 + 0.5) * VIRTUAL_WIDTH / SKYLINE_COLUMNS as f32;
+                skyline_top(state, x)
+            })
+            .collect();
+        Observation {
+            player,
+            faces_left: state.faces_left(player),
+            gravity: state.gravity,
+            wind: state.wind as f32,
+            thrower: centre(player),
+            target: centre(state.nearest_opponent(player)),
+            skyline,
+        }
+    }
+
+    /// The observation as `OBSERVATION_LEN` numbers, scaled to roughly
+    /// `0.0..=1.0` (wind to `-1.0..=1.0`) for feeding to a network.
+    pub fn to_vec(&self) -> Vec<f32> {
+        let mut features = Vec::with_capacity(OBSERVATION_LEN);
+        features.push(if self.faces_left { 1.0 } else { 0.0 });
+        features.push(self.gravity / 30.0);
+        features.push(self.wind / 15.0);
+        features.push(self.player as f32);
+        features.push(self.thrower.0 / VIRTUAL_WIDTH);
+        features.push(self.thrower.1 / VIRTUAL_HEIGHT);
+        features.push(self.target.0 / VIRTUAL_WIDTH);
+        features.push(self.target.1 / VIRTUAL_HEIGHT);
+        features.extend(self.skyline.iter().map(|y| y / VIRTUAL_HEIGHT));
+        features
+    }
+}
+
+// Where a banana falling straight down at `x` would first meet a building.
+fn skyline_top(state: &GameState, x: f32) -> f32 {
+    let Some(building) = state
+        .buildings
+        .iter()
+        .find(|b| x >= b.x && x < b.x + b.width)
+    else {
+        return VIRTUAL_HEIGHT;
+    };
+    let mut y = building.y;
+    while y < VIRTUAL_HEIGHT && building.is_cratered(x, y) {
+        y += 1.0;
+    }
+    y
+}
+
+pub struct Env {
+    pub config: EnvConfig,
+    game: Match,
+    opponent: Option<AiPlayer>,
+    throws: u32,
+    done: bool,
+}
+
+impl Env {
+    /// A new environment, ready to play the map for seed 0.
+    pub fn new(config: EnvConfig) -> Self {
+        let mut env = Env {
+            config,
+            game: Match::new(GameState::new()),
+            opponent: None,
+            throws: 0,
+            done: false,
+        };
+        env.reset(0);
+        env
+    }
+
+    /// Starts a new episode on the map for `seed`.
+    pub fn reset(&mut self, seed: u32) -> Observation {
+        let mut state = GameState::new();
+        state.seed = seed;
+        state.gravity = self.config.gravity;
+        state.num_games = 1;
+        state.min_fairness = self.config.min_fairness;
+        self.game = Match::new(state);
+        self.opponent = self
+            .config
+            .opponent
+            .map(|difficulty| AiPlayer::new(difficulty, seed as u64));
+        self.throws = 0;
+        self.done = false;
+        self.observation()
+    }
+
+    /// Throws `action` for whoever is up. Returns what the next thrower
+    /// sees, the reward for this throw, and whether the episode is over.
+    /// Stepping a finished episode changes nothing and returns a reward of
+    /// `0.0`.
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool) {
+        if self.done {
+            return (self.observation(), 0.0, true);
+        }
+
+        let shooter = self.game.current_player;
+        let target = self.game.state.nearest_opponent(shooter);
+        let Some((shot, winner)) = self.game.throw(action.angle, action.velocity) else {
+            self.done = true;
+            return (self.observation(), 0.0, true);
+        };
+        self.throws += 1;
+        let mut reward = self.reward(shooter, target, &shot, winner);
+        self.done = winner.is_some();
+
+        if let (false, Some(ai)) = (self.done, self.opponent.as_mut()) {
+            let thrower = self.game.current_player;
+            let (angle, velocity) = ai.choose_throw(&self.game.state, thrower);
+            if let Some((shot, winner)) = self.game.throw(angle, velocity) {
+                ai.observe(&self.game.state, thrower, &shot);
+                if let Some(winner) = winner {
+                    self.done = true;
+                    reward = if winner == self.game.state.teams[shooter] {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                }
+            }
+        }
+
+        if self.throws >= self.config.max_throws {
+            self.done = true;
+        }
+        (self.observation(), reward, self.done)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The match being played, for drawing or inspecting it.
+    pub fn state(&self) -> &GameState {
+        &self.game.state
+    }
+
+    fn observation(&self) -> Observation {
+        Observation::new(&self.game.state, self.game.current_player)
+    }
+
+    fn reward(
+        &self,
+        shooter: usize,
+        target: usize,
+        shot: &ShotState,
+        winner: Option<usize>,
+    ) -> f32 {
+        let state = &self.game.state;
+        if let Some(winner) = winner {
+            return if winner == state.teams[shooter] {
+                1.0
+            } else {
+                -1.0
+            };
+        }
+        // Bananas that leave the screen are judged by where they left it.
+        let (x, y) = explosion_point(shot).unwrap_or((shot.x, shot.y));
+        let (tx, ty) = (
+            state.gorilla_x[target] + G_HALF_WIDTH,
+            state.gorilla_y[target] + G_HEIGHT / 2.0,
+        );
+        let miss = ((x - tx).powi(2) + (y - ty).powi(2)).sqrt() / VIRTUAL_WIDTH;
+        -self.config.miss_penalty * miss.min(1.0)
+    }
+}
+
+/// A batch of environments stepped together.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    // Seeds the episodes started after the first.
+    rng: Rng,
+}
+
+impl VecEnv {
+    pub fn new(config: EnvConfig, count: usize) -> Self {
+        VecEnv {
+            envs: (0..count).map(|_| Env::new(config.clone())).collect(),
+            rng: Rng::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+
+    /// Starts every environment on its own map. The whole batch, including
+    /// the episodes started later on, follows from `seed`.
+    pub fn reset(&mut self, seed: u32) -> Vec<Observation> {
+        self.rng = Rng::new(seed as u64);
+        let rng = &mut self.rng;
+        self.envs
+            .iter_mut()
+            .map(|env| env.reset(rng.next_u32()))
+            .collect()
+    }
+
+    /// Steps environment `i` with `actions[i]`. Where an episode ends, the
+    /// environment is reset and the returned observation is the first of
+    /// the next episode, as with Gym's vector environments.
+    ///
+    /// Panics unless there is one action per environment.
+    pub fn step(&mut self, actions: &[Action]) -> Vec<(Observation, f32, bool)> {
+        assert_eq!(
+            actions.len(),
+            self.envs.len(),
+            "expected one action per environment"
+        );
+        let rng = &mut self.rng;
+        self.envs
+            .iter_mut()
+            .zip(actions)
+            .map(|(env, &action)| {
+                let (observation, reward, done) = env.step(action);
+                if done {
+                    (env.reset(rng.next_u32()), reward, true)
+                } else {
+                    (observation, reward, false)
+                }
+            })
+            .collect()
+    }
+}
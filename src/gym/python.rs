@@ -0,0 +1,154 @@
+//! Python bindings for `Env` and `VecEnv`, built with the `python` feature.
+//!
+//! ```bash
+//! cargo rustc --release --lib --no-default-features --features python --crate-type cdylib
+//! cp target/release/libgorillas.so gorillas.so    # gorillas.pyd on Windows
+//! ```
+//!
+//! ```python
+//! import gorillas
+//! env = gorillas.Env(opponent="expert")
+//! obs = env.reset(seed=42)
+//! obs, reward, done = env.step(45.0, 60.0)
+//! ```
+//!
+//! Observations come back as flat lists of `OBSERVATION_LEN` floats, as
+//! from `Observation::to_vec`.
+
+use super::{Action, Env, EnvConfig, VecEnv, OBSERVATION_LEN};
+use crate::ai::Difficulty;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+type PyStep = (Vec<f32>, f32, bool);
+type PyBatchStep = (Vec<Vec<f32>>, Vec<f32>, Vec<bool>);
+
+fn config(
+    gravity: f32,
+    opponent: Option<&str>,
+    max_throws: u32,
+    miss_penalty: f32,
+    min_fairness: Option<f32>,
+) -> PyResult<EnvConfig> {
+    let opponent = match opponent {
+        None => None,
+        Some(name) => Some(
+            Difficulty::ALL
+                .into_iter()
+                .find(|d| d.name().eq_ignore_ascii_case(name) || d.level().to_string() == name)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown opponent '{}'", name)))?,
+        ),
+    };
+    Ok(EnvConfig {
+        gravity,
+        opponent,
+        max_throws,
+        miss_penalty,
+        min_fairness,
+    })
+}
+
+#[pyclass(name = "Env")]
+struct PyEnv(Env);
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (gravity=9.8, opponent=None, max_throws=20, miss_penalty=0.1, min_fairness=None))]
+    fn new(
+        gravity: f32,
+        opponent: Option<&str>,
+        max_throws: u32,
+        miss_penalty: f32,
+        min_fairness: Option<f32>,
+    ) -> PyResult<Self> {
+        let config = config(gravity, opponent, max_throws, miss_penalty, min_fairness)?;
+        Ok(PyEnv(Env::new(config)))
+    }
+
+    #[pyo3(signature = (seed=0))]
+    fn reset(&mut self, seed: u32) -> Vec<f32> {
+        self.0.reset(seed).to_vec()
+    }
+
+    fn step(&mut self, angle: f32, velocity: f32) -> PyStep {
+        let (observation, reward, done) = self.0.step(Action { angle, velocity });
+        (observation.to_vec(), reward, done)
+    }
+
+    #[getter]
+    fn seed(&self) -> u32 {
+        self.0.state().seed
+    }
+
+    #[classattr]
+    fn observation_size() -> usize {
+        OBSERVATION_LEN
+    }
+}
+
+#[pyclass(name = "VecEnv")]
+struct PyVecEnv(VecEnv);
+
+#[pymethods]
+impl PyVecEnv {
+    #[new]
+    #[pyo3(signature = (count, gravity=9.8, opponent=None, max_throws=20, miss_penalty=0.1, min_fairness=None))]
+    fn new(
+        count: usize,
+        gravity: f32,
+        opponent: Option<&str>,
+        max_throws: u32,
+        miss_penalty: f32,
+        min_fairness: Option<f32>,
+    ) -> PyResult<Self> {
+        let config = config(gravity, opponent, max_throws, miss_penalty, min_fairness)?;
+        Ok(PyVecEnv(VecEnv::new(config, count)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    #[pyo3(signature = (seed=0))]
+    fn reset(&mut self, seed: u32) -> Vec<Vec<f32>> {
+        self.0.reset(seed).iter().map(|o| o.to_vec()).collect()
+    }
+
+    /// Takes one `(angle, velocity)` pair per environment and returns the
+    /// observations, rewards and done flags as three lists.
+    fn step(&mut self, actions: Vec<(f32, f32)>) -> PyResult<PyBatchStep> {
+        if actions.len() != self.0.len() {
+            return Err(PyValueError::new_err(format!(
+                "expected {} actions, got {}",
+                self.0.len(),
+                actions.len()
+            )));
+        }
+        let actions: Vec<Action> = actions
+            .into_iter()
+            .map(|(angle, velocity)| Action { angle, velocity })
+            .collect();
+        let mut observations = Vec::with_capacity(actions.len());
+        let mut rewards = Vec::with_capacity(actions.len());
+        let mut dones = Vec::with_capacity(actions.len());
+        for (observation, reward, done) in self.0.step(&actions) {
+            observations.push(observation.to_vec());
+            rewards.push(reward);
+            dones.push(done);
+        }
+        Ok((observations, rewards, dones))
+    }
+
+    #[classattr]
+    fn observation_size() -> usize {
+        OBSERVATION_LEN
+    }
+}
+
+#[pymodule]
+fn gorillas(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEnv>()?;
+    module.add_class::<PyVecEnv>()?;
+    Ok(())
+}
@@ -0,0 +1,318 @@
+//! Keys, characters and clicks, from the keyboard or from a script.
+//!
+//! The front-end reads its input through `InputSource` once per frame: the
+//! key pressed last, whether a particular key was pressed, the characters
+//! typed and whether the mouse was clicked. The window answers from the
+//! keyboard and mouse. `Script` answers from a file of timed events
+//! instead, so a whole session can be played without anyone at the keys:
+//!
+//! ```text
+//! # Two players, everything else left at its default.
+//! 0.5 key Enter
+//! 1.0 type 2
+//! 1.1 key Enter
+//! 1.5 type Alice
+//! 1.6 key Enter
+//! 4.0 click
+//! ```
+//!
+//! Each line gives the time in seconds since the first frame, then one of
+//! `key <name>`, which presses a key (`Enter`, `Backspace`, `Escape`,
+//! `Space`, the arrows, `F2`, `F3`, `F4`, `F6`, `Control`, or a single
+//! letter, digit or punctuation key), `type <text>`, which types the rest of
+//! the line and presses each character's key, or `click`. Blank lines and
+//! lines starting with `#` are skipped.
+//!
+//! An event never fires before its time, and at most one fires per frame,
+//! so text typed and the Enter that confirms it arrive in order however
+//! fast the frames come.
+
+use crate::{ShotPhase, ShotState};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+
+/// What a throw's angle and velocity are when the player types nothing
+/// usable.
+pub const DEFAULT_ANGLE: f32 = 45.0;
+pub const DEFAULT_VELOCITY: f32 = 50.0;
+
+/// The keys the game tells apart.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Enter,
+    Backspace,
+    Escape,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    F2,
+    F3,
+    F4,
+    F6,
+    /// Either Control key, or Command on a Mac.
+    Control,
+    /// A letter key, as its uppercase letter, or a digit or punctuation key,
+    /// as the character it types without Shift.
+    Char(char),
+    /// Any other key, which only counts for "press any key".
+    Other,
+}
+
+impl Key {
+    /// Parses a key as scripts name it.
+    pub fn from_name(name: &str) -> Option<Key> {
+        let key = match name.to_ascii_lowercase().as_str() {
+            "enter" => Key::Enter,
+            "backspace" => Key::Backspace,
+            "escape" => Key::Escape,
+            "space" => Key::Space,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "f2" => Key::F2,
+            "f3" => Key::F3,
+            "f4" => Key::F4,
+            "f6" => Key::F6,
+            "control" => Key::Control,
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_graphic() => Key::Char(c.to_ascii_uppercase()),
+                    _ => return None,
+                }
+            }
+        };
+        Some(key)
+    }
+
+    // The key pressed to type `c`.
+    fn typing(c: char) -> Key {
+        if c == ' ' {
+            Key::Space
+        } else if c.is_ascii_graphic() {
+            Key::Char(c.to_ascii_uppercase())
+        } else {
+            Key::Other
+        }
+    }
+}
+
+/// Where the front-end gets its input from, a frame at a time.
+pub trait InputSource {
+    /// Moves on to the frame starting `time` seconds into the session.
+    fn next_frame(&mut self, time: f64);
+    /// The last key pressed this frame.
+    fn last_key_pressed(&self) -> Option<Key>;
+    fn is_key_pressed(&self, key: Key) -> bool;
+    fn is_key_down(&self, key: Key) -> bool;
+    /// The next character typed this frame, in the order they were typed.
+    fn char_pressed(&mut self) -> Option<char>;
+    /// Whether the left mouse button was pressed this frame.
+    fn clicked(&self) -> bool;
+
+    /// Whether any key was pressed or the mouse clicked, for "press any key".
+    fn any_pressed(&self) -> bool {
+        self.last_key_pressed().is_some() || self.clicked()
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(std::io::Error),
+    Malformed { line: usize, reason: String },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "could not read input script: {}", err),
+            ScriptError::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<std::io::Error> for ScriptError {
+    fn from(err: std::io::Error) -> Self {
+        ScriptError::Io(err)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Event {
+    Key(Key),
+    Type(String),
+    Click,
+}
+
+/// Input replayed from a file of timed events; see the module docs for the
+/// format.
+#[derive(Clone, Debug)]
+pub struct Script {
+    events: Vec<(f64, Event)>,
+    next: usize,
+    // When the first frame started; event times count from here.
+    start: Option<f64>,
+    keys: Vec<Key>,
+    chars: VecDeque<char>,
+    click: bool,
+}
+
+impl Script {
+    pub fn load(path: impl AsRef<Path>) -> Result<Script, ScriptError> {
+        Script::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Script, ScriptError> {
+        let mut events: Vec<(f64, Event)> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let malformed = |reason: &str| ScriptError::Malformed {
+                line: i + 1,
+                reason: reason.to_string(),
+            };
+            let content = line.trim();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            let (time, event) = content
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| malformed("expected '<seconds> <event>'"))?;
+            let time: f64 = match time.parse() {
+                Ok(time) if time >= 0.0 => time,
+                _ => return Err(malformed(&format!("bad time '{}'", time))),
+            };
+            if events.last().is_some_and(|&(last, _)| time < last) {
+                return Err(malformed("events must be in order of time"));
+            }
+
+            let event = event.trim_start();
+            let (name, argument) = event.split_once(' ').unwrap_or((event, ""));
+            let event = match name {
+                "key" => Key::from_name(argument.trim())
+                    .map(Event::Key)
+                    .ok_or_else(|| malformed(&format!("unknown key '{}'", argument.trim())))?,
+                "type" if !argument.is_empty() => Event::Type(argument.to_string()),
+                "type" => return Err(malformed("expected 'type <text>'")),
+                "click" => Event::Click,
+                _ => return Err(malformed(&format!("unknown event '{}'", name))),
+            };
+            events.push((time, event));
+        }
+
+        Ok(Script {
+            events,
+            next: 0,
+            start: None,
+            keys: Vec::new(),
+            chars: VecDeque::new(),
+            click: false,
+        })
+    }
+
+    /// Whether every event has fired.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+}
+
+impl InputSource for Script {
+    fn next_frame(&mut self, time: f64) {
+        self.keys.clear();
+        self.chars.clear();
+        self.click = false;
+
+        let start = *self.start.get_or_insert(time);
+        let Some((at, event)) = self.events.get(self.next) else {
+            return;
+        };
+        if time - start < *at {
+            return;
+        }
+        match event {
+            Event::Key(key) => self.keys.push(*key),
+            Event::Type(text) => {
+                for c in text.chars() {
+                    self.keys.push(Key::typing(c));
+                    self.chars.push_back(c);
+                }
+            }
+            Event::Click => self.click = true,
+        }
+        self.next += 1;
+    }
+
+    fn last_key_pressed(&self) -> Option<Key> {
+        self.keys.last().copied()
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        self.keys.contains(&key)
+    }
+
+    // Scripted keys are let go in the frame they are pressed.
+    fn is_key_down(&self, key: Key) -> bool {
+        self.is_key_pressed(key)
+    }
+
+    fn char_pressed(&mut self) -> Option<char> {
+        self.chars.pop_front()
+    }
+
+    fn clicked(&self) -> bool {
+        self.click
+    }
+}
+
+/// Reads the angle and then the velocity of a throw as they are typed at
+/// their prompts: digits and points go into `ShotState::angle_input` or
+/// `velocity_input`, Backspace takes one back and Enter confirms. Anything
+/// that isn't a number when confirmed counts as `DEFAULT_ANGLE` or
+/// `DEFAULT_VELOCITY`. Returns the velocity once it is confirmed; the angle
+/// is in `ShotState::angle` by then.
+pub fn type_throw(shot_state: &mut ShotState, input: &mut dyn InputSource) -> Option<f32> {
+    let entering_angle = match shot_state.phase {
+        ShotPhase::InputAngle => true,
+        ShotPhase::InputVelocity => false,
+        ShotPhase::Animating | ShotPhase::Done => return None,
+    };
+    let mut velocity = None;
+    match input.last_key_pressed() {
+        Some(Key::Enter) if entering_angle => {
+            shot_state.angle = shot_state.angle_input.parse().unwrap_or(DEFAULT_ANGLE);
+            shot_state.phase = ShotPhase::InputVelocity;
+        }
+        Some(Key::Enter) => {
+            velocity = Some(
+                shot_state
+                    .velocity_input
+                    .parse()
+                    .unwrap_or(DEFAULT_VELOCITY),
+            );
+        }
+        Some(Key::Backspace) if entering_angle => {
+            shot_state.angle_input.pop();
+        }
+        Some(Key::Backspace) => {
+            shot_state.velocity_input.pop();
+        }
+        _ => {}
+    }
+
+    let buffer = if entering_angle {
+        &mut shot_state.angle_input
+    } else {
+        &mut shot_state.velocity_input
+    };
+    while let Some(c) = input.char_pressed() {
+        if c.is_ascii_digit() || c == '.' {
+            buffer.push(c);
+        }
+    }
+    velocity
+}
@@ -0,0 +1,865 @@
+//! Headless simulation of QBasic Gorillas.
+//!
+//! Everything needed to play a match lives here: cityscape generation,
+//! gorilla placement, the banana trajectory and its collision rules, and
+//! scoring. Nothing in this crate opens a window or reads the keyboard, so
+//! tools, tests and bots can drive matches without either. The `gorillas`
+//! binary is a thin macroquad front-end on top of it.
+
+use std::f32::consts::PI;
+
+pub mod ai;
+pub mod bot;
+pub mod clock;
+pub mod display;
+pub mod fairness;
+pub mod font;
+pub mod game;
+pub mod gym;
+pub mod input;
+pub mod mixer;
+pub mod mml;
+pub mod net;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod session;
+pub mod setup;
+pub mod sfx;
+pub mod solver;
+pub mod storage;
+pub mod term;
+pub mod turnfile;
+
+pub use clock::SimClock;
+pub use display::{CitySizing, DisplayMode};
+pub use fairness::Fairness;
+pub use rng::Rng;
+
+// Virtual screen dimensions (original EGA)
+pub const VIRTUAL_WIDTH: f32 = 640.0;
+pub const VIRTUAL_HEIGHT: f32 = 350.0;
+pub const G_HEIGHT: f32 = 25.0;
+pub const SUN_HT: f32 = 39.0;
+
+pub const EXPLOSION_RADIUS: f32 = VIRTUAL_HEIGHT / 50.0;
+
+/// Number of entries in the building palette; `Building::color` indexes it.
+/// The original picked from three.
+pub const BUILDING_COLOR_COUNT: usize = 3;
+
+/// Gorillas in a match. The original only ever had two.
+pub const MIN_PLAYERS: usize = 2;
+pub const MAX_PLAYERS: usize = 6;
+
+#[derive(Clone, Copy, Default)]
+pub struct XYPoint {
+    pub x_coor: i32,
+    pub y_coor: i32,
+}
+
+#[derive(Clone)]
+pub struct Building {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: usize,
+    pub windows: Vec<Window>,
+    pub craters: Vec<Crater>,
+}
+
+impl Building {
+    fn intersects_circle(&self, cx: f32, cy: f32, radius: f32) -> bool {
+        let nearest_x = cx.clamp(self.x, self.x + self.width);
+        let nearest_y = cy.clamp(self.y, self.y + self.height);
+        (cx - nearest_x).powi(2) + (cy - nearest_y).powi(2) <= radius * radius
+    }
+
+    pub fn is_cratered(&self, x: f32, y: f32) -> bool {
+        self.craters.iter().any(|crater| crater.contains(x, y))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Window {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub lit: bool,
+}
+
+// A hole blown into a building by an explosion. Bananas fly through it.
+#[derive(Clone, Copy)]
+pub struct Crater {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+impl Crater {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (x - self.x).powi(2) + (y - self.y).powi(2) <= self.radius * self.radius
+    }
+}
+
+#[derive(Clone)]
+pub struct GameState {
+    /// Indexed by player, in turn order. All per-player vectors share the
+    /// length set by `set_num_players`.
+    pub gorilla_x: Vec<f32>,
+    pub gorilla_y: Vec<f32>,
+    pub last_building: usize,
+    pub gravity: f32,
+    pub wind: i32,
+    pub sun_hit: bool,
+    pub player_names: Vec<String>,
+    /// Team of each player, numbered from 0. In a free-for-all every
+    /// gorilla is a team of its own.
+    pub teams: Vec<usize>,
+    pub friendly_fire: FriendlyFire,
+    pub num_games: i32,
+    /// Points per team, shared by its members.
+    pub total_wins: Vec<i32>,
+    pub bcoor: Vec<XYPoint>,
+    pub buildings: Vec<Building>,
+    /// Building sizes of the display mode the city is dealt for.
+    pub city: CitySizing,
+    pub cityscape_generated: bool,
+    /// Seed of the map currently on screen; feeding it back reproduces it.
+    pub seed: u32,
+    pub rng: Rng,
+    /// Maps scoring below this are rerolled. `None` deals them as they come,
+    /// like the original.
+    pub min_fairness: Option<f32>,
+    /// Score of the current map, when it has been evaluated.
+    pub fairness: Option<Fairness>,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        let mut state = GameState {
+            gorilla_x: Vec::new(),
+            gorilla_y: Vec::new(),
+            last_building: 0,
+            gravity: 9.8,
+            wind: 0,
+            sun_hit: false,
+            player_names: Vec::new(),
+            teams: Vec::new(),
+            friendly_fire: FriendlyFire::default(),
+            num_games: 3,
+            total_wins: Vec::new(),
+            bcoor: vec![XYPoint::default(); 31],
+            buildings: Vec::new(),
+            city: CitySizing::default(),
+            cityscape_generated: false,
+            seed: 0,
+            rng: Rng::new(0),
+            min_fairness: None,
+            fairness: None,
+        };
+        state.set_num_players(MIN_PLAYERS);
+        state
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.gorilla_x.len()
+    }
+
+    /// Grows or shrinks the match to `count` gorillas, clamped to
+    /// `MIN_PLAYERS..=MAX_PLAYERS`. New players get default names, and the
+    /// match goes back to a free-for-all.
+    pub fn set_num_players(&mut self, count: usize) {
+        let count = count.clamp(MIN_PLAYERS, MAX_PLAYERS);
+        self.gorilla_x.resize(count, 0.0);
+        self.gorilla_y.resize(count, 0.0);
+        while self.player_names.len() < count {
+            self.player_names
+                .push(format!("Player {}", self.player_names.len() + 1));
+        }
+        self.player_names.truncate(count);
+        self.set_teams(&(0..count).collect::<Vec<_>>());
+    }
+
+    /// Assigns each player to a team. Any labels will do; they are renumbered
+    /// from 0 in order of first appearance. Scores start over.
+    pub fn set_teams(&mut self, teams: &[usize]) {
+        let mut labels = Vec::new();
+        self.teams = (0..self.num_players())
+            .map(|player| {
+                let label = teams.get(player).copied().unwrap_or(player + MAX_PLAYERS);
+                match labels.iter().position(|&l| l == label) {
+                    Some(team) => team,
+                    None => {
+                        labels.push(label);
+                        labels.len() - 1
+                    }
+                }
+            })
+            .collect();
+        self.total_wins = vec![0; labels.len()];
+    }
+
+    pub fn num_teams(&self) -> usize {
+        self.total_wins.len()
+    }
+
+    /// Whether anyone shares a team, as opposed to a free-for-all.
+    pub fn is_team_match(&self) -> bool {
+        self.num_teams() < self.num_players()
+    }
+
+    pub fn team_members(&self, team: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_players()).filter(move |&player| self.teams[player] == team)
+    }
+
+    pub fn is_opponent(&self, player: usize, other: usize) -> bool {
+        self.teams[player] != self.teams[other]
+    }
+
+    /// Who throws in what order: one gorilla from each team in turn, so the
+    /// sides alternate. In a free-for-all that is simply by player number.
+    pub fn turn_order(&self) -> Vec<usize> {
+        let rosters: Vec<Vec<usize>> = (0..self.num_teams())
+            .map(|team| self.team_members(team).collect())
+            .collect();
+        let longest = rosters.iter().map(Vec::len).max().unwrap_or(0);
+        (0..longest)
+            .flat_map(|slot| rosters.iter().filter_map(move |roster| roster.get(slot)))
+            .copied()
+            .collect()
+    }
+
+    /// Whose turn follows `player`'s.
+    pub fn next_player(&self, player: usize) -> usize {
+        let order = self.turn_order();
+        let position = order.iter().position(|&p| p == player).unwrap_or(0);
+        order[(position + 1) % order.len()]
+    }
+
+    /// The team that gets the point when `player` knocks out their own side:
+    /// the next team to throw after theirs.
+    pub fn opposing_team(&self, player: usize) -> usize {
+        let mut next = self.next_player(player);
+        while !self.is_opponent(player, next) && next != player {
+            next = self.next_player(next);
+        }
+        self.teams[next]
+    }
+
+    /// Gorillas on the right half of the skyline throw leftwards and have
+    /// their angles mirrored, like player 2 in the original.
+    pub fn faces_left(&self, player: usize) -> bool {
+        self.gorilla_x[player] + 10.0 > VIRTUAL_WIDTH / 2.0
+    }
+
+    /// The closest gorilla on another team, which is the only one with two
+    /// players.
+    pub fn nearest_opponent(&self, player: usize) -> usize {
+        let x = self.gorilla_x[player];
+        (0..self.num_players())
+            .filter(|&other| self.is_opponent(player, other))
+            .min_by(|&a, &b| {
+                (self.gorilla_x[a] - x)
+                    .abs()
+                    .total_cmp(&(self.gorilla_x[b] - x).abs())
+            })
+            .unwrap_or(player)
+    }
+
+    pub fn reset_cityscape(&mut self) {
+        self.buildings.clear();
+        self.cityscape_generated = false;
+        self.bcoor = vec![XYPoint::default(); 31];
+    }
+
+    /// Throws away the current city and builds the map for `self.seed`.
+    ///
+    /// With `min_fairness` set, a lopsided map first gets a few fresh winds.
+    /// If none of them evens it out, the city itself is rerolled from a seed
+    /// drawn off this one, and `seed` moves on to it. Either way the outcome
+    /// follows from the seed, so replays and shared seeds still reproduce.
+    pub fn new_round(&mut self) {
+        self.fairness = None;
+        for _ in 0..MAX_CITY_REROLLS {
+            self.deal_map();
+            let Some(min_fairness) = self.min_fairness else {
+                return;
+            };
+            for wind_roll in 0..=MAX_WIND_REROLLS {
+                if wind_roll > 0 {
+                    roll_wind(self);
+                }
+                let fairness = self.fairness.insert(fairness::evaluate(self));
+                if fairness.score >= min_fairness {
+                    return;
+                }
+            }
+            self.seed = self.rng.next_u32();
+        }
+        // Nothing fair turned up. Play the last map dealt rather than stall.
+        self.deal_map();
+        if self.min_fairness.is_some() {
+            self.fairness = Some(fairness::evaluate(self));
+        }
+    }
+
+    fn deal_map(&mut self) {
+        self.rng = Rng::new(self.seed as u64);
+        self.sun_hit = false;
+        self.reset_cityscape();
+        generate_cityscape(self);
+        place_gorillas(self);
+    }
+
+    /// Moves on to the next map. Its seed is drawn from the current map's
+    /// generator, so a whole match follows from the seed it started with.
+    pub fn next_round(&mut self) {
+        self.seed = self.rng.next_u32();
+        self.new_round();
+    }
+
+    /// Fingerprint of everything a throw can change, for checking that two
+    /// copies of a match still agree. FNV-1a, so it is the same on every
+    /// platform and build.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.feed(self.seed as u64);
+        hash.feed(self.rng.clone().next_u64());
+        hash.feed(self.wind as u64);
+        hash.feed(self.gravity.to_bits() as u64);
+        for (x, y) in self.gorilla_x.iter().zip(&self.gorilla_y) {
+            hash.feed(x.to_bits() as u64);
+            hash.feed(y.to_bits() as u64);
+        }
+        for &wins in &self.total_wins {
+            hash.feed(wins as u64);
+        }
+        for building in &self.buildings {
+            hash.feed(building.x.to_bits() as u64);
+            hash.feed(building.height.to_bits() as u64);
+            for crater in &building.craters {
+                hash.feed(crater.x.to_bits() as u64);
+                hash.feed(crater.y.to_bits() as u64);
+            }
+        }
+        hash.0
+    }
+
+    /// Fingerprint of the buildings, their windows and craters, and the wind
+    /// arrow: everything that stays put on screen between explosions. A
+    /// front end can keep the skyline drawn until this changes.
+    pub fn skyline_fingerprint(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.feed(self.wind as u64);
+        for building in &self.buildings {
+            for value in [building.x, building.y, building.width, building.height] {
+                hash.feed(value.to_bits() as u64);
+            }
+            hash.feed(building.color as u64);
+            for window in &building.windows {
+                hash.feed(window.x.to_bits() as u64);
+                hash.feed(window.y.to_bits() as u64);
+                hash.feed(window.lit as u64);
+            }
+            for crater in &building.craters {
+                hash.feed(crater.x.to_bits() as u64);
+                hash.feed(crater.y.to_bits() as u64);
+                hash.feed(crater.radius.to_bits() as u64);
+            }
+        }
+        hash.0
+    }
+
+    pub fn add_crater(&mut self, x: f32, y: f32, radius: f32) {
+        let crater = Crater { x, y, radius };
+        for building in &mut self.buildings {
+            if building.intersects_circle(x, y, radius) {
+                building.craters.push(crater);
+            }
+        }
+    }
+}
+
+// 64-bit FNV-1a over little-endian words.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn feed(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// What happens when a banana meets a teammate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FriendlyFire {
+    /// Bananas fly straight through teammates.
+    Off,
+    /// Teammates can be hit, and it counts like hitting yourself: the other
+    /// team scores.
+    #[default]
+    Penalty,
+}
+
+/// Cities and winds tried per round before settling for an unfair map.
+const MAX_CITY_REROLLS: u32 = 8;
+const MAX_WIND_REROLLS: u32 = 3;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ShotPhase {
+    InputAngle,
+    InputVelocity,
+    Animating,
+    Done,
+}
+
+#[derive(Clone)]
+pub struct ShotState {
+    pub phase: ShotPhase,
+    pub angle: f32,
+    pub velocity: f32,
+    pub angle_input: String,
+    pub velocity_input: String,
+    pub t: f32,
+    pub impact: bool,
+    pub on_screen: bool,
+    pub player_hit: Option<usize>,
+    /// Index into `GameState::buildings` of the building the banana hit.
+    pub building_hit: Option<usize>,
+    pub x: f32,
+    pub y: f32,
+    /// Position one step earlier, for interpolating between steps.
+    pub prev_x: f32,
+    pub prev_y: f32,
+}
+
+impl Default for ShotState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShotState {
+    pub fn new() -> Self {
+        ShotState {
+            phase: ShotPhase::InputAngle,
+            angle: 0.0,
+            velocity: 0.0,
+            angle_input: String::new(),
+            velocity_input: String::new(),
+            t: 0.0,
+            impact: false,
+            on_screen: true,
+            player_hit: None,
+            building_hit: None,
+            x: 0.0,
+            y: 0.0,
+            prev_x: 0.0,
+            prev_y: 0.0,
+        }
+    }
+
+    /// Sets up a throw as entered by a player. Angles of gorillas facing
+    /// left are mirrored, see `GameState::faces_left`.
+    pub fn aim(&mut self, faces_left: bool, angle: f32, velocity: f32) {
+        self.angle = if faces_left { 180.0 - angle } else { angle };
+        self.velocity = velocity;
+        self.phase = ShotPhase::Animating;
+        self.t = 0.0;
+        self.impact = false;
+        self.on_screen = true;
+        self.player_hit = None;
+        self.building_hit = None;
+    }
+
+    /// Whether the banana should be drawn at `(x, y)` this frame.
+    pub fn banana_visible(&self) -> bool {
+        self.phase == ShotPhase::Animating && self.on_screen && !self.impact && self.y > 0.0
+    }
+
+    /// Where to draw the banana when `alpha` of the way to the next step.
+    pub fn lerp_position(&self, alpha: f32) -> (f32, f32) {
+        (
+            self.prev_x + (self.x - self.prev_x) * alpha,
+            self.prev_y + (self.y - self.prev_y) * alpha,
+        )
+    }
+
+    /// Which of the four banana sprites to show, spinning as it flies.
+    pub fn banana_rotation(&self) -> i32 {
+        ((self.t * 10.0) as i32) % 4
+    }
+}
+
+pub fn generate_cityscape(state: &mut GameState) {
+    // The city is laid out in the pixels of the display mode it was sized
+    // for, as the original did, and scaled to the playfield as it is stored.
+    let size = state.city.dimensions();
+    let (px, py) = size.pixel;
+    let screen_width = VIRTUAL_WIDTH / px;
+
+    let mut x = 2.0;
+    let slope = state.rng.fn_ran(6);
+    let mut new_ht: f32 = match slope {
+        1 => size.low_start,
+        2 => size.high_start,
+        3..=5 => size.low_start,
+        _ => size.high_start,
+    };
+
+    let bottom_line = size.bottom_line;
+    let ht_inc = size.height_step;
+    let def_b_width = size.building_width;
+    let random_height = size.random_height;
+    let max_height = (SUN_HT + 10.0) / py;
+    let g_height = G_HEIGHT / py;
+
+    let mut cur_building = 1;
+    state.buildings.clear();
+
+    while x <= screen_width - ht_inc {
+        match slope {
+            1 => new_ht += ht_inc,
+            2 => new_ht -= ht_inc,
+            3..=5 => {
+                if x > screen_width / 2.0 {
+                    new_ht -= 2.0 * ht_inc;
+                } else {
+                    new_ht += 2.0 * ht_inc;
+                }
+            }
+            _ => {
+                if x > screen_width / 2.0 {
+                    new_ht += 2.0 * ht_inc;
+                } else {
+                    new_ht -= 2.0 * ht_inc;
+                }
+            }
+        }
+
+        let mut b_width = (state.rng.fn_ran(def_b_width) + def_b_width) as f32;
+        if x + b_width > screen_width {
+            b_width = screen_width - x - 2.0;
+        }
+
+        let mut b_height = state.rng.fn_ran(random_height) as f32 + new_ht;
+        if b_height < ht_inc {
+            b_height = ht_inc;
+        }
+        if bottom_line - b_height <= max_height + g_height {
+            b_height = max_height + g_height - 5.0;
+        }
+
+        if cur_building < state.bcoor.len() {
+            state.bcoor[cur_building].x_coor = (x * px) as i32;
+            state.bcoor[cur_building].y_coor = ((bottom_line - b_height) * py) as i32;
+        }
+
+        let building_color = state.rng.fn_ran(BUILDING_COLOR_COUNT as i32) as usize - 1;
+
+        let mut windows = Vec::new();
+        let mut c = x + 3.0;
+        while c < x + b_width - 3.0 {
+            let mut i = b_height - 3.0;
+            while i >= 7.0 {
+                windows.push(Window {
+                    x: c * px,
+                    y: (bottom_line - i) * py,
+                    width: size.window_width * px,
+                    height: size.window_height * py,
+                    lit: state.rng.fn_ran(4) != 1,
+                });
+                i -= size.window_spacing_v;
+            }
+            c += size.window_spacing_h;
+        }
+
+        state.buildings.push(Building {
+            x: x * px,
+            y: (bottom_line - b_height) * py,
+            width: b_width * px,
+            height: b_height * py,
+            color: building_color,
+            windows,
+            craters: Vec::new(),
+        });
+
+        x += b_width + 2.0;
+        cur_building += 1;
+    }
+
+    state.last_building = cur_building - 1;
+
+    roll_wind(state);
+
+    state.cityscape_generated = true;
+}
+
+/// Draws a new wind, mostly gentle with the odd gust.
+pub fn roll_wind(state: &mut GameState) {
+    state.wind = state.rng.fn_ran(10) - 5;
+    if state.rng.fn_ran(3) == 1 {
+        if state.wind > 0 {
+            state.wind += state.rng.fn_ran(10);
+        } else {
+            state.wind -= state.rng.fn_ran(10);
+        }
+    }
+}
+
+pub fn place_gorillas(state: &mut GameState) {
+    let x_adj = 14.0;
+    let y_adj = 30.0;
+    let count = state.num_players();
+    let mut taken = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let b_num = if count == 2 {
+            // The original: second or third building in from either edge.
+            if i == 0 {
+                state.rng.fn_ran(2) as usize + 1
+            } else {
+                state.last_building - state.rng.fn_ran(2) as usize
+            }
+        } else {
+            // Cut the same stretch of skyline into one slice per gorilla and
+            // pick a building in each, so nobody shares a roof. Teams fill
+            // the slices together, so each side holds its own end of town.
+            let slice = (0..count)
+                .filter(|&other| (state.teams[other], other) < (state.teams[i], i))
+                .count();
+            let first = 2;
+            let span = state.last_building - first;
+            let start = first + slice * span / count;
+            let end = first + (slice + 1) * span / count;
+            start + state.rng.fn_ran((end - start) as i32) as usize - 1
+        };
+        // A skyline with fewer buildings than slices leaves some slices
+        // without a building of their own; move over to the nearest one
+        // nobody stands on yet.
+        let b_num = (1..=state.last_building)
+            .filter(|b| !taken.contains(b))
+            .min_by_key(|b| b.abs_diff(b_num))
+            .unwrap_or(b_num);
+        taken.push(b_num);
+
+        let b_width = if b_num + 1 < state.bcoor.len() {
+            (state.bcoor[b_num + 1].x_coor - state.bcoor[b_num].x_coor) as f32
+        } else {
+            50.0
+        };
+
+        state.gorilla_x[i] = state.bcoor[b_num].x_coor as f32 + b_width / 2.0 - x_adj;
+        state.gorilla_y[i] = state.bcoor[b_num].y_coor as f32 - y_adj;
+    }
+}
+
+/// Flight time after which a throw is given up as lost. Only a banana thrown
+/// straight up in near-zero gravity gets anywhere near it, or one with a
+/// velocity that isn't a number, which never leaves the screen.
+pub const MAX_FLIGHT_TIME: f32 = 1000.0;
+
+/// Advances the banana one fixed step along its trajectory. Returns `true`
+/// once the shot is over, either because it hit something or left the screen,
+/// or because it has flown for `MAX_FLIGHT_TIME` and is given up as lost.
+pub fn plot_shot(
+    state: &mut GameState,
+    shot_state: &mut ShotState,
+    player_num: usize,
+    start_x: f32,
+    start_y: f32,
+) -> bool {
+    let angle_rad = shot_state.angle / 180.0 * PI;
+    let init_x_vel = angle_rad.cos() * shot_state.velocity;
+    let init_y_vel = angle_rad.sin() * shot_state.velocity;
+
+    let adjust = 4.0;
+    let start_x_pos = if state.faces_left(player_num) {
+        start_x + 25.0
+    } else {
+        start_x
+    };
+    let start_y_pos = start_y - adjust - 3.0;
+
+    shot_state.prev_x = shot_state.x;
+    shot_state.prev_y = shot_state.y;
+    shot_state.x = start_x_pos
+        + (init_x_vel * shot_state.t)
+        + (0.5 * (state.wind as f32 / 5.0) * shot_state.t * shot_state.t);
+    shot_state.y = start_y_pos
+        + (-(init_y_vel * shot_state.t) + (0.5 * state.gravity * shot_state.t * shot_state.t))
+            * (VIRTUAL_HEIGHT / 350.0);
+    if shot_state.t == 0.0 {
+        shot_state.prev_x = shot_state.x;
+        shot_state.prev_y = shot_state.y;
+    }
+
+    if shot_state.x >= VIRTUAL_WIDTH - 10.0
+        || shot_state.x <= 3.0
+        || shot_state.y >= VIRTUAL_HEIGHT - 3.0
+    {
+        shot_state.on_screen = false;
+        return true;
+    }
+
+    if shot_state.y > 0.0 && shot_state.on_screen {
+        for i in 0..state.num_players() {
+            if i != player_num
+                && !state.is_opponent(player_num, i)
+                && state.friendly_fire == FriendlyFire::Off
+            {
+                continue;
+            }
+            let gx = state.gorilla_x[i];
+            let gy = state.gorilla_y[i];
+            if shot_state.x >= gx - 5.0
+                && shot_state.x <= gx + 25.0
+                && shot_state.y >= gy - 5.0
+                && shot_state.y <= gy + 30.0
+            {
+                shot_state.impact = true;
+                shot_state.player_hit = Some(i);
+                return true;
+            }
+        }
+
+        for (i, building) in state.buildings.iter().enumerate() {
+            let next_bx = state
+                .buildings
+                .get(i + 1)
+                .map_or(VIRTUAL_WIDTH, |next| next.x);
+            if shot_state.x >= building.x
+                && shot_state.x <= next_bx
+                && shot_state.y >= building.y
+                && !building.is_cratered(shot_state.x, shot_state.y)
+            {
+                shot_state.impact = true;
+                shot_state.building_hit = Some(i);
+                return true;
+            }
+        }
+
+        let sun_x = VIRTUAL_WIDTH / 2.0;
+        if (shot_state.x - sun_x).abs() < 20.0 && shot_state.y < SUN_HT {
+            state.sun_hit = true;
+        }
+    }
+
+    shot_state.t += 0.1;
+    if shot_state.t > MAX_FLIGHT_TIME {
+        shot_state.on_screen = false;
+        return true;
+    }
+    false
+}
+
+/// Advances an animated banana by up to `ticks` steps of the `SimClock`,
+/// from the shooter's gorilla, and marks the shot done once it is over. This
+/// is how the front ends fly a throw while drawing it.
+pub fn step_shot(state: &mut GameState, shot_state: &mut ShotState, shooter: usize, ticks: u32) {
+    let gx = state.gorilla_x[shooter];
+    let gy = state.gorilla_y[shooter];
+    for _ in 0..ticks {
+        if plot_shot(state, shot_state, shooter, gx, gy) {
+            shot_state.phase = ShotPhase::Done;
+            break;
+        }
+    }
+}
+
+/// Where the explosion of a finished shot is centred, if it blew up at all.
+pub fn explosion_point(shot_state: &ShotState) -> Option<(f32, f32)> {
+    if shot_state.player_hit.is_some() {
+        Some((shot_state.x, shot_state.y))
+    } else if shot_state.impact {
+        Some((shot_state.x + 4.0, shot_state.y + 4.0))
+    } else {
+        None
+    }
+}
+
+/// Applies the result of a finished shot: craters the skyline where it blew
+/// up and awards the point if a gorilla was hit. Returns the team that won
+/// the round, which outside team matches is the winning player.
+///
+/// The shooter's team scores for hitting an opponent. Hitting yourself or a
+/// teammate hands the point to the next team in turn order, which with two
+/// players is the opponent, as in the original.
+pub fn resolve_shot(
+    state: &mut GameState,
+    shot_state: &ShotState,
+    shooter: usize,
+) -> Option<usize> {
+    if let Some((x, y)) = explosion_point(shot_state) {
+        state.add_crater(x, y, EXPLOSION_RADIUS);
+    }
+
+    let hit_player = shot_state.player_hit?;
+    let winner = if state.is_opponent(shooter, hit_player) {
+        state.teams[shooter]
+    } else {
+        state.opposing_team(shooter)
+    };
+    state.total_wins[winner] += 1;
+    Some(winner)
+}
+
+/// Flies a whole throw without rendering it or applying the result. `angle`
+/// is as the player typed it.
+pub fn fly_shot(state: &mut GameState, shooter: usize, angle: f32, velocity: f32) -> ShotState {
+    trace_shot(state, shooter, angle, velocity, |_, _| {})
+}
+
+/// Like `fly_shot`, but hands every step of the flight to `visit`, the last
+/// one included, along with the time of the position in `x` and `y`.
+pub fn trace_shot(
+    state: &mut GameState,
+    shooter: usize,
+    angle: f32,
+    velocity: f32,
+    mut visit: impl FnMut(f32, &ShotState),
+) -> ShotState {
+    let mut shot_state = ShotState::new();
+    shot_state.aim(state.faces_left(shooter), angle, velocity);
+    state.sun_hit = false;
+
+    let gx = state.gorilla_x[shooter];
+    let gy = state.gorilla_y[shooter];
+    loop {
+        let t = shot_state.t;
+        let done = plot_shot(state, &mut shot_state, shooter, gx, gy);
+        visit(t, &shot_state);
+        if done {
+            break;
+        }
+    }
+    shot_state.phase = ShotPhase::Done;
+    shot_state
+}
+
+/// Flies a whole throw without rendering it and applies the result.
+pub fn throw_banana(
+    state: &mut GameState,
+    shooter: usize,
+    angle: f32,
+    velocity: f32,
+) -> (ShotState, Option<usize>) {
+    let shot_state = fly_shot(state, shooter, angle, velocity);
+    let winner = resolve_shot(state, &shot_state, shooter);
+    (shot_state, winner)
+}
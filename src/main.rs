@@ -1,100 +1,94 @@
+use gorillas::ai::{AiPlayer, Thinking};
+use gorillas::bot::{ExternalBot, DEFAULT_TIMEOUT};
+use gorillas::display::{DisplayMode, Rgb, Scheme, EGA_COLORS};
+use gorillas::fairness;
+use gorillas::font;
+use gorillas::input::{type_throw, InputSource, Key, Script, DEFAULT_ANGLE, DEFAULT_VELOCITY};
+use gorillas::mixer::{self, Channel, MixerSettings};
+use gorillas::mml::{wav, Tune, SAMPLE_RATE};
+use gorillas::net::{Listener, NetError, Peer, Role, Room, DEFAULT_PORT};
+use gorillas::render::{
+    paint_banana, paint_cityscape, paint_explosion, paint_gorilla, paint_sun, Renderer, ARMS_DOWN,
+    LEFT_UP, RIGHT_UP,
+};
+use gorillas::replay::{Replay, REPLAY_EXTENSION};
+use gorillas::setup::{offers_teams, team_letter, Field, Setup};
+use gorillas::sfx;
+use gorillas::storage;
+use gorillas::turnfile::{Ledger, TurnFile, DEFAULT_LEDGER};
+use gorillas::{
+    explosion_point, resolve_shot, step_shot, GameState, Rng, ShotPhase, ShotState, SimClock,
+    VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
+};
+use macroquad::audio::{
+    load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound,
+};
 use macroquad::prelude::*;
-use macroquad::rand::gen_range;
-use std::f32::consts::PI;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
 
 // Constants from the original
-const OBJECT_COLOR: Color = BROWN;
-const WINDOW_COLOR: Color = YELLOW;
-const SUN_ATTR: Color = Color::new(1.0, 1.0, 0.0, 1.0);
 const SUN_HAPPY: bool = false;
 const SUN_SHOCK: bool = true;
-const ARMS_DOWN: i32 = 3;
-const LEFT_UP: i32 = 2;
-const RIGHT_UP: i32 = 1;
-
-// Virtual screen dimensions (original EGA)
-const VIRTUAL_WIDTH: f32 = 640.0;
-const VIRTUAL_HEIGHT: f32 = 350.0;
-const G_HEIGHT: f32 = 25.0;
-const SUN_HT: f32 = 39.0;
-
-const BUILDING_COLORS: [Color; 4] = [
-    Color::new(0.5, 0.0, 0.5, 1.0),
-    Color::new(0.0, 0.5, 0.5, 1.0),
-    Color::new(0.5, 0.5, 0.0, 1.0),
-    Color::new(0.3, 0.3, 0.6, 1.0),
-];
 
-const EXPLOSION_COLOR: Color = RED;
-const BACK_COLOR: Color = Color::new(0.0, 0.0, 0.5, 1.0);
+// The original's PLAY strings.
+const INTRO_TUNE: &str = "MBT160O1L8CDEDCDL4ECC";
+const THROW_SOUND: &str = "MBO0L32A-L64CL16BL64A+";
+const EXPLOSION_SOUND: &str = "MBO0L32EFGEFDC";
+const GORILLA_EXPLOSION_SOUND: &str = "MBO0L16EFGEFDC";
+const VICTORY_TUNE: &str = "MFO0L32EFGEFDC";
+// Pause between the victory tune's repeats, on top of the tune itself.
+const VICTORY_REST_SECS: f64 = 0.2;
+
+// Picked once at startup with `--display ega|cga|mono`.
+static DISPLAY_MODE: OnceLock<DisplayMode> = OnceLock::new();
 
-#[derive(Clone, Copy, Default)]
-struct XYPoint {
-    x_coor: i32,
-    y_coor: i32,
+fn display_mode() -> DisplayMode {
+    DISPLAY_MODE.get().copied().unwrap_or_default()
 }
 
-#[derive(Clone)]
-struct Building {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    color: Color,
-    windows: Vec<Window>,
+fn display_mode_from_args() -> DisplayMode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(position) = args.iter().position(|arg| arg == "--display") else {
+        return DisplayMode::default();
+    };
+    match args
+        .get(position + 1)
+        .and_then(|name| DisplayMode::from_name(name))
+    {
+        Some(mode) => mode,
+        None => {
+            eprintln!("--display takes ega, cga or mono");
+            DisplayMode::default()
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
-struct Window {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    color: Color,
+// A fresh match, dealt in the display mode's building sizes.
+fn new_game_state() -> GameState {
+    GameState {
+        city: display_mode().city(),
+        ..GameState::new()
+    }
 }
 
-struct GameState {
-    gorilla_x: [f32; 2],
-    gorilla_y: [f32; 2],
-    last_building: usize,
-    gravity: f32,
-    wind: i32,
-    sun_hit: bool,
-    player1_name: String,
-    player2_name: String,
-    num_games: i32,
-    total_wins: [i32; 2],
-    bcoor: Vec<XYPoint>,
-    buildings: Vec<Building>,
-    cityscape_generated: bool,
+fn rgb(color: Rgb) -> Color {
+    Color::from_rgba(color[0], color[1], color[2], 255)
 }
 
-impl GameState {
-    fn new() -> Self {
-        GameState {
-            gorilla_x: [0.0; 2],
-            gorilla_y: [0.0; 2],
-            last_building: 0,
-            gravity: 9.8,
-            wind: 0,
-            sun_hit: false,
-            player1_name: "Player 1".to_string(),
-            player2_name: "Player 2".to_string(),
-            num_games: 3,
-            total_wins: [0, 0],
-            bcoor: vec![XYPoint::default(); 31],
-            buildings: Vec::new(),
-            cityscape_generated: false,
-        }
-    }
+// A colour as the display mode shows it.
+fn ink(color: Color) -> Color {
+    let [r, g, b, _] = color.into();
+    rgb(display_mode().nearest([r, g, b]))
+}
 
-    fn reset_cityscape(&mut self) {
-        self.buildings.clear();
-        self.cityscape_generated = false;
-        self.bcoor = vec![XYPoint::default(); 31];
-    }
+// The playfield's colours in the display mode.
+fn scheme() -> &'static Scheme {
+    display_mode().scheme()
 }
 
+// `gorillas::session::Session` copies the moves between the first six for
+// the session tests; a change to them here has to be made there too.
 #[derive(PartialEq, Clone, Copy)]
 enum GamePhase {
     Intro,
@@ -103,59 +97,364 @@ enum GamePhase {
     Playing,
     GameOver,
     PlayAgain,
+    // Typing the code of a room on the relay.
+    RoomCode,
+    // Waiting for the other machine, then dealing the first round.
+    MatchStart,
+    NetError,
+    // Play-by-file: this machine's throw is saved, or the file it was
+    // given cannot be played.
+    TurnSent,
+    BadTurnFile,
 }
 
-#[derive(PartialEq, Clone, Copy)]
-enum InputField {
-    Player1Name,
-    Player2Name,
-    NumGames,
-    Gravity,
-    ViewOrPlay,
+// Records the match being played and rewrites the file after every throw,
+// so quitting mid-match still leaves a usable replay behind.
+struct Recording {
+    replay: Replay,
+    path: String,
+    // Set for play-by-file matches, which are saved as a turn file once each
+    // throw has landed instead.
+    ledger: Option<Ledger>,
 }
 
-#[derive(PartialEq, Clone, Copy)]
-enum ShotPhase {
-    InputAngle,
-    InputVelocity,
-    Animating,
-    Done,
+impl Recording {
+    fn start(state: &GameState) -> Option<Self> {
+        if cfg!(target_arch = "wasm32") {
+            return None;
+        }
+        Some(Recording {
+            replay: Replay::new(state),
+            path: format!(
+                "gorillas-{}.{}",
+                miniquad::date::now() as u64,
+                REPLAY_EXTENSION
+            ),
+            ledger: None,
+        })
+    }
+
+    // Carries on a play-by-file match from its throws so far.
+    fn by_file(replay: Replay, ledger: Ledger) -> Self {
+        Recording {
+            replay,
+            path: String::new(),
+            ledger: Some(ledger),
+        }
+    }
+
+    fn push_shot(&mut self, angle: f32, velocity: f32) {
+        self.replay.push_shot(angle, velocity);
+        if self.ledger.is_some() {
+            return;
+        }
+        if let Err(err) = self.replay.save(&self.path) {
+            eprintln!("Could not save replay to {}: {}", self.path, err);
+        }
+    }
+
+    // Writes the turn file for the throw that just landed. Returns its path.
+    fn save_turn(&mut self, state: &GameState) -> Result<String, String> {
+        let Some(ledger) = &mut self.ledger else {
+            return Err("not a play-by-file match".to_string());
+        };
+        let turn = TurnFile::new(self.replay.clone(), state);
+        let path = turn.file_name();
+        turn.save(&path)
+            .and_then(|_| ledger.record(&turn))
+            .map_err(|err| format!("Could not save {}: {}", path, err))?;
+        Ok(path)
+    }
+}
+
+// `--bot 2="python3 my_bot.py"` hands player 2 to an outside program.
+// Returns each seat's command, indexed from 0.
+fn bot_commands_from_args() -> Vec<(usize, String)> {
+    let mut commands = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg != "--bot" {
+            continue;
+        }
+        let Some(spec) = args.next() else {
+            break;
+        };
+        match spec.split_once('=') {
+            Some((player, command)) => match player.trim().parse::<usize>() {
+                Ok(player) if player >= 1 => commands.push((player - 1, command.to_string())),
+                _ => eprintln!("Ignoring --bot {}: bad player number", spec),
+            },
+            None => eprintln!("Ignoring --bot {}: expected <player>=<command>", spec),
+        }
+    }
+    commands
+}
+
+fn spawn_bots(commands: &[(usize, String)], num_players: usize) -> Vec<Option<ExternalBot>> {
+    let mut bots: Vec<Option<ExternalBot>> = (0..num_players).map(|_| None).collect();
+    for (player, command) in commands {
+        if *player >= num_players {
+            continue;
+        }
+        match ExternalBot::spawn(command, DEFAULT_TIMEOUT) {
+            Ok(bot) => bots[*player] = Some(bot),
+            Err(err) => eprintln!("Player {} stays human: {}", player + 1, err),
+        }
+    }
+    bots
 }
 
-struct ShotState {
-    phase: ShotPhase,
-    angle: f32,
+// `--turns` starts a play-by-file match, `--turns <file>` carries one on.
+enum TurnArgs {
+    New,
+    Continue(String),
+}
+
+impl TurnArgs {
+    fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let position = args.iter().position(|arg| arg == "--turns")?;
+        match args.get(position + 1).filter(|arg| !arg.starts_with("--")) {
+            Some(path) => Some(TurnArgs::Continue(path.clone())),
+            None => Some(TurnArgs::New),
+        }
+    }
+}
+
+// Checks that the turn file at `path` can be played here, and sets up to
+// replay it: the earlier throws straight away, the last one at full length.
+fn continue_turns(path: &str) -> Result<(Playback, Recording), String> {
+    let turn = TurnFile::load(path).map_err(|err| err.to_string())?;
+    let ledger = Ledger::open(DEFAULT_LEDGER)
+        .map_err(|err| format!("Could not read {}: {}", DEFAULT_LEDGER, err))?;
+    ledger.check(&turn).map_err(|err| err.to_string())?;
+    turn.verify().map_err(|err| err.to_string())?;
+
+    let playback = Playback {
+        replay: turn.replay.clone(),
+        next_shot: 0,
+        paused: false,
+        step: false,
+        prompt_time: 0.0,
+        instant_until: turn.throws().saturating_sub(1),
+        hand_over: true,
+    };
+    Ok((playback, Recording::by_file(turn.replay, ledger)))
+}
+
+// Drives the Playing phase from a replay instead of the keyboard.
+struct Playback {
+    replay: Replay,
+    next_shot: usize,
+    paused: bool,
+    step: bool,
+    prompt_time: f64,
+    // Shots before this one fly without showing the prompt or the flight.
+    instant_until: usize,
+    // Once the shots run out, the players take over.
+    hand_over: bool,
+}
+
+// How long a replayed throw's angle and velocity stay on screen before the
+// banana flies, at game speed 1.0.
+const PLAYBACK_PROMPT_SECS: f64 = 0.75;
+
+impl Playback {
+    fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--replay" {
+                let path = args.next()?;
+                return match Replay::load(&path) {
+                    Ok(replay) => Some(Playback {
+                        replay,
+                        next_shot: 0,
+                        paused: false,
+                        step: false,
+                        prompt_time: 0.0,
+                        instant_until: 0,
+                        hand_over: false,
+                    }),
+                    Err(err) => {
+                        eprintln!("Could not play {}: {}", path, err);
+                        None
+                    }
+                };
+            }
+        }
+        None
+    }
+}
+
+// Types the next recorded throw into the prompt, then launches it once it has
+// been on screen for a moment (or straight away when stepping).
+fn advance_playback(
+    playback: &mut Playback,
+    state: &mut GameState,
+    shot_state: &mut ShotState,
+    player_num: usize,
+    sim_clock: &mut SimClock,
+) {
+    let Some(shot) = playback.replay.shots.get(playback.next_shot).copied() else {
+        center_text(5, "End of replay");
+        return;
+    };
+
+    if shot_state.phase == ShotPhase::InputAngle {
+        shot_state.angle = shot.angle;
+        shot_state.angle_input = shot.angle.to_string();
+        shot_state.velocity_input = shot.velocity.to_string();
+        shot_state.phase = ShotPhase::InputVelocity;
+        playback.prompt_time = get_time();
+        return;
+    }
+
+    let instant = playback.next_shot < playback.instant_until;
+    let wait = PLAYBACK_PROMPT_SECS / sim_clock.speed;
+    if instant || playback.step || (!playback.paused && get_time() - playback.prompt_time > wait) {
+        // Left set, stepping fast-forwards the flight too.
+        playback.step = instant;
+        playback.next_shot += 1;
+        launch_shot(
+            state,
+            shot_state,
+            player_num,
+            shot.velocity,
+            sim_clock,
+            None,
+            None,
+        );
+    }
+}
+
+// Sends the banana on its way once both prompts have been answered.
+fn launch_shot(
+    state: &mut GameState,
+    shot_state: &mut ShotState,
+    player_num: usize,
     velocity: f32,
-    angle_input: String,
-    velocity_input: String,
-    t: f32,
-    impact: bool,
-    on_screen: bool,
-    player_hit: Option<usize>,
-    x: f32,
-    y: f32,
+    sim_clock: &mut SimClock,
+    recording: Option<&mut Recording>,
+    peer: Option<&mut Peer>,
+) {
+    let angle = shot_state.angle;
+    shot_state.aim(state.faces_left(player_num), angle, velocity);
+    state.sun_hit = false;
+    sim_clock.reset();
+    if let Some(recording) = recording {
+        recording.push_shot(angle, velocity);
+    }
+    if let Some(peer) = peer {
+        // A failed send means the connection is gone, which the next poll
+        // reports.
+        let _ = peer.send_throw(angle, velocity);
+    }
 }
 
-impl ShotState {
-    fn new() -> Self {
-        ShotState {
-            phase: ShotPhase::InputAngle,
-            angle: 0.0,
-            velocity: 0.0,
-            angle_input: String::new(),
-            velocity_input: String::new(),
-            t: 0.0,
-            impact: false,
-            on_screen: true,
-            player_hit: None,
-            x: 0.0,
-            y: 0.0,
+// Gets both machines ready for a network match. Returns whether the match can
+// start; offline that is straight away.
+fn net_lobby(
+    listener: Option<&Listener>,
+    room: &mut Option<Room>,
+    peer: &mut Option<Peer>,
+    state: &mut GameState,
+) -> Result<bool, NetError> {
+    if peer.is_none() {
+        if let Some(listener) = listener {
+            center_text(
+                12,
+                &format!("Waiting for an opponent on port {}...", listener.port()),
+            );
+            *peer = listener.try_accept()?;
+        } else if let Some(waiting) = room {
+            center_text(10, &format!("Room {}", waiting.code));
+            center_text(12, "Waiting for an opponent to join this room...");
+            *peer = waiting.try_pair()?;
+            if peer.is_some() {
+                *room = None;
+            }
+        } else {
+            return Ok(true);
+        }
+        if peer.is_none() {
+            return Ok(false);
+        }
+    }
+
+    let peer = peer.as_mut().unwrap();
+    if peer.role == Role::Host {
+        peer.send_settings(state)?;
+        return Ok(true);
+    }
+    peer.poll()?;
+    match peer.take_settings() {
+        Some(settings) => {
+            *state = GameState::new();
+            settings.apply_settings(state);
+            Ok(true)
+        }
+        None => {
+            center_text(12, "Waiting for the host to set up the match...");
+            Ok(false)
+        }
+    }
+}
+
+// Ends a network match that cannot go on. Returns what to tell the player.
+fn hang_up(peer: &mut Option<Peer>, err: NetError) -> String {
+    if let Some(peer) = peer.take() {
+        peer.close();
+    }
+    eprintln!("{}", err);
+    err.to_string()
+}
+
+// How to reach the other machine, from `--host [port]` or `--join <addr>`.
+enum NetMode {
+    Host(u16),
+    Join(String),
+}
+
+impl NetMode {
+    fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let position = args
+            .iter()
+            .position(|arg| arg == "--host" || arg == "--join")?;
+        let value = args
+            .get(position + 1)
+            .filter(|value| !value.starts_with("--"));
+        if args[position] == "--host" {
+            let port = value.and_then(|port| port.parse().ok());
+            Some(NetMode::Host(port.unwrap_or(DEFAULT_PORT)))
+        } else {
+            let addr = value?.clone();
+            if addr.contains(':') {
+                Some(NetMode::Join(addr))
+            } else {
+                Some(NetMode::Join(format!("{}:{}", addr, DEFAULT_PORT)))
+            }
         }
     }
 }
 
-fn fn_ran(x: i32) -> i32 {
-    gen_range(1, x + 1)
+// A computer player's throw, fed to the prompt one keystroke at a time so it
+// reads as if someone typed it. '\n' stands for Enter.
+struct AiTyping {
+    keys: VecDeque<char>,
+    next_key_time: f64,
+}
+
+const AI_THINK_SECS: f64 = 0.6;
+const AI_KEY_SECS: f64 = 0.15;
+
+impl AiTyping {
+    fn new(angle: f32, velocity: f32) -> Self {
+        AiTyping {
+            keys: format!("{}\n{}\n", angle, velocity).chars().collect(),
+            next_key_time: get_time() + AI_THINK_SECS,
+        }
+    }
 }
 
 fn window_conf() -> Conf {
@@ -166,894 +465,1434 @@ fn window_conf() -> Conf {
     }
 }
 
-fn get_scale() -> (f32, f32, f32) {
-    let sw = screen_width();
-    let sh = screen_height();
-    let scale = (sw / VIRTUAL_WIDTH).min(sh / VIRTUAL_HEIGHT);
-    let offset_x = (sw - VIRTUAL_WIDTH * scale) / 2.0;
-    let offset_y = (sh - VIRTUAL_HEIGHT * scale) / 2.0;
-    (scale, offset_x, offset_y)
+// How the 640x350 playfield fills the window.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ScaleMode {
+    // The largest whole multiple that fits, so every EGA pixel is the same
+    // size.
+    Integer,
+    // As large as fits while keeping the shape.
+    Fit,
+    // The whole window, shape or not.
+    Stretch,
 }
 
-fn draw_scaled_rect(x: f32, y: f32, w: f32, h: f32, color: Color) {
-    let (scale, ox, oy) = get_scale();
-    draw_rectangle(ox + x * scale, oy + y * scale, w * scale, h * scale, color);
+impl ScaleMode {
+    const ALL: [ScaleMode; 3] = [ScaleMode::Integer, ScaleMode::Fit, ScaleMode::Stretch];
+    const SETTINGS_NAME: &'static str = "scale.cfg";
+
+    fn name(self) -> &'static str {
+        match self {
+            ScaleMode::Integer => "integer",
+            ScaleMode::Fit => "fit",
+            ScaleMode::Stretch => "stretch",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        ScaleMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == name.trim())
+    }
+
+    // `--scale integer|fit|stretch`, or else the mode picked last time.
+    fn from_args_or_saved() -> Self {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if let Some(position) = args.iter().position(|arg| arg == "--scale") {
+            match args
+                .get(position + 1)
+                .and_then(|name| Self::from_name(name))
+            {
+                Some(mode) => return mode,
+                None => eprintln!("--scale takes integer, fit or stretch"),
+            }
+        }
+        storage::load(Self::SETTINGS_NAME)
+            .and_then(|text| Self::from_name(&text))
+            .unwrap_or(ScaleMode::Integer)
+    }
+
+    fn next(self) -> Self {
+        let index = ScaleMode::ALL
+            .iter()
+            .position(|&mode| mode == self)
+            .unwrap();
+        ScaleMode::ALL[(index + 1) % ScaleMode::ALL.len()]
+    }
+
+    // Where an image of `image` pixels goes in a window of the given size.
+    fn placement(self, image: Vec2, width: f32, height: f32) -> Rect {
+        let fit = (width / image.x).min(height / image.y);
+        let (w, h) = match self {
+            ScaleMode::Stretch => return Rect::new(0.0, 0.0, width, height),
+            // Windows smaller than the image get it shrunk to fit.
+            ScaleMode::Integer if fit >= 1.0 => (image.x * fit.floor(), image.y * fit.floor()),
+            _ => (image.x * fit, image.y * fit),
+        };
+        Rect::new(
+            ((width - w) / 2.0).floor(),
+            ((height - h) / 2.0).floor(),
+            w,
+            h,
+        )
+    }
 }
 
-fn draw_scaled_circle(x: f32, y: f32, r: f32, color: Color) {
-    let (scale, ox, oy) = get_scale();
-    draw_circle(ox + x * scale, oy + y * scale, r * scale, color);
+// An offscreen image, drawn on in its own pixels from the top left.
+struct Canvas {
+    target: RenderTarget,
+    camera: Camera2D,
 }
 
-fn draw_scaled_line(x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
-    let (scale, ox, oy) = get_scale();
-    draw_line(
-        ox + x1 * scale,
-        oy + y1 * scale,
-        ox + x2 * scale,
-        oy + y2 * scale,
-        thickness * scale,
-        color,
-    );
+impl Canvas {
+    fn new(width: f32, height: f32) -> Self {
+        Canvas::with_view(
+            width as u32,
+            height as u32,
+            Rect::new(0.0, 0.0, width, height),
+        )
+    }
+
+    // An image of `width` by `height` pixels that `view` is drawn into, so
+    // it can be drawn on in units other than its own pixels.
+    fn with_view(width: u32, height: u32, view: Rect) -> Self {
+        let target = render_target(width, height);
+        target.texture.set_filter(FilterMode::Nearest);
+        let mut camera = Camera2D::from_display_rect(view);
+        camera.render_target = Some(target.clone());
+        Canvas { target, camera }
+    }
+
+    fn texture(&self) -> &Texture2D {
+        &self.target.texture
+    }
+
+    // Directs drawing here until another camera is set.
+    fn begin(&self) {
+        set_camera(&self.camera);
+    }
+
+    // Wipes the image to transparent and draws it afresh. Drawing goes back
+    // to `screen` afterwards.
+    fn repaint(&self, screen: &Screen, paint: impl FnOnce()) {
+        self.begin();
+        clear_background(BLANK);
+        paint();
+        screen.begin();
+    }
+
+    // Copies `source`, in the image's pixels, with its top left at `x`, `y`
+    // of wherever drawing is going.
+    fn draw(&self, source: Rect, x: f32, y: f32) {
+        // Render targets come out upside down, rows and all.
+        let height = self.texture().height();
+        let source = Rect::new(source.x, height - source.y - source.h, source.w, source.h);
+        draw_texture_ex(
+            self.texture(),
+            x.round(),
+            y.round(),
+            ink(WHITE),
+            DrawTextureParams {
+                source: Some(source),
+                flip_y: true,
+                ..Default::default()
+            },
+        );
+    }
 }
 
-fn draw_scaled_text(text: &str, x: f32, y: f32, font_size: f32, color: Color) {
-    let (scale, ox, oy) = get_scale();
-    draw_text(
-        text,
-        ox + x * scale,
-        oy + y * scale,
-        font_size * scale,
-        color,
-    );
+// Everything is drawn in playfield pixels into an offscreen image at the
+// display mode's resolution, which is blown up with hard pixel edges once a
+// frame. Text is printed over it from a second image at the EGA's
+// resolution, so it stays readable in CGA.
+struct Screen {
+    canvas: Canvas,
+    text: Canvas,
+    font: Texture2D,
+    mode: ScaleMode,
 }
 
-fn draw_scaled_arc(
-    cx: f32,
-    cy: f32,
-    radius: f32,
-    thickness: f32,
-    start_angle: f32,
-    sweep: f32,
-    color: Color,
-) {
-    let (scale, ox, oy) = get_scale();
-    let steps = 16;
-    for i in 0..steps {
-        let a1 = start_angle + sweep * (i as f32 / steps as f32);
-        let a2 = start_angle + sweep * ((i + 1) as f32 / steps as f32);
-        let x1 = ox + (cx + radius * a1.cos()) * scale;
-        let y1 = oy + (cy + radius * a1.sin()) * scale;
-        let x2 = ox + (cx + radius * a2.cos()) * scale;
-        let y2 = oy + (cy + radius * a2.sin()) * scale;
-        draw_line(x1, y1, x2, y2, thickness * scale, color);
-    }
-}
-
-fn center_text(row: f32, text: &str) {
-    let (scale, _, _) = get_scale();
-    let font_size = 20.0;
-    let text_width = measure_text(text, None, (font_size * scale) as u16, 1.0).width;
-    let (_, ox, oy) = get_scale();
-    let x = ox + (VIRTUAL_WIDTH * scale - text_width) / 2.0;
-    let y = oy + row * 14.0 * scale;
-    draw_text(text, x, y, font_size * scale, WHITE);
-}
-
-fn draw_sun(mouth_shocked: bool) {
-    let x = VIRTUAL_WIDTH / 2.0;
-    let y = 25.0;
-
-    draw_scaled_rect(x - 22.0, y - 18.0, 44.0, 36.0, BACK_COLOR);
-    draw_scaled_circle(x, y, 12.0, SUN_ATTR);
-
-    draw_scaled_line(x - 20.0, y, x + 20.0, y, 2.0, SUN_ATTR);
-    draw_scaled_line(x, y - 15.0, x, y + 15.0, 2.0, SUN_ATTR);
-    draw_scaled_line(x - 15.0, y - 10.0, x + 15.0, y + 10.0, 2.0, SUN_ATTR);
-    draw_scaled_line(x - 15.0, y + 10.0, x + 15.0, y - 10.0, 2.0, SUN_ATTR);
-    draw_scaled_line(x - 8.0, y - 13.0, x + 8.0, y + 13.0, 2.0, SUN_ATTR);
-    draw_scaled_line(x - 8.0, y + 13.0, x + 8.0, y - 13.0, 2.0, SUN_ATTR);
-    draw_scaled_line(x - 18.0, y - 5.0, x + 18.0, y + 5.0, 2.0, SUN_ATTR);
-    draw_scaled_line(x - 18.0, y + 5.0, x + 18.0, y - 5.0, 2.0, SUN_ATTR);
-
-    if mouth_shocked {
-        draw_scaled_circle(x, y + 5.0, 3.0, BLACK);
-    } else {
-        // QBasic CIRCLE arc from 210 to 330 degrees draws a smile
-        // In screen coords (Y down), we need to draw from 30 to 150 degrees
-        let start_angle = 30.0 * PI / 180.0;
-        let end_angle = 150.0 * PI / 180.0;
-        let steps = 20;
-        for i in 0..steps {
-            let a1 = start_angle + (end_angle - start_angle) * (i as f32 / steps as f32);
-            let a2 = start_angle + (end_angle - start_angle) * ((i + 1) as f32 / steps as f32);
-            let x1 = x + 8.0 * a1.cos();
-            let y1 = y + 8.0 * a1.sin();
-            let x2 = x + 8.0 * a2.cos();
-            let y2 = y + 8.0 * a2.sin();
-            draw_scaled_line(x1, y1, x2, y2, 1.0, BLACK);
+impl Screen {
+    fn new(mode: ScaleMode) -> Self {
+        let (width, height) = display_mode().resolution();
+        let playfield = Rect::new(0.0, 0.0, VIRTUAL_WIDTH, VIRTUAL_HEIGHT);
+        Screen {
+            canvas: Canvas::with_view(width, height, playfield),
+            text: Canvas::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
+            font: font_texture(),
+            mode,
         }
     }
 
-    draw_scaled_circle(x - 3.0, y - 2.0, 1.5, BLACK);
-    draw_scaled_circle(x + 3.0, y - 2.0, 1.5, BLACK);
-}
-
-fn draw_gorilla(x: f32, y: f32, arms: i32) {
-    draw_scaled_rect(x - 4.0, y, 7.0, 6.0, OBJECT_COLOR);
-    draw_scaled_rect(x - 5.0, y + 2.0, 9.0, 2.0, OBJECT_COLOR);
-    draw_scaled_line(x - 3.0, y + 2.0, x + 2.0, y + 2.0, 1.0, BLACK);
-    draw_scaled_circle(x - 1.5, y + 4.0, 0.8, BLACK);
-    draw_scaled_circle(x + 1.5, y + 4.0, 0.8, BLACK);
-    draw_scaled_line(x - 3.0, y + 7.0, x + 2.0, y + 7.0, 1.0, OBJECT_COLOR);
-    draw_scaled_rect(x - 8.0, y + 8.0, 15.0, 6.0, OBJECT_COLOR);
-    draw_scaled_rect(x - 6.0, y + 15.0, 11.0, 5.0, OBJECT_COLOR);
-
-    for i in 0..5 {
-        let offset = i as f32;
-        draw_scaled_arc(
-            x + offset,
-            y + 25.0,
-            10.0,
-            1.0,
-            3.0 * PI / 4.0,
-            9.0 * PI / 8.0 - 3.0 * PI / 4.0,
-            OBJECT_COLOR,
-        );
-        draw_scaled_arc(
-            x - 6.0 + offset,
-            y + 25.0,
-            10.0,
-            1.0,
-            15.0 * PI / 8.0,
-            2.0 * PI + PI / 4.0 - 15.0 * PI / 8.0,
-            OBJECT_COLOR,
-        );
+    fn begin(&self) {
+        self.canvas.begin();
     }
 
-    draw_scaled_arc(
-        x - 5.0,
-        y + 10.0,
-        5.0,
-        1.0,
-        3.0 * PI / 2.0,
-        2.0 * PI - 3.0 * PI / 2.0,
-        BLACK,
-    );
-    draw_scaled_arc(x + 5.0, y + 10.0, 5.0, 1.0, PI, 3.0 * PI / 2.0 - PI, BLACK);
-
-    for i in -5..0 {
-        let offset = i as f32;
-        match arms {
-            1 => {
-                draw_scaled_arc(
-                    x + offset,
-                    y + 14.0,
-                    9.0,
-                    1.0,
-                    3.0 * PI / 4.0,
-                    5.0 * PI / 4.0 - 3.0 * PI / 4.0,
-                    OBJECT_COLOR,
-                );
-                draw_scaled_arc(
-                    x + 5.0 + offset,
-                    y + 4.0,
-                    9.0,
-                    1.0,
-                    7.0 * PI / 4.0,
-                    2.0 * PI + PI / 4.0 - 7.0 * PI / 4.0,
-                    OBJECT_COLOR,
-                );
-            }
-            2 => {
-                draw_scaled_arc(
-                    x + offset,
-                    y + 4.0,
-                    9.0,
-                    1.0,
-                    3.0 * PI / 4.0,
-                    5.0 * PI / 4.0 - 3.0 * PI / 4.0,
-                    OBJECT_COLOR,
-                );
-                draw_scaled_arc(
-                    x + 5.0 + offset,
-                    y + 14.0,
-                    9.0,
-                    1.0,
-                    7.0 * PI / 4.0,
-                    2.0 * PI + PI / 4.0 - 7.0 * PI / 4.0,
-                    OBJECT_COLOR,
-                );
-            }
-            _ => {
-                draw_scaled_arc(
-                    x + offset,
-                    y + 14.0,
-                    9.0,
-                    1.0,
-                    3.0 * PI / 4.0,
-                    5.0 * PI / 4.0 - 3.0 * PI / 4.0,
-                    OBJECT_COLOR,
-                );
-                draw_scaled_arc(
-                    x + 5.0 + offset,
-                    y + 14.0,
-                    9.0,
-                    1.0,
-                    7.0 * PI / 4.0,
-                    2.0 * PI + PI / 4.0 - 7.0 * PI / 4.0,
-                    OBJECT_COLOR,
+    fn cycle_mode(&mut self) {
+        self.mode = self.mode.next();
+        if let Err(err) = storage::save(ScaleMode::SETTINGS_NAME, self.mode.name()) {
+            eprintln!("Could not save the scale mode: {}", err);
+        }
+    }
+
+    // Draws the frame's printed text into its image and empties the queue.
+    fn print_text(&self) {
+        self.text.begin();
+        clear_background(BLANK);
+        for print in PRINTS.lock().unwrap().drain(..) {
+            for (i, c) in print.text.chars().enumerate() {
+                let index = font::glyph_index(c);
+                draw_texture_ex(
+                    &self.font,
+                    print.x + (i * font::GLYPH_WIDTH) as f32,
+                    print.y,
+                    print.color,
+                    DrawTextureParams {
+                        source: Some(Rect::new(
+                            (index * font::GLYPH_WIDTH) as f32,
+                            0.0,
+                            font::GLYPH_WIDTH as f32,
+                            font::GLYPH_HEIGHT as f32,
+                        )),
+                        ..Default::default()
+                    },
                 );
             }
         }
     }
-}
 
-fn draw_banana(xc: f32, yc: f32, rotation: i32) {
-    match rotation % 4 {
-        0 => draw_scaled_arc(xc + 4.0, yc + 3.0, 4.0, 3.0, PI / 2.0, PI, YELLOW),
-        1 => draw_scaled_arc(xc + 3.0, yc, 4.0, 3.0, 0.0, PI, YELLOW),
-        2 => draw_scaled_arc(xc + 3.0, yc + 6.0, 4.0, 3.0, PI, PI, YELLOW),
-        3 => draw_scaled_arc(xc, yc + 3.0, 4.0, 3.0, 3.0 * PI / 2.0, PI, YELLOW),
-        _ => {}
+    // Puts the frame on the window and waits for the next one.
+    async fn show(&self) {
+        self.print_text();
+        set_default_camera();
+        clear_background(BLACK);
+        let image = self.canvas.texture().size();
+        let place = self.mode.placement(image, screen_width(), screen_height());
+        for layer in [&self.canvas, &self.text] {
+            draw_texture_ex(
+                layer.texture(),
+                place.x,
+                place.y,
+                ink(WHITE),
+                DrawTextureParams {
+                    dest_size: Some(vec2(place.w, place.h)),
+                    // Render targets come out upside down.
+                    flip_y: true,
+                    ..Default::default()
+                },
+            );
+        }
+        next_frame().await;
+        self.begin();
     }
 }
 
-fn generate_cityscape(state: &mut GameState) {
-    let mut x = 2.0;
-    let slope = fn_ran(6);
-    let mut new_ht: f32 = match slope {
-        1 => 15.0,
-        2 => 130.0,
-        3..=5 => 15.0,
-        _ => 130.0,
-    };
-
-    let bottom_line = 335.0;
-    let ht_inc = 10.0;
-    let def_b_width = 37.0;
-    let random_height = 120.0;
-    let w_width = 3.0;
-    let w_height = 6.0;
-    let w_dif_v = 15.0;
-    let w_dif_h = 10.0;
-    let max_height = SUN_HT + 10.0;
-
-    let mut cur_building = 1;
-    state.buildings.clear();
-
-    while x <= VIRTUAL_WIDTH - ht_inc {
-        match slope {
-            1 => new_ht += ht_inc,
-            2 => new_ht -= ht_inc,
-            3..=5 => {
-                if x > VIRTUAL_WIDTH / 2.0 {
-                    new_ht -= 2.0 * ht_inc;
-                } else {
-                    new_ht += 2.0 * ht_inc;
-                }
-            }
-            _ => {
-                if x > VIRTUAL_WIDTH / 2.0 {
-                    new_ht += 2.0 * ht_inc;
-                } else {
-                    new_ht -= 2.0 * ht_inc;
+// The font's glyphs side by side in one strip, white where they're inked.
+fn font_texture() -> Texture2D {
+    let count = font::GLYPH_COUNT;
+    let width = count * font::GLYPH_WIDTH;
+    let mut pixels = vec![0u8; width * font::GLYPH_HEIGHT * 4];
+    for index in 0..count {
+        for (row, bits) in font::glyph_at(index).iter().enumerate() {
+            for column in 0..font::GLYPH_WIDTH {
+                if bits & (0x80 >> column) != 0 {
+                    let at = (row * width + index * font::GLYPH_WIDTH + column) * 4;
+                    pixels[at..at + 4].fill(255);
                 }
             }
         }
+    }
+    let texture = Texture2D::from_rgba8(width as u16, font::GLYPH_HEIGHT as u16, &pixels);
+    texture.set_filter(FilterMode::Nearest);
+    texture
+}
 
-        let mut b_width = fn_ran(def_b_width as i32) as f32 + def_b_width;
-        if x + b_width > VIRTUAL_WIDTH {
-            b_width = VIRTUAL_WIDTH - x - 2.0;
-        }
+// Text waiting to be printed over the current frame.
+struct Print {
+    x: f32,
+    y: f32,
+    text: String,
+    color: Color,
+}
 
-        let mut b_height = fn_ran(random_height as i32) as f32 + new_ht;
-        if b_height < ht_inc {
-            b_height = ht_inc;
-        }
-        if bottom_line - b_height <= max_height + G_HEIGHT {
-            b_height = max_height + G_HEIGHT - 5.0;
-        }
+static PRINTS: Mutex<Vec<Print>> = Mutex::new(Vec::new());
 
-        if cur_building < state.bcoor.len() {
-            state.bcoor[cur_building].x_coor = x as i32;
-            state.bcoor[cur_building].y_coor = (bottom_line - b_height) as i32;
-        }
+// Prints `text` from `row` and `column` of the 80x25 text screen, counted
+// from 1 like QBasic's LOCATE.
+fn locate_print(row: usize, column: usize, text: &str, color: Color) {
+    let (x, y) = font::locate(row, column);
+    print_at(x, y, text, color);
+}
 
-        let building_color = BUILDING_COLORS[fn_ran(4) as usize - 1];
+// Prints `text` with its top left at a playfield point, for labels that
+// follow something in the picture rather than the grid.
+fn print_at(x: f32, y: f32, text: &str, color: Color) {
+    PRINTS.lock().unwrap().push(Print {
+        x: x.round(),
+        y: y.round(),
+        text: text.to_string(),
+        color,
+    });
+}
 
-        let mut windows = Vec::new();
-        let mut c = x + 3.0;
-        while c < x + b_width - 3.0 {
-            let mut i = b_height - 3.0;
-            while i >= 7.0 {
-                let win_color = if fn_ran(4) == 1 {
-                    DARKGRAY
-                } else {
-                    WINDOW_COLOR
-                };
-                windows.push(Window {
-                    x: c,
-                    y: bottom_line - i,
-                    width: w_width,
-                    height: w_height,
-                    color: win_color,
-                });
-                i -= w_dif_v;
-            }
-            c += w_dif_h;
-        }
+// Draws into whatever macroquad is drawing to.
+struct Gpu;
+
+impl Renderer for Gpu {
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgb) {
+        draw_rectangle(x, y, width, height, rgb(color));
+    }
+
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Rgb) {
+        draw_circle(x, y, radius, rgb(color));
+    }
 
-        state.buildings.push(Building {
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Rgb) {
+        draw_line(x1, y1, x2, y2, thickness, rgb(color));
+    }
+
+    fn arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        thickness: f32,
+        start: f32,
+        sweep: f32,
+        color: Rgb,
+    ) {
+        // macroquad's arcs grow outwards from their radius.
+        draw_arc(
             x,
-            y: bottom_line - b_height,
-            width: b_width,
-            height: b_height,
-            color: building_color,
-            windows,
-        });
+            y,
+            32,
+            radius - thickness / 2.0,
+            start.to_degrees(),
+            thickness,
+            sweep.to_degrees(),
+            rgb(color),
+        );
+    }
+}
+
+// Reads the keyboard and mouse through macroquad.
+struct Keyboard;
+
+// The keys that count as `Key::Char`, and their characters.
+const CHAR_KEYS: [(KeyCode, char); 51] = [
+    (KeyCode::A, 'A'),
+    (KeyCode::B, 'B'),
+    (KeyCode::C, 'C'),
+    (KeyCode::D, 'D'),
+    (KeyCode::E, 'E'),
+    (KeyCode::F, 'F'),
+    (KeyCode::G, 'G'),
+    (KeyCode::H, 'H'),
+    (KeyCode::I, 'I'),
+    (KeyCode::J, 'J'),
+    (KeyCode::K, 'K'),
+    (KeyCode::L, 'L'),
+    (KeyCode::M, 'M'),
+    (KeyCode::N, 'N'),
+    (KeyCode::O, 'O'),
+    (KeyCode::P, 'P'),
+    (KeyCode::Q, 'Q'),
+    (KeyCode::R, 'R'),
+    (KeyCode::S, 'S'),
+    (KeyCode::T, 'T'),
+    (KeyCode::U, 'U'),
+    (KeyCode::V, 'V'),
+    (KeyCode::W, 'W'),
+    (KeyCode::X, 'X'),
+    (KeyCode::Y, 'Y'),
+    (KeyCode::Z, 'Z'),
+    (KeyCode::Key0, '0'),
+    (KeyCode::Key1, '1'),
+    (KeyCode::Key2, '2'),
+    (KeyCode::Key3, '3'),
+    (KeyCode::Key4, '4'),
+    (KeyCode::Key5, '5'),
+    (KeyCode::Key6, '6'),
+    (KeyCode::Key7, '7'),
+    (KeyCode::Key8, '8'),
+    (KeyCode::Key9, '9'),
+    (KeyCode::Apostrophe, '\''),
+    (KeyCode::Comma, ','),
+    (KeyCode::Minus, '-'),
+    (KeyCode::Period, '.'),
+    (KeyCode::Slash, '/'),
+    (KeyCode::Semicolon, ';'),
+    (KeyCode::Equal, '='),
+    (KeyCode::LeftBracket, '['),
+    (KeyCode::Backslash, '\\'),
+    (KeyCode::RightBracket, ']'),
+    (KeyCode::GraveAccent, '`'),
+    (KeyCode::KpAdd, '+'),
+    (KeyCode::KpSubtract, '-'),
+    (KeyCode::KpMultiply, '*'),
+    (KeyCode::KpDivide, '/'),
+];
 
-        x += b_width + 2.0;
-        cur_building += 1;
+fn key_from(code: KeyCode) -> Key {
+    match code {
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Space => Key::Space,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::F2 => Key::F2,
+        KeyCode::F3 => Key::F3,
+        KeyCode::F4 => Key::F4,
+        KeyCode::F6 => Key::F6,
+        KeyCode::LeftControl | KeyCode::RightControl | KeyCode::LeftSuper | KeyCode::RightSuper => {
+            Key::Control
+        }
+        _ => CHAR_KEYS
+            .iter()
+            .find(|&&(key_code, _)| key_code == code)
+            .map_or(Key::Other, |&(_, c)| Key::Char(c)),
     }
+}
 
-    state.last_building = cur_building - 1;
+impl InputSource for Keyboard {
+    fn next_frame(&mut self, _time: f64) {}
 
-    state.wind = fn_ran(10) - 5;
-    if fn_ran(3) == 1 {
-        if state.wind > 0 {
-            state.wind += fn_ran(10);
-        } else {
-            state.wind -= fn_ran(10);
+    fn last_key_pressed(&self) -> Option<Key> {
+        get_last_key_pressed().map(key_from)
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        get_keys_pressed()
+            .into_iter()
+            .any(|code| key_from(code) == key)
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        get_keys_down()
+            .into_iter()
+            .any(|code| key_from(code) == key)
+    }
+
+    fn char_pressed(&mut self) -> Option<char> {
+        get_char_pressed()
+    }
+
+    fn clicked(&self) -> bool {
+        is_mouse_button_pressed(MouseButton::Left)
+    }
+}
+
+// The keyboard, or the script given with --input.
+fn input_from_args() -> Box<dyn InputSource> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--input")
+        .and_then(|position| args.get(position + 1))
+    else {
+        return Box::new(Keyboard);
+    };
+    match Script::load(path) {
+        Ok(script) => Box::new(script),
+        Err(err) => {
+            eprintln!("Cannot play {}: {}", path, err);
+            Box::new(Keyboard)
         }
     }
+}
 
-    state.cityscape_generated = true;
+fn center_text(row: usize, text: &str) {
+    locate_print(row, font::centered_column(text), text, ink(WHITE));
 }
 
-fn draw_cityscape(state: &GameState) {
-    for building in &state.buildings {
-        draw_scaled_rect(
-            building.x - 1.0,
-            building.y - 1.0,
-            building.width + 2.0,
-            building.height + 2.0,
-            BACK_COLOR,
-        );
-        draw_scaled_rect(
-            building.x,
-            building.y,
-            building.width,
-            building.height,
-            building.color,
-        );
-        for window in &building.windows {
-            draw_scaled_rect(
-                window.x,
-                window.y,
-                window.width,
-                window.height,
-                window.color,
-            );
+// The buildings, drawn once into their own image and only drawn again when a
+// new map is dealt or an explosion takes a bite out of them.
+struct Skyline {
+    canvas: Canvas,
+    fingerprint: Option<u64>,
+}
+
+impl Skyline {
+    fn new() -> Self {
+        Skyline {
+            canvas: Canvas::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT),
+            fingerprint: None,
         }
     }
 
-    if state.wind != 0 {
-        let wind_line = (state.wind * 3 * 2) as f32;
-        draw_scaled_line(
-            VIRTUAL_WIDTH / 2.0,
-            VIRTUAL_HEIGHT - 5.0,
-            VIRTUAL_WIDTH / 2.0 + wind_line,
-            VIRTUAL_HEIGHT - 5.0,
-            2.0,
-            EXPLOSION_COLOR,
-        );
-        let arrow_dir: f32 = if state.wind > 0 { -4.0 } else { 4.0 };
-        draw_scaled_line(
-            VIRTUAL_WIDTH / 2.0 + wind_line,
-            VIRTUAL_HEIGHT - 5.0,
-            VIRTUAL_WIDTH / 2.0 + wind_line + arrow_dir,
-            VIRTUAL_HEIGHT - 7.0,
-            2.0,
-            EXPLOSION_COLOR,
-        );
-        draw_scaled_line(
-            VIRTUAL_WIDTH / 2.0 + wind_line,
-            VIRTUAL_HEIGHT - 5.0,
-            VIRTUAL_WIDTH / 2.0 + wind_line + arrow_dir,
-            VIRTUAL_HEIGHT - 3.0,
-            2.0,
-            EXPLOSION_COLOR,
-        );
+    fn draw(&mut self, state: &GameState, screen: &Screen) {
+        let fingerprint = state.skyline_fingerprint();
+        if self.fingerprint != Some(fingerprint) {
+            self.canvas
+                .repaint(screen, || paint_cityscape(&mut Gpu, scheme(), state));
+            self.fingerprint = Some(fingerprint);
+        }
+        self.canvas
+            .draw(Rect::new(0.0, 0.0, VIRTUAL_WIDTH, VIRTUAL_HEIGHT), 0.0, 0.0);
     }
 }
 
-fn place_gorillas(state: &mut GameState) {
-    let x_adj = 14.0;
-    let y_adj = 30.0;
-
-    for i in 0..2 {
-        let b_num = if i == 0 {
-            fn_ran(2) as usize + 1
-        } else {
-            state.last_building - fn_ran(2) as usize
-        };
+// The sprite atlas: a cell for each gorilla pose, in the order of the arm
+// constants from `RIGHT_UP`, and a row of banana rotations under them. The
+// anchor is where the x, y a gorilla or banana is drawn at lands in its cell.
+const GORILLA_POSES: [i32; 3] = [RIGHT_UP, LEFT_UP, ARMS_DOWN];
+const GORILLA_CELL: (f32, f32) = (40.0, 48.0);
+const GORILLA_ANCHOR: (f32, f32) = (20.0, 8.0);
+const BANANA_ROTATIONS: i32 = 4;
+const BANANA_CELL: f32 = 20.0;
+const BANANA_ANCHOR: f32 = 6.0;
+
+struct Sprites {
+    atlas: Canvas,
+}
 
-        let b_width = if b_num + 1 < state.bcoor.len() {
-            (state.bcoor[b_num + 1].x_coor - state.bcoor[b_num].x_coor) as f32
-        } else {
-            50.0
-        };
+impl Sprites {
+    fn new(screen: &Screen) -> Self {
+        let atlas = Canvas::new(
+            GORILLA_CELL.0 * GORILLA_POSES.len() as f32,
+            GORILLA_CELL.1 + BANANA_CELL,
+        );
+        atlas.repaint(screen, || {
+            for (i, &arms) in GORILLA_POSES.iter().enumerate() {
+                let x = i as f32 * GORILLA_CELL.0 + GORILLA_ANCHOR.0;
+                paint_gorilla(&mut Gpu, scheme(), x, GORILLA_ANCHOR.1, arms);
+            }
+            for rotation in 0..BANANA_ROTATIONS {
+                let x = rotation as f32 * BANANA_CELL + BANANA_ANCHOR;
+                paint_banana(
+                    &mut Gpu,
+                    scheme(),
+                    x,
+                    GORILLA_CELL.1 + BANANA_ANCHOR,
+                    rotation,
+                );
+            }
+        });
+        Sprites { atlas }
+    }
 
-        state.gorilla_x[i] = state.bcoor[b_num].x_coor as f32 + b_width / 2.0 - x_adj;
-        state.gorilla_y[i] = state.bcoor[b_num].y_coor as f32 - y_adj;
+    fn gorilla(&self, x: f32, y: f32, arms: i32) {
+        let cell = GORILLA_POSES
+            .iter()
+            .position(|&pose| pose == arms)
+            .unwrap_or(GORILLA_POSES.len() - 1);
+        let (w, h) = GORILLA_CELL;
+        self.atlas.draw(
+            Rect::new(cell as f32 * w, 0.0, w, h),
+            x - GORILLA_ANCHOR.0,
+            y - GORILLA_ANCHOR.1,
+        );
     }
-}
 
-fn do_explosion(x: f32, y: f32) {
-    let radius = VIRTUAL_HEIGHT / 50.0;
-    let mut c = 0.0;
-    while c <= radius {
-        draw_scaled_circle(x, y, c, EXPLOSION_COLOR);
-        c += 0.5;
+    fn banana(&self, x: f32, y: f32, rotation: i32) {
+        let cell = rotation.rem_euclid(BANANA_ROTATIONS) as f32;
+        self.atlas.draw(
+            Rect::new(cell * BANANA_CELL, GORILLA_CELL.1, BANANA_CELL, BANANA_CELL),
+            x - BANANA_ANCHOR,
+            y - BANANA_ANCHOR,
+        );
     }
 }
 
 fn draw_intro_screen(sparkle_offset: i32) {
     clear_background(BLACK);
+    draw_sparkles(sparkle_offset);
+
+    center_text(4, "Q B a s i c    G O R I L L A S");
+    for (row, line) in [
+        (6, "Copyright (C) IBM Corporation 1991"),
+        (8, "Your mission is to hit your opponent with the exploding"),
+        (
+            9,
+            "banana by varying the angle and power of your throw, taking",
+        ),
+        (
+            10,
+            "into account wind speed, gravity, and the city skyline.",
+        ),
+        (
+            11,
+            "The wind speed is shown by a directional arrow at the bottom",
+        ),
+        (
+            12,
+            "of the playing field, its length relative to its strength.",
+        ),
+        (24, "Press any key to continue"),
+    ] {
+        locate_print(row, font::centered_column(line), line, ink(GRAY));
+    }
+}
 
+// The border of stars that runs around the intro and game over screens.
+fn draw_sparkles(sparkle_offset: i32) {
     let sparkle_chars = "*    ";
     for i in 0..80 {
         let idx = ((i + sparkle_offset) % 5) as usize;
         if sparkle_chars.chars().nth(idx) == Some('*') {
-            draw_scaled_text("*", i as f32 * 8.0, 14.0, 20.0, RED);
-            draw_scaled_text("*", i as f32 * 8.0, 310.0, 20.0, RED);
+            locate_print(1, i as usize + 1, "*", ink(RED));
+            locate_print(22, i as usize + 1, "*", ink(RED));
         }
     }
 
     for i in 1..21 {
         let idx = ((i + sparkle_offset) % 5) as usize;
         if sparkle_chars.chars().nth(idx) == Some('*') {
-            draw_scaled_text("*", 0.0, (i * 14 + 14) as f32, 20.0, RED);
-            draw_scaled_text("*", 632.0, (i * 14 + 14) as f32, 20.0, RED);
+            locate_print(i as usize + 1, 1, "*", ink(RED));
+            locate_print(i as usize + 1, font::COLUMNS, "*", ink(RED));
         }
     }
+}
 
-    draw_scaled_text("Q B a s i c    G O R I L L A S", 160.0, 56.0, 24.0, WHITE);
-    draw_scaled_text(
-        "Copyright (C) IBM Corporation 1991",
-        180.0,
-        84.0,
-        18.0,
-        GRAY,
-    );
-    draw_scaled_text(
-        "Your mission is to hit your opponent with the exploding",
-        100.0,
-        112.0,
-        18.0,
-        GRAY,
-    );
-    draw_scaled_text(
-        "banana by varying the angle and power of your throw, taking",
-        90.0,
-        126.0,
-        18.0,
-        GRAY,
+fn draw_input_screen(setup: &Setup, num_players: usize) {
+    let inputs = &setup.answers;
+    let team_play = setup.team_play;
+    clear_background(BLACK);
+
+    let cursor = if (get_time() * 2.0) as i32 % 2 == 0 {
+        "_"
+    } else {
+        " "
+    };
+    let answer = |answers: &[String], i: usize| answers.get(i).cloned().unwrap_or_default();
+
+    // (field, prompt, column, line, confirmed answer). Answers are typed
+    // straight after their prompts, as with LINE INPUT. Each player gets one
+    // line, with their name on the left and skill (and team) to the right;
+    // the rest are centred.
+    let centered = |prompt: &str| font::centered_column(prompt);
+    let prompt = "Number of gorillas 2-6 (Default = 2): ";
+    let mut cells = vec![(
+        Field::NumPlayers,
+        prompt.to_string(),
+        centered(prompt),
+        0,
+        inputs.players.clone(),
+    )];
+    if offers_teams(num_players) {
+        let prompt = "Play in two teams Y/N (Default = N): ";
+        cells.push((
+            Field::TeamPlay,
+            prompt.to_string(),
+            centered(prompt),
+            cells.len(),
+            inputs.team_play.clone(),
+        ));
+    }
+    let first_player_line = cells.len();
+    let (name_column, computer_column) = if team_play { (6, 37) } else { (8, 43) };
+    for i in 0..num_players {
+        let line = first_player_line + i;
+        cells.push((
+            Field::Name(i),
+            format!("Name of Player {}: ", i + 1),
+            name_column,
+            line,
+            answer(&inputs.names, i),
+        ));
+        cells.push((
+            Field::Computer(i),
+            "Computer 1-4: ".to_string(),
+            computer_column,
+            line,
+            answer(&inputs.computers, i),
+        ));
+        if team_play {
+            cells.push((
+                Field::Team(i),
+                "Team A/B: ".to_string(),
+                65,
+                line,
+                answer(&inputs.teams, i),
+            ));
+        }
+    }
+    let mut settings = vec![(
+        Field::NumGames,
+        "Play to how many total points (Default = 3): ",
+        &inputs.games,
+    )];
+    if team_play {
+        settings.push((
+            Field::FriendlyFire,
+            "Friendly fire Y/N (Default = Y): ",
+            &inputs.friendly_fire,
+        ));
+    }
+    settings.extend([
+        (
+            Field::Gravity,
+            "Gravity in Meters/Sec (Earth = 9.8): ",
+            &inputs.gravity,
+        ),
+        (Field::Seed, "Map seed (Default = random): ", &inputs.seed),
+        (
+            Field::FairMaps,
+            "Reroll lopsided maps Y/N (Default = N): ",
+            &inputs.fair_maps,
+        ),
+        (
+            Field::GameSpeed,
+            "Game speed (Default = 1.0): ",
+            &inputs.speed,
+        ),
+    ]);
+    let first_setting_line = first_player_line + num_players;
+    let lines = first_setting_line + settings.len();
+    for (i, (field, prompt, answer)) in settings.into_iter().enumerate() {
+        cells.push((
+            field,
+            prompt.to_string(),
+            centered(prompt),
+            first_setting_line + i,
+            answer.clone(),
+        ));
+    }
+
+    // Double spaced, like the original, while it fits on the screen.
+    let spacing = if lines * 2 <= font::ROWS - 3 { 2 } else { 1 };
+    for (field, prompt, column, line, answer) in cells {
+        let row = 3 + line * spacing;
+        locate_print(row, column, &prompt, ink(GRAY));
+        let display = if setup.field == field {
+            format!("{}{}", setup.buffer, cursor)
+        } else {
+            answer
+        };
+        locate_print(row, column + prompt.len(), &display, ink(WHITE));
+    }
+}
+
+fn team_name(team: usize) -> String {
+    format!("Team {}", team_letter(team))
+}
+
+const TEAM_COLORS: [Rgb; 2] = [EGA_COLORS[11], EGA_COLORS[12]];
+
+// "A AND B" like the original, wrapped over two lines for bigger matches.
+fn starring_lines(names: &[String]) -> Vec<String> {
+    let (last, rest) = names.split_last().expect("a match has players");
+    if names.len() <= 3 {
+        return vec![format!("{} AND {}", rest.join(", "), last)];
+    }
+    let (first, second) = rest.split_at(names.len() / 2);
+    let second = if second.is_empty() {
+        last.clone()
+    } else {
+        format!("{} AND {}", second.join(", "), last)
+    };
+    vec![format!("{},", first.join(", ")), second]
+}
+
+// Fresh seed for players who don't pick one.
+fn random_seed() -> u32 {
+    let now = miniquad::date::now();
+    (now.fract() * 1_000_000.0) as u32 ^ now as u32
+}
+
+fn draw_gorilla_intro_screen(offer_online: bool) {
+    clear_background(BLACK);
+    locate_print(16, 34, "--------------", ink(GRAY));
+    locate_print(18, 34, "V = View Intro", ink(GRAY));
+    locate_print(20, 34, "P = Play Game", ink(GRAY));
+    let mut row = 22;
+    if offer_online {
+        locate_print(row, 34, "O = Play Online", ink(GRAY));
+        row += 2;
+    }
+    locate_print(row, 35, "Your Choice?", ink(GRAY));
+}
+
+fn draw_room_code_prompt(input_buffer: &str) {
+    clear_background(BLACK);
+    let cursor = if (get_time() * 2.0) as i32 % 2 == 0 {
+        "_"
+    } else {
+        " "
+    };
+    center_text(10, "Both players enter the same room code.");
+    center_text(12, "Press Enter on an empty line for a new one.");
+    center_text(16, &format!("Room code: {}{}", input_buffer, cursor));
+}
+
+// Four letters, which is plenty for the handful of rooms open at once.
+fn new_room_code() -> String {
+    let mut rng = Rng::new(random_seed() as u64);
+    (0..4)
+        .map(|_| (b'A' + rng.fn_ran(26) as u8 - 1) as char)
+        .collect()
+}
+
+fn draw_shot_input(shot_state: &ShotState, faces_left: bool) {
+    let locate_col = if faces_left { 66 } else { 1 };
+    let cursor = if (get_time() * 2.0) as i32 % 2 == 0 {
+        "_"
+    } else {
+        " "
+    };
+
+    locate_print(2, locate_col, "Angle:", ink(WHITE));
+    let angle_display = if shot_state.phase == ShotPhase::InputAngle {
+        format!("{}{}", shot_state.angle_input, cursor)
+    } else {
+        format!("{}", shot_state.angle as i32)
+    };
+    locate_print(2, locate_col + 7, &angle_display, ink(WHITE));
+
+    if shot_state.phase != ShotPhase::InputAngle {
+        locate_print(3, locate_col, "Velocity:", ink(WHITE));
+        let velocity_display = if shot_state.phase == ShotPhase::InputVelocity {
+            format!("{}{}", shot_state.velocity_input, cursor)
+        } else {
+            format!("{}", shot_state.velocity as i32)
+        };
+        locate_print(3, locate_col + 10, &velocity_display, ink(WHITE));
+    }
+}
+
+fn draw_game_over(state: &GameState, sparkle_offset: i32) {
+    clear_background(BLACK);
+    draw_sparkles(sparkle_offset);
+
+    locate_print(8, 35, "GAME OVER!", ink(WHITE));
+    locate_print(10, 37, "Score:", ink(WHITE));
+    if state.is_team_match() {
+        for (team, wins) in state.total_wins.iter().enumerate() {
+            let members: Vec<&str> = state
+                .team_members(team)
+                .map(|player| state.player_names[player].as_str())
+                .collect();
+            let label = format!("{} ({})", team_name(team), members.join(", "));
+            locate_print(11 + team, 13, &label, ink(WHITE));
+            locate_print(11 + team, 63, &format!("{}", wins), ink(WHITE));
+        }
+    } else {
+        for (i, (name, wins)) in state.player_names.iter().zip(&state.total_wins).enumerate() {
+            locate_print(11 + i, 30, name, ink(WHITE));
+            locate_print(11 + i, 50, &format!("{}", wins), ink(WHITE));
+        }
+    }
+    locate_print(24, 33, "Press any key to continue", ink(GRAY));
+}
+
+// The original's layout: names in the top corners, score along the bottom.
+fn draw_duel_scoreboard(state: &GameState) {
+    let p2 = &state.player_names[1];
+    locate_print(1, 2, &state.player_names[0], ink(WHITE));
+    let p2_column = (font::COLUMNS - 1)
+        .saturating_sub(p2.chars().count())
+        .max(1);
+    locate_print(1, p2_column, p2, ink(WHITE));
+
+    let score_text = format!("{}>Score<{}", state.total_wins[0], state.total_wins[1]);
+    locate_print(
+        24,
+        font::centered_column(&score_text),
+        &score_text,
+        ink(WHITE),
     );
-    draw_scaled_text(
-        "into account wind speed, gravity, and the city skyline.",
-        100.0,
-        140.0,
-        18.0,
-        GRAY,
+}
+
+// A name over a gorilla's head.
+fn print_over_gorilla(state: &GameState, player: usize, label: &str, color: Color) {
+    print_at(
+        state.gorilla_x[player] - font::text_width(label) / 2.0,
+        state.gorilla_y[player] - 6.0 - font::GLYPH_HEIGHT as f32,
+        label,
+        color,
     );
-    draw_scaled_text(
-        "The wind speed is shown by a directional arrow at the bottom",
-        85.0,
-        154.0,
-        18.0,
-        GRAY,
+}
+
+// With more gorillas the corners run out, so each one wears its name and
+// score above its head. Whoever is up is highlighted.
+fn draw_scoreboard(state: &GameState, current_player: usize) {
+    for (i, (name, wins)) in state.player_names.iter().zip(&state.total_wins).enumerate() {
+        let color = if i == current_player {
+            ink(YELLOW)
+        } else {
+            ink(WHITE)
+        };
+        print_over_gorilla(state, i, &format!("{} {}", name, wins), color);
+    }
+}
+
+// Team colours over each gorilla, and the shared totals along the bottom.
+fn draw_team_scoreboard(state: &GameState, current_player: usize) {
+    for (i, name) in state.player_names.iter().enumerate() {
+        let color = if i == current_player {
+            ink(YELLOW)
+        } else {
+            ink(rgb(TEAM_COLORS[state.teams[i] % TEAM_COLORS.len()]))
+        };
+        print_over_gorilla(state, i, name, color);
+    }
+
+    let score_text = format!(
+        "{} {}>Score<{} {}",
+        team_name(0),
+        state.total_wins[0],
+        state.total_wins[1],
+        team_name(1)
     );
-    draw_scaled_text(
-        "of the playing field, its length relative to its strength.",
-        95.0,
-        168.0,
-        18.0,
-        GRAY,
+    locate_print(
+        24,
+        font::centered_column(&score_text),
+        &score_text,
+        ink(WHITE),
     );
-    draw_scaled_text("Press any key to continue", 220.0, 336.0, 18.0, GRAY);
 }
 
-fn draw_input_screen(
-    current_field: InputField,
-    input_buffer: &str,
-    player1_input: &str,
-    player2_input: &str,
-    games_input: &str,
-    gravity_input: &str,
-) {
-    clear_background(BLACK);
+fn draw_play_again() {
+    clear_background(BLACK);
+    let question = "Would you like to play again?";
+    locate_print(12, font::centered_column(question), question, ink(MAGENTA));
+    center_text(15, "(Y/N)");
+}
+
+fn victory_dance(sprites: &Sprites, state: &GameState, player: usize, frame: i32) {
+    let arms = if frame % 2 == 0 { LEFT_UP } else { RIGHT_UP };
+    sprites.gorilla(state.gorilla_x[player], state.gorilla_y[player], arms);
+}
+
+// A sound rendered once for each speaker, so that the two volumes place it
+// between them.
+struct Stereo {
+    left: Sound,
+    right: Sound,
+}
+
+impl Stereo {
+    async fn load(samples: &[f32]) -> Option<Self> {
+        Some(Stereo {
+            left: load_sound_from_bytes(&sfx::stereo_wav(samples, 1.0, 0.0))
+                .await
+                .ok()?,
+            right: load_sound_from_bytes(&sfx::stereo_wav(samples, 0.0, 1.0))
+                .await
+                .ok()?,
+        })
+    }
+
+    fn play(&self, gain: f32, x: f32, looped: bool) {
+        let (left, right) = mixer::pan(x);
+        for (sound, side) in [(&self.left, left), (&self.right, right)] {
+            let volume = gain * side;
+            play_sound(sound, PlaySoundParams { looped, volume });
+        }
+    }
+
+    fn set_volume(&self, gain: f32, x: f32) {
+        let (left, right) = mixer::pan(x);
+        set_sound_volume(&self.left, gain * left);
+        set_sound_volume(&self.right, gain * right);
+    }
+
+    fn stop(&self) {
+        stop_sound(&self.left);
+        stop_sound(&self.right);
+    }
+}
+
+// Pitches of the whoosh, from the slowest throws to the fastest, and sizes of
+// the synthesized explosion.
+const WHOOSH_BANDS: usize = 6;
+const EXPLOSION_SIZES: usize = 4;
+
+struct Sounds {
+    intro: Sound,
+    victory: Sound,
+    // The victory tune plays in the foreground, so each dance step waits
+    // for it.
+    victory_secs: f64,
+    // The original's PC speaker effects.
+    speaker_throw: Sound,
+    speaker_explosion: Sound,
+    speaker_gorilla_explosion: Sound,
+    whooshes: Vec<Stereo>,
+    explosions: Vec<Stereo>,
+    ouch: Sound,
+    click: Sound,
+}
 
-    let cursor = if (get_time() * 2.0) as i32 % 2 == 0 {
-        "_"
-    } else {
-        " "
-    };
+async fn load_tune(play: &str) -> Option<(Sound, Tune)> {
+    let tune = Tune::parse(play).expect("built-in PLAY strings are valid");
+    let sound = load_sound_from_bytes(&tune.to_wav(SAMPLE_RATE))
+        .await
+        .ok()?;
+    Some((sound, tune))
+}
 
-    draw_scaled_text(
-        "Name of Player 1 (Default = 'Player 1'): ",
-        100.0,
-        112.0,
-        18.0,
-        GRAY,
-    );
-    let p1_display = if current_field == InputField::Player1Name {
-        format!("{}{}", input_buffer, cursor)
-    } else {
-        player1_input.to_string()
-    };
-    draw_scaled_text(&p1_display, 430.0, 112.0, 18.0, WHITE);
-
-    draw_scaled_text(
-        "Name of Player 2 (Default = 'Player 2'): ",
-        100.0,
-        140.0,
-        18.0,
-        GRAY,
-    );
-    let p2_display = if current_field == InputField::Player2Name {
-        format!("{}{}", input_buffer, cursor)
-    } else {
-        player2_input.to_string()
-    };
-    draw_scaled_text(&p2_display, 430.0, 140.0, 18.0, WHITE);
+async fn load_effect(samples: &[f32]) -> Option<Sound> {
+    load_sound_from_bytes(&wav(samples, 1, SAMPLE_RATE))
+        .await
+        .ok()
+}
 
-    draw_scaled_text(
-        "Play to how many total points (Default = 3): ",
-        80.0,
-        168.0,
-        18.0,
-        GRAY,
-    );
-    let games_display = if current_field == InputField::NumGames {
-        format!("{}{}", input_buffer, cursor)
-    } else {
-        games_input.to_string()
-    };
-    draw_scaled_text(&games_display, 450.0, 168.0, 18.0, WHITE);
-
-    draw_scaled_text(
-        "Gravity in Meters/Sec (Earth = 9.8): ",
-        110.0,
-        196.0,
-        18.0,
-        GRAY,
-    );
-    let gravity_display = if current_field == InputField::Gravity {
-        format!("{}{}", input_buffer, cursor)
-    } else {
-        gravity_input.to_string()
-    };
-    draw_scaled_text(&gravity_display, 430.0, 196.0, 18.0, WHITE);
+// Renders every sound the game makes. Without working audio it is silent.
+async fn load_sounds() -> Option<Sounds> {
+    let (victory, victory_tune) = load_tune(VICTORY_TUNE).await?;
+    let mut whooshes = Vec::new();
+    for band in 0..WHOOSH_BANDS {
+        let velocity = sfx::WHOOSH_TOP_VELOCITY * (band as f32 + 0.5) / WHOOSH_BANDS as f32;
+        whooshes.push(Stereo::load(&sfx::whoosh(velocity)).await?);
+    }
+    let mut explosions = Vec::new();
+    for size in 0..EXPLOSION_SIZES {
+        let size = size as f32 / (EXPLOSION_SIZES - 1) as f32;
+        explosions.push(Stereo::load(&sfx::explosion(size)).await?);
+    }
+    Some(Sounds {
+        intro: load_tune(INTRO_TUNE).await?.0,
+        victory,
+        victory_secs: if victory_tune.background {
+            0.0
+        } else {
+            victory_tune.duration() as f64
+        },
+        speaker_throw: load_tune(THROW_SOUND).await?.0,
+        speaker_explosion: load_tune(EXPLOSION_SOUND).await?.0,
+        speaker_gorilla_explosion: load_tune(GORILLA_EXPLOSION_SOUND).await?.0,
+        whooshes,
+        explosions,
+        ouch: load_effect(&sfx::ouch()).await?,
+        click: load_effect(&sfx::click()).await?,
+    })
 }
 
-fn draw_gorilla_intro_screen() {
-    clear_background(BLACK);
-    draw_scaled_text("--------------", 220.0, 224.0, 18.0, GRAY);
-    draw_scaled_text("V = View Intro", 220.0, 252.0, 18.0, GRAY);
-    draw_scaled_text("P = Play Game", 220.0, 280.0, 18.0, GRAY);
-    draw_scaled_text("Your Choice?", 230.0, 308.0, 18.0, GRAY);
+// How big a bang a finished shot makes: bananas that hit harder blow up
+// louder and longer, and hitting a gorilla is the biggest of all.
+fn impact_size(shot_state: &ShotState) -> f32 {
+    if shot_state.player_hit.is_some() {
+        return 1.0;
+    }
+    let step = ((shot_state.x - shot_state.prev_x).powi(2)
+        + (shot_state.y - shot_state.prev_y).powi(2))
+    .sqrt();
+    // Positions are 0.1 apart in flight time.
+    (step * 10.0 / sfx::WHOOSH_TOP_VELOCITY).clamp(0.0, 0.8)
 }
 
-fn draw_shot_input(shot_state: &ShotState, player_num: usize) {
-    let locate_col = if player_num == 0 { 10.0 } else { 500.0 };
-    let cursor = if (get_time() * 2.0) as i32 % 2 == 0 {
-        "_"
-    } else {
-        " "
-    };
+// Plays the game's sounds at the volumes in `settings`.
+struct Audio {
+    settings: MixerSettings,
+    sounds: Option<Sounds>,
+    // The whoosh looping while a banana flies.
+    whoosh: Option<usize>,
+}
 
-    draw_scaled_text("Angle:", locate_col, 28.0, 18.0, WHITE);
-    let angle_display = if shot_state.phase == ShotPhase::InputAngle {
-        format!("{}{}", shot_state.angle_input, cursor)
-    } else {
-        format!("{}", shot_state.angle as i32)
-    };
-    draw_scaled_text(&angle_display, locate_col + 60.0, 28.0, 18.0, WHITE);
+impl Audio {
+    async fn load() -> Self {
+        let sounds = load_sounds().await;
+        if sounds.is_none() {
+            eprintln!("No audio; playing without sound");
+        }
+        Audio {
+            settings: MixerSettings::load(),
+            sounds,
+            whoosh: None,
+        }
+    }
 
-    if shot_state.phase != ShotPhase::InputAngle {
-        draw_scaled_text("Velocity:", locate_col, 42.0, 18.0, WHITE);
-        let velocity_display = if shot_state.phase == ShotPhase::InputVelocity {
-            format!("{}{}", shot_state.velocity_input, cursor)
-        } else {
-            format!("{}", shot_state.velocity as i32)
-        };
-        draw_scaled_text(&velocity_display, locate_col + 80.0, 42.0, 18.0, WHITE);
+    fn play(&self, channel: Channel, pick: impl Fn(&Sounds) -> &Sound) {
+        if let Some(sounds) = &self.sounds {
+            let volume = self.settings.gain(channel);
+            play_sound(
+                pick(sounds),
+                PlaySoundParams {
+                    looped: false,
+                    volume,
+                },
+            );
+        }
     }
-}
 
-fn plot_shot(
-    state: &mut GameState,
-    shot_state: &mut ShotState,
-    player_num: usize,
-    start_x: f32,
-    start_y: f32,
-) -> bool {
-    let angle_rad = shot_state.angle / 180.0 * PI;
-    let init_x_vel = angle_rad.cos() * shot_state.velocity;
-    let init_y_vel = angle_rad.sin() * shot_state.velocity;
-
-    let adjust = 4.0;
-    let start_x_pos = if player_num == 1 {
-        start_x + 25.0
-    } else {
-        start_x
-    };
-    let start_y_pos = start_y - adjust - 3.0;
-
-    shot_state.x = start_x_pos
-        + (init_x_vel * shot_state.t)
-        + (0.5 * (state.wind as f32 / 5.0) * shot_state.t * shot_state.t);
-    shot_state.y = start_y_pos
-        + (-(init_y_vel * shot_state.t) + (0.5 * state.gravity * shot_state.t * shot_state.t))
-            * (VIRTUAL_HEIGHT / 350.0);
-
-    if shot_state.x >= VIRTUAL_WIDTH - 10.0
-        || shot_state.x <= 3.0
-        || shot_state.y >= VIRTUAL_HEIGHT - 3.0
-    {
-        shot_state.on_screen = false;
-        return true;
-    }
-
-    if shot_state.y > 0.0 && shot_state.on_screen {
-        for i in 0..2 {
-            let gx = state.gorilla_x[i];
-            let gy = state.gorilla_y[i];
-            if shot_state.x >= gx - 5.0
-                && shot_state.x <= gx + 25.0
-                && shot_state.y >= gy - 5.0
-                && shot_state.y <= gy + 30.0
-            {
-                shot_state.impact = true;
-                shot_state.player_hit = Some(i);
-                return true;
-            }
+    fn victory_secs(&self) -> f64 {
+        self.sounds.as_ref().map_or(0.0, |s| s.victory_secs)
+    }
+
+    fn throw(&mut self, velocity: f32, x: f32) {
+        let Some(sounds) = &self.sounds else {
+            return;
+        };
+        if self.settings.pc_speaker {
+            self.play(Channel::Effects, |s| &s.speaker_throw);
+            return;
         }
+        let band = (velocity / sfx::WHOOSH_TOP_VELOCITY * WHOOSH_BANDS as f32) as usize;
+        let band = band.min(WHOOSH_BANDS - 1);
+        sounds.whooshes[band].play(self.settings.gain(Channel::Effects), x, true);
+        self.whoosh = Some(band);
+    }
 
-        for i in 1..=state.last_building {
-            let bx = state.bcoor[i].x_coor as f32;
-            let by = state.bcoor[i].y_coor as f32;
-            let next_bx = if i + 1 < state.bcoor.len() {
-                state.bcoor[i + 1].x_coor as f32
+    // Follows the banana across the screen with the whoosh.
+    fn banana_moved(&self, x: f32, paused: bool) {
+        if let (Some(sounds), Some(band)) = (&self.sounds, self.whoosh) {
+            let gain = if paused {
+                0.0
             } else {
-                VIRTUAL_WIDTH
+                self.settings.gain(Channel::Effects)
             };
-            if shot_state.x >= bx && shot_state.x <= next_bx && shot_state.y >= by {
-                shot_state.impact = true;
-                return true;
-            }
+            sounds.whooshes[band].set_volume(gain, x);
         }
+    }
 
-        let sun_x = VIRTUAL_WIDTH / 2.0;
-        if (shot_state.x - sun_x).abs() < 20.0 && shot_state.y < SUN_HT {
-            state.sun_hit = true;
+    fn sun_hit(&self) {
+        if !self.settings.pc_speaker {
+            self.play(Channel::Effects, |s| &s.ouch);
         }
+    }
 
-        if !shot_state.impact {
-            let rot = ((shot_state.t * 10.0) as i32) % 4;
-            draw_banana(shot_state.x, shot_state.y, rot);
+    fn landed(&mut self, shot_state: &ShotState) {
+        let Some(sounds) = &self.sounds else {
+            return;
+        };
+        if let Some(band) = self.whoosh.take() {
+            sounds.whooshes[band].stop();
+        }
+        let Some((x, _)) = explosion_point(shot_state) else {
+            return;
+        };
+        if self.settings.pc_speaker {
+            if shot_state.player_hit.is_some() {
+                self.play(Channel::Effects, |s| &s.speaker_gorilla_explosion);
+            } else {
+                self.play(Channel::Effects, |s| &s.speaker_explosion);
+            }
+        } else {
+            let size = impact_size(shot_state);
+            let index = (size * (EXPLOSION_SIZES - 1) as f32).round() as usize;
+            sounds.explosions[index].play(self.settings.gain(Channel::Effects), x, false);
         }
     }
 
-    shot_state.t += 0.1;
-    false
-}
-
-fn draw_game_over(state: &GameState, sparkle_offset: i32) {
-    clear_background(BLACK);
-
-    let sparkle_chars = "*    ";
-    for i in 0..80 {
-        let idx = ((i + sparkle_offset) % 5) as usize;
-        if sparkle_chars.chars().nth(idx) == Some('*') {
-            draw_scaled_text("*", i as f32 * 8.0, 14.0, 20.0, RED);
-            draw_scaled_text("*", i as f32 * 8.0, 310.0, 20.0, RED);
-        }
+    fn click(&self) {
+        self.play(Channel::Ui, |s| &s.click);
     }
 
-    for i in 1..21 {
-        let idx = ((i + sparkle_offset) % 5) as usize;
-        if sparkle_chars.chars().nth(idx) == Some('*') {
-            draw_scaled_text("*", 0.0, (i * 14 + 14) as f32, 20.0, RED);
-            draw_scaled_text("*", 632.0, (i * 14 + 14) as f32, 20.0, RED);
+    // Brings sounds that are already playing in line with changed settings,
+    // and saves them.
+    fn settings_changed(&self) {
+        if let Some(sounds) = &self.sounds {
+            let music = self.settings.gain(Channel::Music);
+            let effects = self.settings.gain(Channel::Effects);
+            for sound in [&sounds.intro, &sounds.victory] {
+                set_sound_volume(sound, music);
+            }
+            for sound in [
+                &sounds.speaker_throw,
+                &sounds.speaker_explosion,
+                &sounds.speaker_gorilla_explosion,
+                &sounds.ouch,
+            ] {
+                set_sound_volume(sound, effects);
+            }
+            for explosion in &sounds.explosions {
+                explosion.set_volume(effects, VIRTUAL_WIDTH / 2.0);
+            }
+            set_sound_volume(&sounds.click, self.settings.gain(Channel::Ui));
+        }
+        if let Err(err) = self.settings.save() {
+            eprintln!("Could not save sound settings: {}", err);
         }
     }
-
-    draw_scaled_text("GAME OVER!", 270.0, 112.0, 24.0, WHITE);
-    draw_scaled_text("Score:", 290.0, 140.0, 20.0, WHITE);
-    draw_scaled_text(&state.player1_name, 200.0, 168.0, 18.0, WHITE);
-    draw_scaled_text(
-        &format!("{}", state.total_wins[0]),
-        400.0,
-        168.0,
-        18.0,
-        WHITE,
-    );
-    draw_scaled_text(&state.player2_name, 200.0, 196.0, 18.0, WHITE);
-    draw_scaled_text(
-        &format!("{}", state.total_wins[1]),
-        400.0,
-        196.0,
-        18.0,
-        WHITE,
-    );
-    draw_scaled_text("Press any key to continue", 220.0, 336.0, 18.0, GRAY);
 }
 
-fn draw_play_again() {
+// Rows of the sound settings screen after the channel volumes.
+const SOUND_MUTE_ROW: usize = Channel::ALL.len();
+const SOUND_STYLE_ROW: usize = SOUND_MUTE_ROW + 1;
+
+fn draw_sound_settings(settings: &MixerSettings, selected: usize) {
     clear_background(BLACK);
-    draw_scaled_text("Would you like to play again?", 180.0, 168.0, 24.0, MAGENTA);
-    draw_scaled_text("(Y/N)", 290.0, 210.0, 20.0, WHITE);
+    center_text(4, "S O U N D");
+    let mut lines: Vec<String> = Channel::ALL
+        .iter()
+        .map(|&channel| {
+            let volume = settings.volume(channel);
+            let filled = (volume * 10.0).round() as usize;
+            format!(
+                "{:<8} [{}{}] {:>3}%",
+                channel.name(),
+                "#".repeat(filled),
+                "-".repeat(10 - filled),
+                (volume * 100.0).round()
+            )
+        })
+        .collect();
+    lines.push(format!(
+        "Mute     {}",
+        if settings.muted { "On" } else { "Off" }
+    ));
+    lines.push(format!(
+        "Effects  {}",
+        if settings.pc_speaker {
+            "PC speaker"
+        } else {
+            "Synthesized"
+        }
+    ));
+    // One column for every row, centred on the longest.
+    let widest = lines.iter().map(|line| line.len() + 2).max().unwrap_or(0);
+    let column = font::centered_column(&" ".repeat(widest));
+    for (row, line) in lines.iter().enumerate() {
+        let marker = if row == selected { ">" } else { " " };
+        locate_print(
+            8 + row * 2,
+            column,
+            &format!("{} {}", marker, line),
+            ink(WHITE),
+        );
+    }
+    center_text(
+        21,
+        "Up/Down to choose, Left/Right to change, M to mute, F2 to close",
+    );
 }
 
-fn victory_dance(state: &GameState, player: usize, frame: i32) {
-    let arms = if frame % 2 == 0 { LEFT_UP } else { RIGHT_UP };
-    draw_gorilla(state.gorilla_x[player], state.gorilla_y[player], arms);
+// Handles a frame of the sound settings screen. Returns whether it stays
+// open.
+fn sound_settings_input(input: &dyn InputSource, audio: &mut Audio, selected: &mut usize) -> bool {
+    if input.is_key_pressed(Key::F2) || input.is_key_pressed(Key::Escape) {
+        return false;
+    }
+    let settings = &mut audio.settings;
+    let before = settings.clone();
+    if input.is_key_pressed(Key::Up) {
+        *selected = selected.saturating_sub(1);
+    }
+    if input.is_key_pressed(Key::Down) {
+        *selected = (*selected + 1).min(SOUND_STYLE_ROW);
+    }
+    let step = if input.is_key_pressed(Key::Right) {
+        0.1
+    } else if input.is_key_pressed(Key::Left) {
+        -0.1
+    } else {
+        0.0
+    };
+    let toggle =
+        step != 0.0 || input.is_key_pressed(Key::Enter) || input.is_key_pressed(Key::Space);
+    match *selected {
+        SOUND_MUTE_ROW if toggle => settings.muted = !settings.muted,
+        SOUND_STYLE_ROW if toggle => settings.pc_speaker = !settings.pc_speaker,
+        row if row < SOUND_MUTE_ROW => {
+            let channel = Channel::ALL[row];
+            let volume = ((settings.volume(channel) + step) * 10.0).round() / 10.0;
+            settings.set_volume(channel, volume);
+        }
+        _ => {}
+    }
+    if input.is_key_pressed(Key::Char('M')) {
+        settings.muted = !settings.muted;
+    }
+    if audio.settings != before {
+        audio.settings_changed();
+        audio.click();
+    }
+    true
 }
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut state = GameState::new();
+    DISPLAY_MODE.get_or_init(display_mode_from_args);
+    let mut state = new_game_state();
     let mut phase = GamePhase::Intro;
     let mut sparkle_offset = 0;
     let mut last_sparkle_time = 0.0;
 
-    let mut current_input_field = InputField::Player1Name;
-    let mut input_buffer = String::new();
-    let mut player1_input = String::new();
-    let mut player2_input = String::new();
-    let mut games_input = String::new();
-    let mut gravity_input = String::new();
+    let mut input = input_from_args();
+    let mut room_code = String::new();
 
     let mut current_player = 0;
     let mut shot_state = ShotState::new();
+    let mut sim_clock = SimClock::default();
     let mut current_game = 0;
     let mut victory_frame = 0;
     let mut victory_timer = 0.0;
     let mut showing_victory = false;
-    let mut winning_player = 0;
+    let mut audio = Audio::load().await;
+    let mut screen = Screen::new(ScaleMode::from_args_or_saved());
+    let sprites = Sprites::new(&screen);
+    let mut skyline = Skyline::new();
+    screen.begin();
+    let mut sound_settings: Option<usize> = None;
+    // Set while a banana flies. Fast-forwarded replay throws fly without a
+    // sound.
+    let mut in_flight = false;
+    let mut shot_muted = false;
+    let mut sun_yelled = false;
+    let mut winning_team = 0;
+    let mut hit_player = None;
     let mut showing_view_intro = false;
     let mut intro_dance_frame = 0;
     let mut intro_dance_timer = 0.0;
     let mut fullscreen = true;
+    let mut show_fairness = false;
+
+    let mut setup = Setup::new(&state, random_seed());
+    let mut ai_players: Vec<Option<AiPlayer>> = Vec::new();
+    let mut ai_typing: Option<AiTyping> = None;
+    // A computer player's throw, worked out over a few frames before it is
+    // typed.
+    let mut ai_thinking: Option<Thinking> = None;
+    let bot_commands = bot_commands_from_args();
+    let mut bots: Vec<Option<ExternalBot>> = Vec::new();
+    let mut recording: Option<Recording> = None;
+    let mut listener = None;
+    let mut room = None;
+    let mut peer = None;
+    let mut net_error = String::new();
+    match NetMode::from_args() {
+        Some(NetMode::Host(port)) => match Listener::bind(port) {
+            Ok(bound) => listener = Some(bound),
+            Err(err) => eprintln!("Could not listen on port {}: {}", port, err),
+        },
+        Some(NetMode::Join(addr)) => {
+            match Peer::join(&addr) {
+                Ok(host) => peer = Some(host),
+                Err(err) => {
+                    net_error = format!("Could not join {}: {}", addr, err);
+                    eprintln!("{}", net_error);
+                }
+            }
+            phase = if peer.is_some() {
+                GamePhase::MatchStart
+            } else {
+                GamePhase::NetError
+            };
+        }
+        None => {}
+    }
+    let mut playback = Playback::from_args();
+    let mut turn_ledger = None;
+    let mut turn_saved: Option<String> = None;
+    let mut turn_error = String::new();
+    match TurnArgs::from_args() {
+        Some(TurnArgs::New) => match Ledger::open(DEFAULT_LEDGER) {
+            Ok(ledger) => turn_ledger = Some(ledger),
+            Err(err) => eprintln!("Could not read {}: {}", DEFAULT_LEDGER, err),
+        },
+        Some(TurnArgs::Continue(path)) => match continue_turns(&path) {
+            Ok((turn_playback, turn_recording)) => {
+                playback = Some(turn_playback);
+                recording = Some(turn_recording);
+            }
+            Err(err) => {
+                eprintln!("Cannot play {}: {}", path, err);
+                turn_error = err;
+                phase = GamePhase::BadTurnFile;
+            }
+        },
+        None => {}
+    }
+    if let Some(playback) = &playback {
+        playback.replay.apply_settings(&mut state);
+        state.new_round();
+        phase = GamePhase::Playing;
+    }
+
+    if phase == GamePhase::Intro {
+        audio.play(Channel::Music, |s| &s.intro);
+    }
 
     loop {
         let current_time = get_time();
+        input.next_frame(current_time);
         if current_time - last_sparkle_time > 0.1 {
             sparkle_offset = (sparkle_offset + 1) % 5;
             last_sparkle_time = current_time;
         }
 
+        // F2 opens the sound settings over whatever is going on, which waits
+        // until they are closed.
+        if sound_settings.is_none() && input.is_key_pressed(Key::F2) {
+            sound_settings = Some(0);
+        } else if let Some(selected) = &mut sound_settings {
+            if sound_settings_input(&*input, &mut audio, selected) {
+                draw_sound_settings(&audio.settings, *selected);
+                screen.show().await;
+                continue;
+            }
+            sound_settings = None;
+            screen.show().await;
+            continue;
+        }
+
         // ESC to quit
-        if is_key_pressed(KeyCode::Escape) {
+        if input.is_key_pressed(Key::Escape) {
             break;
         }
 
+        if input.is_key_pressed(Key::F4) {
+            audio.settings.muted = !audio.settings.muted;
+            audio.settings_changed();
+        }
+        if input.is_key_pressed(Key::F6) {
+            screen.cycle_mode();
+        }
+        if input.last_key_pressed().is_some() {
+            audio.click();
+        }
+
         // Cmd+Enter (Mac) or Ctrl+Enter to toggle fullscreen
-        if is_key_pressed(KeyCode::Enter)
-            && (is_key_down(KeyCode::LeftSuper)
-                || is_key_down(KeyCode::RightSuper)
-                || is_key_down(KeyCode::LeftControl)
-                || is_key_down(KeyCode::RightControl))
-        {
+        if input.is_key_pressed(Key::Enter) && input.is_key_down(Key::Control) {
             fullscreen = !fullscreen;
             set_fullscreen(fullscreen);
         }
 
+        if input.is_key_pressed(Key::F3) {
+            show_fairness = !show_fairness;
+        }
+
         match phase {
             GamePhase::Intro => {
                 draw_intro_screen(sparkle_offset);
 
-                if get_last_key_pressed().is_some() || is_mouse_button_pressed(MouseButton::Left) {
+                if input.any_pressed() {
                     phase = GamePhase::GetInputs;
-                    input_buffer.clear();
                 }
             }
 
             GamePhase::GetInputs => {
-                draw_input_screen(
-                    current_input_field,
-                    &input_buffer,
-                    &player1_input,
-                    &player2_input,
-                    &games_input,
-                    &gravity_input,
-                );
-
-                if let Some(key) = get_last_key_pressed() {
-                    match key {
-                        KeyCode::Enter => match current_input_field {
-                            InputField::Player1Name => {
-                                player1_input = if input_buffer.is_empty() {
-                                    "Player 1".to_string()
-                                } else {
-                                    input_buffer.chars().take(10).collect()
-                                };
-                                input_buffer.clear();
-                                current_input_field = InputField::Player2Name;
-                            }
-                            InputField::Player2Name => {
-                                player2_input = if input_buffer.is_empty() {
-                                    "Player 2".to_string()
-                                } else {
-                                    input_buffer.chars().take(10).collect()
-                                };
-                                input_buffer.clear();
-                                current_input_field = InputField::NumGames;
-                            }
-                            InputField::NumGames => {
-                                games_input = input_buffer.clone();
-                                let num: i32 = input_buffer.parse().unwrap_or(3);
-                                state.num_games = if num > 0 { num } else { 3 };
-                                input_buffer.clear();
-                                current_input_field = InputField::Gravity;
-                            }
-                            InputField::Gravity => {
-                                gravity_input = input_buffer.clone();
-                                let grav: f32 = input_buffer.parse().unwrap_or(9.8);
-                                state.gravity = if grav > 0.0 { grav } else { 9.8 };
-                                state.player1_name = player1_input.clone();
-                                state.player2_name = player2_input.clone();
-                                phase = GamePhase::GorillaIntro;
-                                current_input_field = InputField::ViewOrPlay;
-                            }
-                            _ => {}
-                        },
-                        KeyCode::Backspace => {
-                            input_buffer.pop();
-                        }
-                        _ => {}
-                    }
-                }
+                draw_input_screen(&setup, state.num_players());
 
-                while let Some(c) = get_char_pressed() {
-                    if (c.is_alphanumeric() || c == '.' || c == ' ') && input_buffer.len() < 20 {
-                        input_buffer.push(c);
-                    }
+                if setup.update(&mut *input, &mut state) {
+                    sim_clock.speed = setup.speed;
+                    phase = GamePhase::GorillaIntro;
                 }
             }
 
             GamePhase::GorillaIntro => {
                 if !showing_view_intro {
-                    draw_gorilla_intro_screen();
+                    // The desktop build plays online with --host and --join.
+                    let offer_online = cfg!(target_arch = "wasm32") && peer.is_none();
+                    draw_gorilla_intro_screen(offer_online);
 
-                    if let Some(key) = get_last_key_pressed() {
+                    if let Some(key) = input.last_key_pressed() {
                         match key {
-                            KeyCode::V => {
+                            Key::Char('V') => {
                                 showing_view_intro = true;
                                 intro_dance_timer = get_time();
                             }
-                            KeyCode::P => {
-                                current_game = 0;
-                                state.total_wins = [0, 0];
-                                phase = GamePhase::Playing;
-                                current_player = 0;
-                                shot_state = ShotState::new();
-                                state.reset_cityscape();
-                                generate_cityscape(&mut state);
-                                place_gorillas(&mut state);
+                            Key::Char('P') => {
+                                phase = GamePhase::MatchStart;
+                            }
+                            Key::Char('O') if offer_online => {
+                                phase = GamePhase::RoomCode;
+                                room_code.clear();
                             }
                             _ => {}
                         }
                     }
                 } else {
-                    clear_background(BACK_COLOR);
-                    center_text(2.0, "Q B A S I C   G O R I L L A S");
-                    center_text(5.0, "STARRING:");
-                    let p_str = format!("{} AND {}", state.player1_name, state.player2_name);
-                    center_text(7.0, &p_str);
+                    clear_background(rgb(scheme().sky));
+                    center_text(2, "Q B A S I C   G O R I L L A S");
+                    center_text(5, "STARRING:");
+                    for (i, line) in starring_lines(&state.player_names).iter().enumerate() {
+                        center_text(7 + i, line);
+                    }
 
                     let x = 278.0;
                     let y = 175.0;
@@ -1062,8 +1901,8 @@ async fn main() {
                     } else {
                         RIGHT_UP
                     };
-                    draw_gorilla(x - 13.0, y, arm_state);
-                    draw_gorilla(
+                    sprites.gorilla(x - 13.0, y, arm_state);
+                    sprites.gorilla(
                         x + 47.0,
                         y,
                         if arm_state == LEFT_UP {
@@ -1081,72 +1920,177 @@ async fn main() {
                     if intro_dance_frame > 16 {
                         showing_view_intro = false;
                         intro_dance_frame = 0;
+                        phase = GamePhase::MatchStart;
+                    }
+                }
+            }
+
+            GamePhase::RoomCode => {
+                draw_room_code_prompt(&room_code);
+
+                match input.last_key_pressed() {
+                    Some(Key::Enter) => {
+                        let code = if room_code.is_empty() {
+                            new_room_code()
+                        } else {
+                            room_code.clone()
+                        };
+                        match Room::open("", &code) {
+                            Ok(opened) => {
+                                room = Some(opened);
+                                phase = GamePhase::MatchStart;
+                            }
+                            Err(err) => {
+                                net_error = err.to_string();
+                                phase = GamePhase::NetError;
+                            }
+                        }
+                    }
+                    Some(Key::Backspace) => {
+                        room_code.pop();
+                    }
+                    _ => {}
+                }
+
+                while let Some(c) = input.char_pressed() {
+                    if c.is_ascii_alphanumeric() && room_code.len() < 8 {
+                        room_code.push(c.to_ascii_uppercase());
+                    }
+                }
+            }
+
+            GamePhase::MatchStart => {
+                clear_background(BLACK);
+                match net_lobby(listener.as_ref(), &mut room, &mut peer, &mut state) {
+                    Ok(true) => {
+                        recording = match turn_ledger.take() {
+                            Some(ledger) => Some(Recording::by_file(Replay::new(&state), ledger)),
+                            None => Recording::start(&state),
+                        };
+                        ai_players = setup.computer_players(state.seed);
+                        bots = spawn_bots(&bot_commands, state.num_players());
+                        if recording.as_ref().is_some_and(|r| r.ledger.is_some()) {
+                            // Computer players can't be handed on in a file.
+                            ai_players.clear();
+                            bots.clear();
+                        }
+                        if let Some(peer) = &peer {
+                            // The other machine plays its own gorillas.
+                            for i in 0..state.num_players() {
+                                if !peer.owns(i) {
+                                    if let Some(ai) = ai_players.get_mut(i) {
+                                        *ai = None;
+                                    }
+                                    if let Some(bot) = bots.get_mut(i) {
+                                        *bot = None;
+                                    }
+                                }
+                            }
+                        }
                         current_game = 0;
-                        state.total_wins = [0, 0];
+                        state.total_wins.fill(0);
                         phase = GamePhase::Playing;
                         current_player = 0;
                         shot_state = ShotState::new();
-                        state.reset_cityscape();
-                        generate_cityscape(&mut state);
-                        place_gorillas(&mut state);
+                        state.new_round();
+                    }
+                    Ok(false) => {}
+                    Err(err) => {
+                        room = None;
+                        net_error = hang_up(&mut peer, err);
+                        phase = GamePhase::NetError;
                     }
                 }
             }
 
             GamePhase::Playing => {
-                clear_background(BACK_COLOR);
+                clear_background(rgb(scheme().sky));
+
+                if let Some(err) = peer.as_mut().and_then(|peer| peer.poll().err()) {
+                    net_error = hang_up(&mut peer, err);
+                    phase = GamePhase::NetError;
+                }
 
                 if !state.cityscape_generated {
-                    generate_cityscape(&mut state);
-                    place_gorillas(&mut state);
+                    state.new_round();
                 }
 
-                draw_cityscape(&state);
+                skyline.draw(&state, &screen);
 
                 if state.sun_hit {
-                    draw_sun(SUN_SHOCK);
+                    paint_sun(&mut Gpu, scheme(), SUN_SHOCK);
                 } else {
-                    draw_sun(SUN_HAPPY);
+                    paint_sun(&mut Gpu, scheme(), SUN_HAPPY);
                 }
 
-                for i in 0..2 {
-                    if !showing_victory || i != 1 - winning_player {
-                        if showing_victory && i == winning_player {
-                            victory_dance(&state, winning_player, victory_frame);
-                        } else {
-                            draw_gorilla(state.gorilla_x[i], state.gorilla_y[i], ARMS_DOWN);
-                        }
+                for i in 0..state.num_players() {
+                    if showing_victory && Some(i) == hit_player {
+                        continue;
+                    }
+                    if showing_victory && state.teams[i] == winning_team {
+                        victory_dance(&sprites, &state, i, victory_frame);
+                    } else {
+                        sprites.gorilla(state.gorilla_x[i], state.gorilla_y[i], ARMS_DOWN);
                     }
                 }
 
-                draw_scaled_text(&state.player1_name, 10.0, 14.0, 18.0, WHITE);
-                let (scale, _, _) = get_scale();
-                let p2_width = measure_text(&state.player2_name, None, (18.0 * scale) as u16, 1.0)
-                    .width
-                    / scale;
-                draw_scaled_text(
-                    &state.player2_name,
-                    VIRTUAL_WIDTH - p2_width - 10.0,
-                    14.0,
-                    18.0,
-                    WHITE,
-                );
+                if state.is_team_match() {
+                    draw_team_scoreboard(&state, current_player);
+                } else if state.num_players() == 2 {
+                    draw_duel_scoreboard(&state);
+                } else {
+                    draw_scoreboard(&state, current_player);
+                }
+                locate_print(25, 2, &format!("Seed {}", state.seed), ink(LIGHTGRAY));
 
-                let score_text = format!("{}>Score<{}", state.total_wins[0], state.total_wins[1]);
-                let score_width =
-                    measure_text(&score_text, None, (18.0 * scale) as u16, 1.0).width / scale;
-                draw_scaled_text(
-                    &score_text,
-                    (VIRTUAL_WIDTH - score_width) / 2.0,
-                    330.0,
-                    18.0,
-                    WHITE,
-                );
+                if show_fairness {
+                    if state.fairness.is_none() {
+                        state.fairness = Some(fairness::evaluate(&state));
+                    }
+                    let fairness = state.fairness.as_ref().unwrap();
+                    let chances: Vec<String> = fairness
+                        .chances
+                        .iter()
+                        .map(|c| format!("{:.1}", c))
+                        .collect();
+                    locate_print(
+                        24,
+                        2,
+                        &format!("Fairness {:.2}  ({})", fairness.score, chances.join(" vs ")),
+                        ink(LIGHTGRAY),
+                    );
+                }
+
+                if let Some(playback) = &mut playback {
+                    if input.is_key_pressed(Key::Space) {
+                        playback.paused = !playback.paused;
+                    }
+                    if input.is_key_pressed(Key::Char('N')) {
+                        playback.step = true;
+                    }
+                    if input.is_key_pressed(Key::Char('=')) || input.is_key_pressed(Key::Char('+'))
+                    {
+                        sim_clock.speed = (sim_clock.speed * 2.0).min(8.0);
+                    }
+                    if input.is_key_pressed(Key::Char('-')) {
+                        sim_clock.speed = (sim_clock.speed / 2.0).max(0.25);
+                    }
+                    let status = if playback.paused { "PAUSED" } else { "REPLAY" };
+                    let help = format!(
+                        "{} x{}  Space: pause  N: next shot  +/-: speed",
+                        status, sim_clock.speed
+                    );
+                    let column = font::COLUMNS.saturating_sub(help.len()).max(1);
+                    locate_print(25, column, &help, ink(LIGHTGRAY));
+                }
 
                 if showing_victory {
-                    if get_time() - victory_timer > 0.2 {
+                    if get_time() - victory_timer > VICTORY_REST_SECS + audio.victory_secs() {
                         victory_frame += 1;
                         victory_timer = get_time();
+                        if victory_frame <= 8 {
+                            audio.play(Channel::Music, |s| &s.victory);
+                        }
                     }
 
                     if victory_frame > 8 {
@@ -1158,106 +2102,239 @@ async fn main() {
                         } else {
                             current_player = 0;
                             shot_state = ShotState::new();
-                            state.sun_hit = false;
-                            state.reset_cityscape();
-                            generate_cityscape(&mut state);
-                            place_gorillas(&mut state);
+                            state.next_round();
                         }
                     }
                 } else {
+                    if shot_state.phase == ShotPhase::InputAngle {
+                        if playback
+                            .as_ref()
+                            .is_some_and(|p| p.hand_over && p.next_shot >= p.replay.shots.len())
+                        {
+                            playback = None;
+                        }
+                        if turn_saved.is_some() {
+                            phase = GamePhase::TurnSent;
+                        }
+                    }
                     match shot_state.phase {
-                        ShotPhase::InputAngle => {
-                            draw_shot_input(&shot_state, current_player);
+                        ShotPhase::InputAngle | ShotPhase::InputVelocity if playback.is_some() => {
+                            draw_shot_input(&shot_state, state.faces_left(current_player));
+                            if let Some(playback) = &mut playback {
+                                advance_playback(
+                                    playback,
+                                    &mut state,
+                                    &mut shot_state,
+                                    current_player,
+                                    &mut sim_clock,
+                                );
+                            }
+                        }
+                        ShotPhase::InputAngle | ShotPhase::InputVelocity
+                            if peer.as_ref().is_some_and(|peer| !peer.owns(current_player)) =>
+                        {
+                            draw_shot_input(&shot_state, state.faces_left(current_player));
+                            center_text(
+                                5,
+                                &format!("Waiting for {}...", state.player_names[current_player]),
+                            );
+                            if let Some((angle, velocity)) =
+                                peer.as_mut().and_then(Peer::next_throw)
+                            {
+                                shot_state.angle = angle;
+                                shot_state.angle_input = angle.to_string();
+                                shot_state.velocity_input = velocity.to_string();
+                                launch_shot(
+                                    &mut state,
+                                    &mut shot_state,
+                                    current_player,
+                                    velocity,
+                                    &mut sim_clock,
+                                    recording.as_mut(),
+                                    None,
+                                );
+                            }
+                        }
+                        ShotPhase::InputAngle | ShotPhase::InputVelocity
+                            if ai_typing.is_none()
+                                && matches!(bots.get(current_player), Some(Some(_))) =>
+                        {
+                            draw_shot_input(&shot_state, state.faces_left(current_player));
+                            let bot = bots[current_player].as_mut().unwrap();
+                            let asked = if bot.is_thinking() {
+                                Ok(())
+                            } else {
+                                bot.ask(&state, current_player)
+                            };
+                            // The answer is typed out like a computer player's.
+                            match asked.map(|_| bot.poll_throw()) {
+                                Ok(Some(Ok((angle, velocity)))) => {
+                                    ai_typing = Some(AiTyping::new(angle, velocity));
+                                }
+                                Ok(None) => {}
+                                Ok(Some(Err(err))) | Err(err) => {
+                                    eprintln!(
+                                        "{} ({}): {}. Over to the keyboard.",
+                                        state.player_names[current_player], bot.command, err
+                                    );
+                                    bots[current_player] = None;
+                                }
+                            }
+                        }
+                        ShotPhase::InputAngle | ShotPhase::InputVelocity
+                            if ai_typing.is_some()
+                                || matches!(ai_players.get(current_player), Some(Some(_))) =>
+                        {
+                            draw_shot_input(&shot_state, state.faces_left(current_player));
+                            if ai_typing.is_none() {
+                                let ai = ai_players[current_player].as_mut().unwrap();
+                                let thinking = ai_thinking.get_or_insert_with(|| {
+                                    ai.start_thinking(&state, current_player)
+                                });
+                                if let Some((angle, velocity)) = ai.think(thinking, &state) {
+                                    ai_thinking = None;
+                                    ai_typing = Some(AiTyping::new(angle, velocity));
+                                }
+                            }
 
-                            if let Some(key) = get_last_key_pressed() {
-                                match key {
-                                    KeyCode::Enter => {
+                            if let Some(typing) = ai_typing
+                                .as_mut()
+                                .filter(|typing| get_time() >= typing.next_key_time)
+                            {
+                                typing.next_key_time = get_time() + AI_KEY_SECS;
+                                match (typing.keys.pop_front(), shot_state.phase) {
+                                    (Some('\n'), ShotPhase::InputAngle) => {
                                         shot_state.angle =
-                                            shot_state.angle_input.parse().unwrap_or(45.0);
-                                        if current_player == 1 {
-                                            shot_state.angle = 180.0 - shot_state.angle;
-                                        }
+                                            shot_state.angle_input.parse().unwrap_or(DEFAULT_ANGLE);
                                         shot_state.phase = ShotPhase::InputVelocity;
                                     }
-                                    KeyCode::Backspace => {
-                                        shot_state.angle_input.pop();
+                                    (Some('\n'), _) => {
+                                        let velocity = shot_state
+                                            .velocity_input
+                                            .parse()
+                                            .unwrap_or(DEFAULT_VELOCITY);
+                                        launch_shot(
+                                            &mut state,
+                                            &mut shot_state,
+                                            current_player,
+                                            velocity,
+                                            &mut sim_clock,
+                                            recording.as_mut(),
+                                            peer.as_mut(),
+                                        );
+                                        ai_typing = None;
                                     }
-                                    _ => {}
-                                }
-                            }
-
-                            while let Some(c) = get_char_pressed() {
-                                if c.is_ascii_digit() || c == '.' {
-                                    shot_state.angle_input.push(c);
-                                }
-                            }
-                        }
-                        ShotPhase::InputVelocity => {
-                            draw_shot_input(&shot_state, current_player);
-
-                            if let Some(key) = get_last_key_pressed() {
-                                match key {
-                                    KeyCode::Enter => {
-                                        shot_state.velocity =
-                                            shot_state.velocity_input.parse().unwrap_or(50.0);
-                                        shot_state.phase = ShotPhase::Animating;
-                                        shot_state.t = 0.0;
-                                        shot_state.impact = false;
-                                        shot_state.on_screen = true;
-                                        shot_state.player_hit = None;
-                                        state.sun_hit = false;
+                                    (Some(c), ShotPhase::InputAngle) => {
+                                        audio.click();
+                                        shot_state.angle_input.push(c)
                                     }
-                                    KeyCode::Backspace => {
-                                        shot_state.velocity_input.pop();
+                                    (Some(c), _) => {
+                                        audio.click();
+                                        shot_state.velocity_input.push(c)
                                     }
-                                    _ => {}
+                                    (None, _) => ai_typing = None,
                                 }
                             }
-
-                            while let Some(c) = get_char_pressed() {
-                                if c.is_ascii_digit() || c == '.' {
-                                    shot_state.velocity_input.push(c);
-                                }
+                        }
+                        ShotPhase::InputAngle | ShotPhase::InputVelocity => {
+                            draw_shot_input(&shot_state, state.faces_left(current_player));
+
+                            if let Some(velocity) = type_throw(&mut shot_state, &mut *input) {
+                                launch_shot(
+                                    &mut state,
+                                    &mut shot_state,
+                                    current_player,
+                                    velocity,
+                                    &mut sim_clock,
+                                    recording.as_mut(),
+                                    peer.as_mut(),
+                                );
                             }
                         }
                         ShotPhase::Animating => {
-                            let arms = if current_player == 0 {
-                                LEFT_UP
-                            } else {
+                            let arms = if state.faces_left(current_player) {
                                 RIGHT_UP
+                            } else {
+                                LEFT_UP
                             };
                             let gx = state.gorilla_x[current_player];
                             let gy = state.gorilla_y[current_player];
-                            draw_gorilla(gx, gy, arms);
-
-                            let done =
-                                plot_shot(&mut state, &mut shot_state, current_player, gx, gy);
+                            sprites.gorilla(gx, gy, arms);
+
+                            let paused = playback.as_ref().is_some_and(|p| p.paused);
+                            let fast_forward = playback
+                                .as_mut()
+                                .is_some_and(|p| std::mem::take(&mut p.step));
+                            let ticks = if fast_forward {
+                                u32::MAX
+                            } else if paused {
+                                0
+                            } else {
+                                sim_clock.advance(get_frame_time() as f64)
+                            };
+                            if !in_flight {
+                                in_flight = true;
+                                shot_muted = fast_forward;
+                                sun_yelled = false;
+                                if !shot_muted {
+                                    audio.throw(shot_state.velocity, gx);
+                                }
+                            }
+                            step_shot(&mut state, &mut shot_state, current_player, ticks);
+                            audio.banana_moved(shot_state.x, paused);
+                            if state.sun_hit && !sun_yelled && !shot_muted {
+                                sun_yelled = true;
+                                audio.sun_hit();
+                            }
 
-                            if done {
-                                shot_state.phase = ShotPhase::Done;
+                            if shot_state.banana_visible() {
+                                let (bx, by) = shot_state.lerp_position(sim_clock.alpha());
+                                sprites.banana(bx, by, shot_state.banana_rotation());
                             }
                         }
                         ShotPhase::Done => {
-                            if let Some(hit_player) = shot_state.player_hit {
-                                do_explosion(shot_state.x, shot_state.y);
-
-                                if hit_player == current_player {
-                                    state.total_wins[1 - current_player] += 1;
-                                    winning_player = 1 - current_player;
-                                } else {
-                                    state.total_wins[current_player] += 1;
-                                    winning_player = current_player;
+                            if let Some(Some(ai)) = ai_players.get_mut(current_player) {
+                                ai.observe(&state, current_player, &shot_state);
+                            }
+                            if let Some(Some(bot)) = bots.get_mut(current_player) {
+                                bot.observe(&shot_state);
+                            }
+                            if let Some((x, y)) = explosion_point(&shot_state) {
+                                paint_explosion(&mut Gpu, scheme(), x, y);
+                            }
+                            if in_flight && !shot_muted {
+                                audio.landed(&shot_state);
+                            }
+                            in_flight = false;
+
+                            let winner = resolve_shot(&mut state, &shot_state, current_player);
+                            if let Some(err) = peer
+                                .as_mut()
+                                .and_then(|peer| peer.throw_resolved(&state).err())
+                            {
+                                net_error = hang_up(&mut peer, err);
+                                phase = GamePhase::NetError;
+                            }
+                            let by_file = recording.as_mut().filter(|r| r.ledger.is_some());
+                            if let (Some(recording), None) = (by_file, &playback) {
+                                match recording.save_turn(&state) {
+                                    Ok(path) => turn_saved = Some(path),
+                                    Err(err) => {
+                                        eprintln!("{}", err);
+                                        turn_error = err;
+                                        phase = GamePhase::BadTurnFile;
+                                    }
                                 }
-
+                            }
+                            if let Some(winner) = winner {
+                                winning_team = winner;
+                                hit_player = shot_state.player_hit;
                                 showing_victory = true;
                                 victory_frame = 0;
                                 victory_timer = get_time();
-                            } else if shot_state.impact {
-                                do_explosion(shot_state.x + 4.0, shot_state.y + 4.0);
-                                current_player = 1 - current_player;
-                                shot_state = ShotState::new();
+                                audio.play(Channel::Music, |s| &s.victory);
                             } else {
-                                current_player = 1 - current_player;
+                                current_player = state.next_player(current_player);
                                 shot_state = ShotState::new();
                             }
 
@@ -1272,7 +2349,55 @@ async fn main() {
             GamePhase::GameOver => {
                 draw_game_over(&state, sparkle_offset);
 
-                if get_last_key_pressed().is_some() || is_mouse_button_pressed(MouseButton::Left) {
+                if input.any_pressed() {
+                    phase = if turn_saved.is_some() {
+                        GamePhase::TurnSent
+                    } else {
+                        GamePhase::PlayAgain
+                    };
+                }
+            }
+
+            GamePhase::TurnSent => {
+                clear_background(BLACK);
+                let path = turn_saved.as_deref().unwrap_or_default();
+                center_text(10, &format!("Your turn is saved to {}", path));
+                if current_game >= state.num_games {
+                    center_text(12, "Send it to the others so they can see how it ended.");
+                } else {
+                    center_text(
+                        12,
+                        &format!(
+                            "Send it to {}, who plays it with --turns.",
+                            state.player_names[current_player]
+                        ),
+                    );
+                }
+                center_text(24, "Press any key to quit");
+
+                if input.any_pressed() {
+                    break;
+                }
+            }
+
+            GamePhase::BadTurnFile => {
+                clear_background(BLACK);
+                center_text(10, "Turn file error");
+                center_text(12, &turn_error);
+                center_text(24, "Press any key to quit");
+
+                if input.any_pressed() {
+                    break;
+                }
+            }
+
+            GamePhase::NetError => {
+                clear_background(BLACK);
+                center_text(10, "Network game over");
+                center_text(12, &net_error);
+                center_text(24, "Press any key to continue");
+
+                if input.any_pressed() {
                     phase = GamePhase::PlayAgain;
                 }
             }
@@ -1280,19 +2405,24 @@ async fn main() {
             GamePhase::PlayAgain => {
                 draw_play_again();
 
-                if let Some(key) = get_last_key_pressed() {
+                if let Some(key) = input.last_key_pressed() {
                     match key {
-                        KeyCode::Y => {
+                        // The guest's next match is whatever the host sets up.
+                        Key::Char('Y') if peer.as_ref().is_some_and(|p| p.role == Role::Guest) => {
+                            phase = GamePhase::MatchStart;
+                            ai_typing = None;
+                            ai_thinking = None;
+                        }
+                        Key::Char('Y') => {
                             phase = GamePhase::GetInputs;
-                            input_buffer.clear();
-                            player1_input.clear();
-                            player2_input.clear();
-                            games_input.clear();
-                            gravity_input.clear();
-                            current_input_field = InputField::Player1Name;
-                            state = GameState::new();
+                            playback = None;
+                            ai_players.clear();
+                            ai_typing = None;
+                            ai_thinking = None;
+                            state = new_game_state();
+                            setup = Setup::new(&state, random_seed());
                         }
-                        KeyCode::N => {
+                        Key::Char('N') => {
                             break;
                         }
                         _ => {}
@@ -1301,6 +2431,10 @@ async fn main() {
             }
         }
 
-        next_frame().await
+        screen.show().await
+    }
+
+    if let Some(peer) = peer {
+        peer.close();
     }
 }
@@ -0,0 +1,147 @@
+//! How loud each kind of sound plays, and where it sits between the
+//! speakers.
+//!
+//! Sounds go out on one of three channels, each with its own volume, and a
+//! mute switch silences them all. The settings are kept with `storage`
+//! as lines of `<name> <value>`:
+//!
+//! ```text
+//! music 0.6
+//! effects 0.8
+//! ui 0.5
+//! muted false
+//! pc_speaker false
+//! ```
+//!
+//! Unknown lines are skipped, so older builds can read newer settings.
+
+use crate::{storage, VIRTUAL_WIDTH};
+use std::f32::consts::FRAC_PI_2;
+use std::fmt;
+
+/// Name the settings are stored under.
+pub const SETTINGS_NAME: &str = "sound.cfg";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Channel {
+    /// The intro tune and the victory jingle.
+    Music,
+    /// Throws, explosions and the sun.
+    Effects,
+    /// Keys typed into prompts and menus.
+    Ui,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Music, Channel::Effects, Channel::Ui];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Music => "Music",
+            Channel::Effects => "Effects",
+            Channel::Ui => "UI",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Channel::Music => "music",
+            Channel::Effects => "effects",
+            Channel::Ui => "ui",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MixerSettings {
+    // Indexed like `Channel::ALL`, each from 0.0 to 1.0.
+    volumes: [f32; 3],
+    pub muted: bool,
+    /// Play the effects as the original's PC speaker beeps instead of the
+    /// synthesized ones.
+    pub pc_speaker: bool,
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        MixerSettings {
+            volumes: [0.6, 0.8, 0.5],
+            muted: false,
+            pc_speaker: false,
+        }
+    }
+}
+
+impl MixerSettings {
+    /// The saved settings, or the defaults if there are none.
+    pub fn load() -> Self {
+        storage::load(SETTINGS_NAME).map_or_else(Self::default, |text| Self::parse(&text))
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        storage::save(SETTINGS_NAME, &self.to_string())
+    }
+
+    /// Reads settings, keeping the default for anything missing or garbled.
+    pub fn parse(text: &str) -> Self {
+        let mut settings = MixerSettings::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.trim().split_once(' ') else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "muted" => settings.muted = value.parse().unwrap_or(settings.muted),
+                "pc_speaker" => settings.pc_speaker = value.parse().unwrap_or(settings.pc_speaker),
+                _ => {
+                    if let (Some(channel), Ok(volume)) = (
+                        Channel::ALL.into_iter().find(|c| c.key() == key),
+                        value.parse::<f32>(),
+                    ) {
+                        settings.set_volume(channel, volume);
+                    }
+                }
+            }
+        }
+        settings
+    }
+
+    pub fn volume(&self, channel: Channel) -> f32 {
+        self.volumes[channel as usize]
+    }
+
+    /// Sets a channel's volume, clamped to `0.0..=1.0`.
+    pub fn set_volume(&mut self, channel: Channel, volume: f32) {
+        if volume.is_finite() {
+            self.volumes[channel as usize] = volume.clamp(0.0, 1.0);
+        }
+    }
+
+    /// How loud to play a sound on `channel`, after muting.
+    pub fn gain(&self, channel: Channel) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume(channel)
+        }
+    }
+}
+
+impl fmt::Display for MixerSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for channel in Channel::ALL {
+            writeln!(f, "{} {}", channel.key(), self.volume(channel))?;
+        }
+        writeln!(f, "muted {}", self.muted)?;
+        writeln!(f, "pc_speaker {}", self.pc_speaker)
+    }
+}
+
+/// Left and right speaker gains for a sound at `x` on the playfield. The
+/// power stays the same across the screen, so a banana doesn't get quieter
+/// as it passes the middle.
+pub fn pan(x: f32) -> (f32, f32) {
+    let position = (x / VIRTUAL_WIDTH).clamp(0.0, 1.0);
+    let angle = position * FRAC_PI_2;
+    (angle.cos(), angle.sin())
+}
@@ -0,0 +1,310 @@
+//! QBasic `PLAY` strings, sung by a PC speaker.
+//!
+//! The original game's music and sound effects were strings in QBasic's
+//! music macro language, such as `"MBT160O1L8CDEDCDL4ECC"` for the intro
+//! tune. `Tune::parse` understands the same commands:
+//!
+//! | Command        | Meaning                                              |
+//! |----------------|------------------------------------------------------|
+//! | `A`..`G`       | a note, optionally sharp (`+`, `#`) or flat (`-`),   |
+//! |                | with its own length (`C8`) and dots (`C.`)           |
+//! | `N n`          | note number `n` from 1 to 84, or a rest for 0        |
+//! | `P n`          | a rest of length `n`, which may be dotted            |
+//! | `O n`, `<`, `>`| set the octave (0 to 6, middle C starts octave 3),   |
+//! |                | or move down or up one                               |
+//! | `L n`          | default length: 1 whole, 4 quarter, up to 64         |
+//! | `T n`          | tempo in quarter notes a minute, 32 to 255           |
+//! | `MN` `ML` `MS` | normal, legato or staccato notes                     |
+//! | `MF` `MB`      | play in the foreground or background                 |
+//!
+//! Commands are case-insensitive and spaces are ignored. Every string
+//! starts from QBasic's defaults (`T120 O4 L4 MN MF`) rather than where the
+//! last one left off. In the foreground QBasic waited for the music to
+//! finish before going on; `Tune::background` says which was asked for and
+//! the caller decides what to do about it.
+
+use std::fmt;
+
+/// Samples per second of rendered tunes.
+pub const SAMPLE_RATE: u32 = 22_050;
+
+// Loudness of the square wave, out of the full 16-bit range.
+const AMPLITUDE: f32 = 0.25;
+
+/// A note or rest.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tone {
+    /// Pitch in hertz, or `None` for a rest.
+    pub frequency: Option<f32>,
+    /// Seconds until the next tone starts.
+    pub duration: f32,
+    /// Seconds of that the speaker sounds, per the articulation.
+    pub sounding: f32,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Tune {
+    pub tones: Vec<Tone>,
+    /// Whether the string asked for `MB`, to go on while the music plays.
+    pub background: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MmlError {
+    /// Byte offset into the string.
+    pub position: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for MmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad PLAY string at {}: {}", self.position, self.reason)
+    }
+}
+
+impl std::error::Error for MmlError {}
+
+// Semitones above C of the note letters A to G.
+const LETTER_SEMITONES: [u32; 7] = [9, 11, 0, 2, 4, 5, 7];
+
+const MAX_OCTAVE: u32 = 6;
+const MAX_NOTE: u32 = 84;
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).map(u8::to_ascii_uppercase)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, reason: impl Into<String>) -> MmlError {
+        self.error_at(self.position, reason)
+    }
+
+    fn error_at(&self, position: usize, reason: impl Into<String>) -> MmlError {
+        MmlError {
+            position,
+            reason: reason.into(),
+        }
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        self.skip_spaces();
+        let start = self.position;
+        while self.text.get(self.position).is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.position])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    // A number that must be there and in `range`.
+    fn argument(
+        &mut self,
+        command: char,
+        range: std::ops::RangeInclusive<u32>,
+    ) -> Result<u32, MmlError> {
+        let start = self.position;
+        match self.number() {
+            Some(n) if range.contains(&n) => Ok(n),
+            Some(n) => Err(self.error_at(
+                start,
+                format!(
+                    "{}{} is out of range {}..={}",
+                    command,
+                    n,
+                    range.start(),
+                    range.end()
+                ),
+            )),
+            None => Err(self.error(format!("{} needs a number", command))),
+        }
+    }
+
+    fn dots(&mut self) -> u32 {
+        let mut dots = 0;
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some(b'.') {
+                return dots;
+            }
+            self.position += 1;
+            dots += 1;
+        }
+    }
+}
+
+impl Tune {
+    pub fn parse(play: &str) -> Result<Tune, MmlError> {
+        let mut parser = Parser {
+            text: play.as_bytes(),
+            position: 0,
+        };
+        let mut tune = Tune::default();
+        let mut tempo = 120;
+        let mut octave = 4;
+        let mut length = 4;
+        // Fraction of each note that sounds.
+        let mut articulation = 7.0 / 8.0;
+
+        loop {
+            parser.skip_spaces();
+            let Some(command) = parser.peek() else {
+                return Ok(tune);
+            };
+            parser.position += 1;
+            match command {
+                b'A'..=b'G' => {
+                    let mut semitone = LETTER_SEMITONES[(command - b'A') as usize] as i32;
+                    match parser.peek() {
+                        Some(b'+' | b'#') => {
+                            semitone += 1;
+                            parser.position += 1;
+                        }
+                        Some(b'-') => {
+                            semitone -= 1;
+                            parser.position += 1;
+                        }
+                        _ => {}
+                    }
+                    let start = parser.position;
+                    let note_length = match parser.number() {
+                        Some(n) if (1..=64).contains(&n) => n,
+                        Some(n) => {
+                            return Err(parser.error_at(
+                                start,
+                                format!("note length {} is out of range 1..=64", n),
+                            ))
+                        }
+                        None => length,
+                    };
+                    let note = (octave * 12) as i32 + semitone + 1;
+                    let note = note.clamp(1, MAX_NOTE as i32) as u32;
+                    let duration = note_duration(tempo, note_length, parser.dots());
+                    tune.push(Some(note), duration, articulation);
+                }
+                b'N' => {
+                    let note = parser.argument('N', 0..=MAX_NOTE)?;
+                    let duration = note_duration(tempo, length, parser.dots());
+                    tune.push(Some(note).filter(|&n| n > 0), duration, articulation);
+                }
+                b'P' => {
+                    let rest = parser.argument('P', 1..=64)?;
+                    let duration = note_duration(tempo, rest, parser.dots());
+                    tune.push(None, duration, articulation);
+                }
+                b'O' => octave = parser.argument('O', 0..=MAX_OCTAVE)?,
+                b'<' => octave = octave.saturating_sub(1),
+                b'>' => octave = (octave + 1).min(MAX_OCTAVE),
+                b'L' => length = parser.argument('L', 1..=64)?,
+                b'T' => tempo = parser.argument('T', 32..=255)?,
+                b'M' => {
+                    match parser.peek() {
+                        Some(b'N') => articulation = 7.0 / 8.0,
+                        Some(b'L') => articulation = 1.0,
+                        Some(b'S') => articulation = 3.0 / 4.0,
+                        Some(b'F') => tune.background = false,
+                        Some(b'B') => tune.background = true,
+                        _ => return Err(parser.error("M must be followed by N, L, S, F or B")),
+                    }
+                    parser.position += 1;
+                }
+                _ => {
+                    parser.position -= 1;
+                    return Err(parser.error(format!("unknown command '{}'", command as char)));
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, note: Option<u32>, duration: f32, articulation: f32) {
+        // Middle C is note 37, so A above it is 46.
+        let frequency = note.map(|n| 440.0 * 2f32.powf((n as f32 - 46.0) / 12.0));
+        self.tones.push(Tone {
+            frequency,
+            duration,
+            sounding: duration * articulation,
+        });
+    }
+
+    /// How long the tune takes to play, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.tones.iter().map(|tone| tone.duration).sum()
+    }
+
+    /// The tune as a square wave, one sample per `1 / sample_rate` seconds.
+    pub fn render(&self, sample_rate: u32) -> Vec<f32> {
+        let mut samples = Vec::new();
+        let mut phase = 0.0;
+        for tone in &self.tones {
+            let total = (tone.duration * sample_rate as f32).round() as usize;
+            let sounding = (tone.sounding * sample_rate as f32).round() as usize;
+            for i in 0..total {
+                let sample = match tone.frequency {
+                    Some(frequency) if i < sounding => {
+                        phase = (phase + frequency / sample_rate as f32).fract();
+                        if phase < 0.5 {
+                            AMPLITUDE
+                        } else {
+                            -AMPLITUDE
+                        }
+                    }
+                    _ => 0.0,
+                };
+                samples.push(sample);
+            }
+        }
+        samples
+    }
+
+    /// The rendered tune as a mono 16-bit WAV file.
+    pub fn to_wav(&self, sample_rate: u32) -> Vec<u8> {
+        wav(&self.render(sample_rate), 1, sample_rate)
+    }
+}
+
+fn note_duration(tempo: u32, length: u32, dots: u32) -> f32 {
+    // A whole note is four beats. Each dot adds half of what the last added,
+    // so two dots make it 1.75 times as long.
+    240.0 / (tempo * length) as f32 * (2.0 - 0.5f32.powi(dots as i32))
+}
+
+/// Packs samples between -1.0 and 1.0 into a 16-bit PCM WAV file. With
+/// more than one channel the samples are interleaved, left first.
+pub fn wav(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<u8> {
+    let block_align = channels * 2;
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
@@ -0,0 +1,542 @@
+//! Two-machine matches over TCP.
+//!
+//! Both peers run the whole simulation. The host picks the settings and the
+//! seed and sends them once; after that only throws cross the wire, and each
+//! peer flies them through `plot_shot` locally. Since everything follows
+//! from the seed and the throws, the two games stay in lockstep. To catch
+//! the day they don't, each peer sends a checksum of its `GameState` after
+//! every throw and compares it with the other side's.
+//!
+//! The protocol is line-based text:
+//!
+//! ```text
+//! HELLO 1
+//! SETTINGS
+//! GORREPLAY 2
+//! seed 1234567
+//! ...
+//! END
+//! THROW 0 45 60
+//! HASH 0 9e3779b97f4a7c15
+//! BYE
+//! ```
+//!
+//! The settings travel in the `.gorreplay` format, without shots.
+//!
+//! The browser build cannot open sockets, so it meets its opponent through
+//! `gorillas-relay` instead. Each tab opens a WebSocket to the relay and
+//! names a room:
+//!
+//! ```text
+//! ROOM BANANA
+//! ```
+//!
+//! Once a second tab names the same room, the relay answers `PAIRED HOST` to
+//! the first and `PAIRED GUEST` to the second, and from then on forwards
+//! each WebSocket message to the other tab untouched. Those messages are the
+//! protocol above, one message per WebSocket message. A third tab gets
+//! `FULL`, and if one tab goes away the relay tells the other `BYE`.
+
+mod websocket;
+
+use crate::replay::{Replay, ReplayError};
+use crate::GameState;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+use websocket::WebSocket;
+
+pub const DEFAULT_PORT: u16 = 7355;
+/// Port `gorillas-relay` listens on unless told otherwise.
+pub const DEFAULT_RELAY_PORT: u16 = 7356;
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(std::io::Error),
+    VersionMismatch {
+        ours: u32,
+        theirs: u32,
+    },
+    Protocol(String),
+    BadSettings(ReplayError),
+    /// The checksums after a throw differ: the two games no longer agree.
+    Desync {
+        turn: u32,
+        ours: u64,
+        theirs: u64,
+    },
+    /// The other side said goodbye.
+    Left,
+    /// The connection dropped without a goodbye.
+    ConnectionLost,
+    /// Two players are already in this room on the relay.
+    RoomFull(String),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetError::Io(err) => write!(f, "network error: {}", err),
+            NetError::VersionMismatch { ours, theirs } => write!(
+                f,
+                "other side speaks protocol version {} (we speak {})",
+                theirs, ours
+            ),
+            NetError::Protocol(reason) => write!(f, "protocol error: {}", reason),
+            NetError::BadSettings(err) => write!(f, "bad match settings: {}", err),
+            NetError::Desync { turn, ours, theirs } => write!(
+                f,
+                "games out of sync after throw {} (ours {:016x}, theirs {:016x})",
+                turn + 1,
+                ours,
+                theirs
+            ),
+            NetError::Left => write!(f, "the other player left"),
+            NetError::ConnectionLost => write!(f, "connection lost"),
+            NetError::RoomFull(code) => write!(f, "room {} already has two players", code),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+impl From<std::io::Error> for NetError {
+    fn from(err: std::io::Error) -> Self {
+        NetError::Io(err)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Message {
+    Hello {
+        version: u32,
+    },
+    Settings(Replay),
+    Throw {
+        turn: u32,
+        angle: f32,
+        velocity: f32,
+    },
+    Hash {
+        turn: u32,
+        hash: u64,
+    },
+    Bye,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Hello { version } => writeln!(f, "HELLO {}", version),
+            Message::Settings(replay) => write!(f, "SETTINGS\n{}END\n", replay),
+            Message::Throw {
+                turn,
+                angle,
+                velocity,
+            } => writeln!(f, "THROW {} {} {}", turn, angle, velocity),
+            Message::Hash { turn, hash } => writeln!(f, "HASH {} {:016x}", turn, hash),
+            Message::Bye => writeln!(f, "BYE"),
+        }
+    }
+}
+
+// Parses the message starting at `line`. A SETTINGS block takes the lines
+// after it, up to END, from `rest`. Blank lines give `None`.
+fn parse_message(
+    line: &str,
+    rest: &mut impl Iterator<Item = String>,
+) -> Option<Result<Message, NetError>> {
+    let protocol = |reason: String| Some(Err(NetError::Protocol(reason)));
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("HELLO"), Some(version), None, None) => match version.parse() {
+            Ok(version) => Some(Ok(Message::Hello { version })),
+            Err(_) => protocol(format!("bad version in '{}'", line)),
+        },
+        (Some("SETTINGS"), None, None, None) => {
+            let mut text = String::new();
+            for line in rest.take_while(|line| line.trim() != "END") {
+                text.push_str(&line);
+                text.push('\n');
+            }
+            Some(
+                Replay::parse(&text)
+                    .map(Message::Settings)
+                    .map_err(NetError::BadSettings),
+            )
+        }
+        (Some("THROW"), Some(turn), Some(angle), Some(velocity)) => {
+            match (turn.parse(), angle.parse::<f32>(), velocity.parse::<f32>()) {
+                // "NaN" and "inf" parse as floats, but a throw can't be flown
+                // with them.
+                (Ok(turn), Ok(angle), Ok(velocity))
+                    if angle.is_finite() && velocity.is_finite() =>
+                {
+                    Some(Ok(Message::Throw {
+                        turn,
+                        angle,
+                        velocity,
+                    }))
+                }
+                _ => protocol(format!("bad throw '{}'", line)),
+            }
+        }
+        (Some("HASH"), Some(turn), Some(hash), None) => {
+            match (turn.parse(), u64::from_str_radix(hash, 16)) {
+                (Ok(turn), Ok(hash)) => Some(Ok(Message::Hash { turn, hash })),
+                _ => protocol(format!("bad hash '{}'", line)),
+            }
+        }
+        (Some("BYE"), None, None, None) => Some(Ok(Message::Bye)),
+        (None, ..) => None,
+        _ => protocol(format!("unknown message '{}'", line)),
+    }
+}
+
+// Reads messages off the socket until it closes. Runs on its own thread so
+// the game loop never blocks on the network.
+fn read_messages(stream: TcpStream, sender: mpsc::Sender<Result<Message, NetError>>) {
+    let mut lines = BufReader::new(stream).lines().map_while(Result::ok);
+
+    while let Some(line) = lines.next() {
+        let Some(message) = parse_message(&line, &mut lines) else {
+            continue;
+        };
+        let done = matches!(message, Ok(Message::Bye) | Err(_));
+        if sender.send(message).is_err() || done {
+            return;
+        }
+    }
+    let _ = sender.send(Err(NetError::ConnectionLost));
+}
+
+// How a peer reaches the other side.
+enum Link {
+    Tcp {
+        stream: TcpStream,
+        incoming: Receiver<Result<Message, NetError>>,
+    },
+    // Through the relay. Messages that arrived together in one WebSocket
+    // message wait in `pending`.
+    Relay {
+        socket: WebSocket,
+        pending: VecDeque<Result<Message, NetError>>,
+    },
+}
+
+impl Link {
+    fn tcp(stream: TcpStream) -> Result<Self, NetError> {
+        stream.set_nodelay(true)?;
+        let (sender, incoming) = mpsc::channel();
+        let reader = stream.try_clone()?;
+        std::thread::spawn(move || read_messages(reader, sender));
+        Ok(Link::Tcp { stream, incoming })
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), NetError> {
+        let text = message.to_string();
+        let sent = match self {
+            Link::Tcp { stream, .. } => stream.write_all(text.as_bytes()),
+            Link::Relay { socket, .. } => socket.send(&text),
+        };
+        sent.map_err(|_| NetError::ConnectionLost)
+    }
+
+    // The next message that has arrived, if any.
+    fn recv(&mut self) -> Option<Result<Message, NetError>> {
+        match self {
+            Link::Tcp { incoming, .. } => match incoming.try_recv() {
+                Ok(message) => Some(message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Err(NetError::ConnectionLost)),
+            },
+            Link::Relay { socket, pending } => {
+                while pending.is_empty() {
+                    match socket.recv() {
+                        Ok(Some(text)) => {
+                            let mut lines = text.lines().map(str::to_string);
+                            while let Some(line) = lines.next() {
+                                pending.extend(parse_message(&line, &mut lines));
+                            }
+                        }
+                        Ok(None) => return None,
+                        Err(_) => return Some(Err(NetError::ConnectionLost)),
+                    }
+                }
+                pending.pop_front()
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        match self {
+            Link::Tcp { stream, .. } => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            Link::Relay { socket, .. } => socket.close(),
+        }
+    }
+}
+
+/// Waits for a guest without blocking the game loop.
+pub struct Listener {
+    listener: TcpListener,
+}
+
+impl Listener {
+    pub fn bind(port: u16) -> Result<Self, NetError> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener { listener })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |addr| addr.port())
+    }
+
+    /// The connected guest, once one has turned up.
+    pub fn try_accept(&self) -> Result<Option<Peer>, NetError> {
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                Peer::start(Link::tcp(stream)?, Role::Host).map(Some)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// The other side of a match.
+pub struct Peer {
+    pub role: Role,
+    link: Link,
+    greeted: bool,
+    settings: Option<Replay>,
+    // Throws made so far by either side, and the other side's throws that
+    // have arrived but not been played yet.
+    turn: u32,
+    throws: VecDeque<(f32, f32)>,
+    resolved: u32,
+    local_hashes: HashMap<u32, u64>,
+    remote_hashes: HashMap<u32, u64>,
+}
+
+impl Peer {
+    /// Connects to a host at `addr`, e.g. `192.168.1.20:7355`.
+    pub fn join(addr: &str) -> Result<Self, NetError> {
+        let addrs = addr.to_socket_addrs()?;
+        let mut last_err = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Peer::start(Link::tcp(stream)?, Role::Guest),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err
+            .map(NetError::Io)
+            .unwrap_or_else(|| NetError::Protocol(format!("no address for '{}'", addr))))
+    }
+
+    fn start(link: Link, role: Role) -> Result<Self, NetError> {
+        let mut peer = Peer {
+            role,
+            link,
+            greeted: false,
+            settings: None,
+            turn: 0,
+            throws: VecDeque::new(),
+            resolved: 0,
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+        };
+        peer.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+        })?;
+        Ok(peer)
+    }
+
+    /// Whether `player` is played on this machine. The host takes players 1,
+    /// 3 and 5, the guest 2, 4 and 6.
+    pub fn owns(&self, player: usize) -> bool {
+        player.is_multiple_of(2) == (self.role == Role::Host)
+    }
+
+    /// Host only: sends the match settings, including the seed.
+    pub fn send_settings(&mut self, state: &GameState) -> Result<(), NetError> {
+        self.send(&Message::Settings(Replay::new(state)))
+    }
+
+    /// Guest only: the host's settings, once they have arrived.
+    pub fn take_settings(&mut self) -> Option<Replay> {
+        self.settings.take()
+    }
+
+    /// Sends a throw made on this machine.
+    pub fn send_throw(&mut self, angle: f32, velocity: f32) -> Result<(), NetError> {
+        let turn = self.turn;
+        self.turn += 1;
+        self.send(&Message::Throw {
+            turn,
+            angle,
+            velocity,
+        })
+    }
+
+    /// The other side's next throw, as typed, if it has arrived.
+    pub fn next_throw(&mut self) -> Option<(f32, f32)> {
+        let throw = self.throws.pop_front()?;
+        self.turn += 1;
+        Some(throw)
+    }
+
+    /// Call once a throw has been fully resolved. Sends our checksum and
+    /// checks it against the other side's.
+    pub fn throw_resolved(&mut self, state: &GameState) -> Result<(), NetError> {
+        let turn = self.resolved;
+        self.resolved += 1;
+        let hash = state.checksum();
+        self.local_hashes.insert(turn, hash);
+        self.send(&Message::Hash { turn, hash })?;
+        self.compare(turn)
+    }
+
+    /// Handles everything that has arrived since the last call. Returns an
+    /// error once the match cannot go on.
+    pub fn poll(&mut self) -> Result<(), NetError> {
+        while let Some(message) = self.link.recv() {
+            let message = message?;
+            match message {
+                Message::Hello { version } if version == PROTOCOL_VERSION => self.greeted = true,
+                Message::Hello { version } => {
+                    return Err(NetError::VersionMismatch {
+                        ours: PROTOCOL_VERSION,
+                        theirs: version,
+                    })
+                }
+                _ if !self.greeted => {
+                    return Err(NetError::Protocol("expected HELLO first".to_string()))
+                }
+                Message::Settings(replay) if self.role == Role::Guest => {
+                    self.settings = Some(replay)
+                }
+                Message::Settings(_) => {
+                    return Err(NetError::Protocol(
+                        "only the host sends settings".to_string(),
+                    ))
+                }
+                Message::Throw {
+                    turn,
+                    angle,
+                    velocity,
+                } => {
+                    let expected = self.turn + self.throws.len() as u32;
+                    if turn != expected {
+                        return Err(NetError::Protocol(format!(
+                            "got throw {} while expecting throw {}",
+                            turn + 1,
+                            expected + 1
+                        )));
+                    }
+                    self.throws.push_back((angle, velocity));
+                }
+                Message::Hash { turn, hash } => {
+                    self.remote_hashes.insert(turn, hash);
+                    self.compare(turn)?;
+                }
+                Message::Bye => return Err(NetError::Left),
+            }
+        }
+        Ok(())
+    }
+
+    /// Says goodbye and hangs up.
+    pub fn close(mut self) {
+        let _ = self.send(&Message::Bye);
+        self.link.close();
+    }
+
+    fn compare(&mut self, turn: u32) -> Result<(), NetError> {
+        if let (Some(&ours), Some(&theirs)) =
+            (self.local_hashes.get(&turn), self.remote_hashes.get(&turn))
+        {
+            self.local_hashes.remove(&turn);
+            self.remote_hashes.remove(&turn);
+            if ours != theirs {
+                return Err(NetError::Desync { turn, ours, theirs });
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), NetError> {
+        self.link.send(message)
+    }
+}
+
+/// A room on `gorillas-relay`, waiting for its second player. Only the
+/// browser build can open one.
+pub struct Room {
+    pub code: String,
+    socket: Option<WebSocket>,
+}
+
+impl Room {
+    /// Opens `code` on the relay at `relay_url`. An empty URL leaves the
+    /// choice to the page; see `index.html`.
+    pub fn open(relay_url: &str, code: &str) -> Result<Self, NetError> {
+        let mut socket = WebSocket::open(relay_url)?;
+        socket.send(&format!("ROOM {}", code))?;
+        Ok(Room {
+            code: code.to_string(),
+            socket: Some(socket),
+        })
+    }
+
+    /// The other player, once the relay has paired us. Whoever opened the
+    /// room first hosts.
+    pub fn try_pair(&mut self) -> Result<Option<Peer>, NetError> {
+        let Some(socket) = self.socket.as_mut() else {
+            return Err(NetError::ConnectionLost);
+        };
+        let Some(reply) = socket.recv().map_err(|_| NetError::ConnectionLost)? else {
+            return Ok(None);
+        };
+        let role = match reply.trim() {
+            "PAIRED HOST" => Role::Host,
+            "PAIRED GUEST" => Role::Guest,
+            "FULL" => return Err(NetError::RoomFull(self.code.clone())),
+            _ => {
+                return Err(NetError::Protocol(format!(
+                    "unexpected reply '{}' from the relay",
+                    reply.trim()
+                )))
+            }
+        };
+        let link = Link::Relay {
+            socket: self.socket.take().unwrap(),
+            pending: VecDeque::new(),
+        };
+        Peer::start(link, role).map(Some)
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        if let Some(socket) = self.socket.as_mut() {
+            socket.close();
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! WebSockets for the browser build.
+//!
+//! A wasm module cannot open sockets itself, so these calls go out to the
+//! `gorillas_ws` plugin that `index.html` registers with the miniquad
+//! loader. The browser queues what arrives, and `recv` picks it up one
+//! message at a time from the game loop. Native builds play over TCP instead,
+//! and their sockets never open.
+
+use std::io;
+
+#[cfg(target_arch = "wasm32")]
+mod js {
+    extern "C" {
+        pub fn gorillas_ws_open(url: *const u8, len: usize) -> i32;
+        pub fn gorillas_ws_send(socket: i32, text: *const u8, len: usize) -> i32;
+        // Length of the next queued message, -1 if none has arrived and -2
+        // once the socket has closed and the queue is empty.
+        pub fn gorillas_ws_next_len(socket: i32) -> i32;
+        pub fn gorillas_ws_recv(socket: i32, buf: *mut u8, len: usize);
+        pub fn gorillas_ws_close(socket: i32);
+    }
+}
+
+// Lets the loader check that `index.html` carries the matching plugin.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+pub extern "C" fn gorillas_ws_crate_version() -> u32 {
+    1
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct WebSocket {
+    id: i32,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebSocket {
+    /// Starts connecting. Messages sent before the connection is up are
+    /// held by the browser side until it is.
+    pub fn open(url: &str) -> io::Result<Self> {
+        let id = unsafe { js::gorillas_ws_open(url.as_ptr(), url.len()) };
+        if id < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "the page cannot open WebSockets",
+            ));
+        }
+        Ok(WebSocket { id })
+    }
+
+    pub fn send(&mut self, text: &str) -> io::Result<()> {
+        match unsafe { js::gorillas_ws_send(self.id, text.as_ptr(), text.len()) } {
+            0 => Ok(()),
+            _ => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// The next message, if one has arrived.
+    pub fn recv(&mut self) -> io::Result<Option<String>> {
+        match unsafe { js::gorillas_ws_next_len(self.id) } {
+            -1 => Ok(None),
+            len if len < 0 => Err(io::ErrorKind::ConnectionAborted.into()),
+            len => {
+                let mut buf = vec![0; len as usize];
+                unsafe { js::gorillas_ws_recv(self.id, buf.as_mut_ptr(), buf.len()) };
+                String::from_utf8(buf)
+                    .map(Some)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            }
+        }
+    }
+
+    pub fn close(&mut self) {
+        unsafe { js::gorillas_ws_close(self.id) };
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct WebSocket {
+    _never_opened: (),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WebSocket {
+    pub fn open(_url: &str) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "rooms are only available in the browser build",
+        ))
+    }
+
+    pub fn send(&mut self, _text: &str) -> io::Result<()> {
+        Err(io::ErrorKind::NotConnected.into())
+    }
+
+    pub fn recv(&mut self) -> io::Result<Option<String>> {
+        Err(io::ErrorKind::NotConnected.into())
+    }
+
+    pub fn close(&mut self) {}
+}
@@ -0,0 +1,393 @@
+//! Drawing the playfield on anything that can fill a few shapes.
+//!
+//! The painters here draw the city, the gorillas, the banana, the sun and
+//! explosions through `Renderer`, in playfield pixels. The window implements
+//! it with macroquad on the GPU, and `Rgba` implements it in software, so a
+//! scene can be drawn into memory and checked without a window at all.
+
+use crate::display::{Rgb, Scheme};
+use crate::{GameState, EXPLOSION_RADIUS, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+use std::f32::consts::PI;
+
+// Arm positions, numbered as in the original.
+pub const RIGHT_UP: i32 = 1;
+pub const LEFT_UP: i32 = 2;
+pub const ARMS_DOWN: i32 = 3;
+
+pub const BLACK: Rgb = [0, 0, 0];
+
+/// Shapes in playfield pixels, x to the right and y down.
+pub trait Renderer {
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgb);
+
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Rgb);
+
+    /// A line `thickness` wide, centred on the segment, with square ends.
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Rgb);
+
+    /// Part of a circle outline, as QBasic's CIRCLE with start and end
+    /// angles drew it: a band `thickness` wide centred on `radius`, from
+    /// `start` through `sweep`. Angles are in radians, clockwise from the
+    /// right.
+    #[allow(clippy::too_many_arguments)]
+    fn arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        thickness: f32,
+        start: f32,
+        sweep: f32,
+        color: Rgb,
+    );
+}
+
+/// A picture in memory, drawn in software. The playfield is stretched over
+/// the whole image, and a pixel is covered by a shape when its centre is.
+pub struct Rgba {
+    pub width: usize,
+    pub height: usize,
+    /// Red, green, blue and alpha, row by row from the top.
+    pub pixels: Vec<u8>,
+}
+
+impl Rgba {
+    /// An image filled with `background`.
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&[background[0], background[1], background[2], 255]);
+        }
+        Rgba {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let at = (y * self.width + x) * 4;
+        [self.pixels[at], self.pixels[at + 1], self.pixels[at + 2]]
+    }
+
+    // Colours every pixel in the box from `left`, `top` to `right`, `bottom`
+    // whose centre `inside` accepts. Both take playfield pixels.
+    fn fill(
+        &mut self,
+        (left, top, right, bottom): (f32, f32, f32, f32),
+        color: Rgb,
+        inside: impl Fn(f32, f32) -> bool,
+    ) {
+        let scale_x = self.width as f32 / VIRTUAL_WIDTH;
+        let scale_y = self.height as f32 / VIRTUAL_HEIGHT;
+        let columns = |from: f32, to: f32, scale: f32, limit: usize| {
+            let first = (from * scale).floor().max(0.0) as usize;
+            let last = ((to * scale).ceil().max(0.0) as usize).min(limit);
+            first..last
+        };
+        for py in columns(top, bottom, scale_y, self.height) {
+            let y = (py as f32 + 0.5) / scale_y;
+            for px in columns(left, right, scale_x, self.width) {
+                let x = (px as f32 + 0.5) / scale_x;
+                if inside(x, y) {
+                    let at = (py * self.width + px) * 4;
+                    self.pixels[at..at + 3].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for Rgba {
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgb) {
+        self.fill((x, y, x + width, y + height), color, |px, py| {
+            px >= x && px < x + width && py >= y && py < y + height
+        });
+    }
+
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Rgb) {
+        let bounds = (x - radius, y - radius, x + radius, y + radius);
+        self.fill(bounds, color, |px, py| {
+            (px - x).powi(2) + (py - y).powi(2) <= radius * radius
+        });
+    }
+
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Rgb) {
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            return;
+        }
+        let half = thickness / 2.0;
+        let bounds = (
+            x1.min(x2) - half,
+            y1.min(y2) - half,
+            x1.max(x2) + half,
+            y1.max(y2) + half,
+        );
+        self.fill(bounds, color, |px, py| {
+            // How far along the segment, and how far to one side of it.
+            let along = ((px - x1) * dx + (py - y1) * dy) / length;
+            let across = ((px - x1) * dy - (py - y1) * dx) / length;
+            (0.0..=length).contains(&along) && (-half..half).contains(&across)
+        });
+    }
+
+    fn arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        thickness: f32,
+        start: f32,
+        sweep: f32,
+        color: Rgb,
+    ) {
+        let inner = radius - thickness / 2.0;
+        let outer = radius + thickness / 2.0;
+        let bounds = (x - outer, y - outer, x + outer, y + outer);
+        self.fill(bounds, color, |px, py| {
+            let distance = (px - x).hypot(py - y);
+            let angle = (py - y).atan2(px - x);
+            let turned = (angle - start).rem_euclid(2.0 * PI);
+            (inner..=outer).contains(&distance) && turned <= sweep
+        });
+    }
+}
+
+/// The sun at the top of the playfield, smiling or, once hit, shocked.
+pub fn paint_sun(r: &mut impl Renderer, scheme: &Scheme, mouth_shocked: bool) {
+    let x = VIRTUAL_WIDTH / 2.0;
+    let y = 25.0;
+
+    r.rect(x - 22.0, y - 18.0, 44.0, 36.0, scheme.sky);
+    r.circle(x, y, 12.0, scheme.sun);
+
+    r.line(x - 20.0, y, x + 20.0, y, 2.0, scheme.sun);
+    r.line(x, y - 15.0, x, y + 15.0, 2.0, scheme.sun);
+    r.line(x - 15.0, y - 10.0, x + 15.0, y + 10.0, 2.0, scheme.sun);
+    r.line(x - 15.0, y + 10.0, x + 15.0, y - 10.0, 2.0, scheme.sun);
+    r.line(x - 8.0, y - 13.0, x + 8.0, y + 13.0, 2.0, scheme.sun);
+    r.line(x - 8.0, y + 13.0, x + 8.0, y - 13.0, 2.0, scheme.sun);
+    r.line(x - 18.0, y - 5.0, x + 18.0, y + 5.0, 2.0, scheme.sun);
+    r.line(x - 18.0, y + 5.0, x + 18.0, y - 5.0, 2.0, scheme.sun);
+
+    if mouth_shocked {
+        r.circle(x, y + 5.0, 3.0, BLACK);
+    } else {
+        // QBasic CIRCLE arc from 210 to 330 degrees draws a smile
+        // In screen coords (Y down), we need to draw from 30 to 150 degrees
+        let start_angle = 30.0 * PI / 180.0;
+        let end_angle = 150.0 * PI / 180.0;
+        let steps = 20;
+        for i in 0..steps {
+            let a1 = start_angle + (end_angle - start_angle) * (i as f32 / steps as f32);
+            let a2 = start_angle + (end_angle - start_angle) * ((i + 1) as f32 / steps as f32);
+            let x1 = x + 8.0 * a1.cos();
+            let y1 = y + 8.0 * a1.sin();
+            let x2 = x + 8.0 * a2.cos();
+            let y2 = y + 8.0 * a2.sin();
+            r.line(x1, y1, x2, y2, 1.0, BLACK);
+        }
+    }
+
+    r.circle(x - 3.0, y - 2.0, 1.5, BLACK);
+    r.circle(x + 3.0, y - 2.0, 1.5, BLACK);
+}
+
+/// A gorilla standing at `x`, `y` with its arms as `RIGHT_UP`, `LEFT_UP`
+/// or `ARMS_DOWN` say.
+pub fn paint_gorilla(r: &mut impl Renderer, scheme: &Scheme, x: f32, y: f32, arms: i32) {
+    r.rect(x - 4.0, y, 7.0, 6.0, scheme.object);
+    r.rect(x - 5.0, y + 2.0, 9.0, 2.0, scheme.object);
+    r.line(x - 3.0, y + 2.0, x + 2.0, y + 2.0, 1.0, BLACK);
+    r.circle(x - 1.5, y + 4.0, 0.8, BLACK);
+    r.circle(x + 1.5, y + 4.0, 0.8, BLACK);
+    r.line(x - 3.0, y + 7.0, x + 2.0, y + 7.0, 1.0, scheme.object);
+    r.rect(x - 8.0, y + 8.0, 15.0, 6.0, scheme.object);
+    r.rect(x - 6.0, y + 15.0, 11.0, 5.0, scheme.object);
+
+    for i in 0..5 {
+        let offset = i as f32;
+        r.arc(
+            x + offset,
+            y + 25.0,
+            10.0,
+            1.0,
+            3.0 * PI / 4.0,
+            9.0 * PI / 8.0 - 3.0 * PI / 4.0,
+            scheme.object,
+        );
+        r.arc(
+            x - 6.0 + offset,
+            y + 25.0,
+            10.0,
+            1.0,
+            15.0 * PI / 8.0,
+            2.0 * PI + PI / 4.0 - 15.0 * PI / 8.0,
+            scheme.object,
+        );
+    }
+
+    r.arc(
+        x - 5.0,
+        y + 10.0,
+        5.0,
+        1.0,
+        3.0 * PI / 2.0,
+        2.0 * PI - 3.0 * PI / 2.0,
+        BLACK,
+    );
+    r.arc(x + 5.0, y + 10.0, 5.0, 1.0, PI, 3.0 * PI / 2.0 - PI, BLACK);
+
+    for i in -5..0 {
+        let offset = i as f32;
+        match arms {
+            1 => {
+                r.arc(
+                    x + offset,
+                    y + 14.0,
+                    9.0,
+                    1.0,
+                    3.0 * PI / 4.0,
+                    5.0 * PI / 4.0 - 3.0 * PI / 4.0,
+                    scheme.object,
+                );
+                r.arc(
+                    x + 5.0 + offset,
+                    y + 4.0,
+                    9.0,
+                    1.0,
+                    7.0 * PI / 4.0,
+                    2.0 * PI + PI / 4.0 - 7.0 * PI / 4.0,
+                    scheme.object,
+                );
+            }
+            2 => {
+                r.arc(
+                    x + offset,
+                    y + 4.0,
+                    9.0,
+                    1.0,
+                    3.0 * PI / 4.0,
+                    5.0 * PI / 4.0 - 3.0 * PI / 4.0,
+                    scheme.object,
+                );
+                r.arc(
+                    x + 5.0 + offset,
+                    y + 14.0,
+                    9.0,
+                    1.0,
+                    7.0 * PI / 4.0,
+                    2.0 * PI + PI / 4.0 - 7.0 * PI / 4.0,
+                    scheme.object,
+                );
+            }
+            _ => {
+                r.arc(
+                    x + offset,
+                    y + 14.0,
+                    9.0,
+                    1.0,
+                    3.0 * PI / 4.0,
+                    5.0 * PI / 4.0 - 3.0 * PI / 4.0,
+                    scheme.object,
+                );
+                r.arc(
+                    x + 5.0 + offset,
+                    y + 14.0,
+                    9.0,
+                    1.0,
+                    7.0 * PI / 4.0,
+                    2.0 * PI + PI / 4.0 - 7.0 * PI / 4.0,
+                    scheme.object,
+                );
+            }
+        }
+    }
+}
+
+/// The banana at `xc`, `yc`, in the `rotation`th of its four turns.
+pub fn paint_banana(r: &mut impl Renderer, scheme: &Scheme, xc: f32, yc: f32, rotation: i32) {
+    match rotation % 4 {
+        0 => r.arc(xc + 4.0, yc + 3.0, 4.0, 3.0, PI / 2.0, PI, scheme.banana),
+        1 => r.arc(xc + 3.0, yc, 4.0, 3.0, 0.0, PI, scheme.banana),
+        2 => r.arc(xc + 3.0, yc + 6.0, 4.0, 3.0, PI, PI, scheme.banana),
+        3 => r.arc(xc, yc + 3.0, 4.0, 3.0, 3.0 * PI / 2.0, PI, scheme.banana),
+        _ => {}
+    }
+}
+
+/// The buildings with their windows and craters, and the wind arrow.
+pub fn paint_cityscape(r: &mut impl Renderer, scheme: &Scheme, state: &GameState) {
+    for building in &state.buildings {
+        r.rect(
+            building.x - 1.0,
+            building.y - 1.0,
+            building.width + 2.0,
+            building.height + 2.0,
+            scheme.sky,
+        );
+        r.rect(
+            building.x,
+            building.y,
+            building.width,
+            building.height,
+            scheme.buildings[building.color],
+        );
+        for window in &building.windows {
+            r.rect(
+                window.x,
+                window.y,
+                window.width,
+                window.height,
+                if window.lit {
+                    scheme.window_lit
+                } else {
+                    scheme.window_dark
+                },
+            );
+        }
+        for crater in &building.craters {
+            r.circle(crater.x, crater.y, crater.radius, scheme.sky);
+        }
+    }
+
+    if state.wind != 0 {
+        let wind_line = (state.wind * 3 * 2) as f32;
+        r.line(
+            VIRTUAL_WIDTH / 2.0,
+            VIRTUAL_HEIGHT - 5.0,
+            VIRTUAL_WIDTH / 2.0 + wind_line,
+            VIRTUAL_HEIGHT - 5.0,
+            2.0,
+            scheme.explosion,
+        );
+        let arrow_dir: f32 = if state.wind > 0 { -4.0 } else { 4.0 };
+        r.line(
+            VIRTUAL_WIDTH / 2.0 + wind_line,
+            VIRTUAL_HEIGHT - 5.0,
+            VIRTUAL_WIDTH / 2.0 + wind_line + arrow_dir,
+            VIRTUAL_HEIGHT - 7.0,
+            2.0,
+            scheme.explosion,
+        );
+        r.line(
+            VIRTUAL_WIDTH / 2.0 + wind_line,
+            VIRTUAL_HEIGHT - 5.0,
+            VIRTUAL_WIDTH / 2.0 + wind_line + arrow_dir,
+            VIRTUAL_HEIGHT - 3.0,
+            2.0,
+            scheme.explosion,
+        );
+    }
+}
+
+/// A banana's explosion at its fullest.
+pub fn paint_explosion(r: &mut impl Renderer, scheme: &Scheme, x: f32, y: f32) {
+    let mut c = 0.0;
+    while c <= EXPLOSION_RADIUS {
+        r.circle(x, y, c, scheme.explosion);
+        c += 0.5;
+    }
+}
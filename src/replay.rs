@@ -0,0 +1,276 @@
+//! Match recordings in the `.gorreplay` format.
+//!
+//! A match is fully determined by its map seed, the setup-screen settings and
+//! the throws the players made, so that is all a replay stores. Playing one
+//! back regenerates the same cities and flies the same bananas.
+//!
+//! The format is line-based text so it survives copy and paste:
+//!
+//! ```text
+//! GORREPLAY 2
+//! seed 1234567
+//! player1 Alice
+//! player2 Bob
+//! games 3
+//! gravity 9.8
+//! shot 45 60
+//! shot 50 62.5
+//! ```
+//!
+//! Matches with more gorillas go on with `player3` and up, and team matches
+//! add `teams 0 0 1 1` (each player's team, in order) and `friendlyfire on`
+//! or `off`. Matches played with the fairness check also carry a
+//! `fairness 0.5` line, since the check changes which maps get dealt, and
+//! matches in the CGA's building sizes carry `city cga`. Shots are stored as
+//! typed, before left-facing players' angles are mirrored.
+//!
+//! Version 1 had two players and none of the optional lines; it still loads.
+
+use crate::{CitySizing, FriendlyFire, GameState, MAX_PLAYERS, MIN_PLAYERS};
+use std::fmt;
+use std::path::Path;
+
+pub const REPLAY_EXTENSION: &str = "gorreplay";
+pub const REPLAY_VERSION: u32 = 2;
+
+const MAGIC: &str = "GORREPLAY";
+const OLDEST_VERSION: u32 = 1;
+const PLAYER_FIELDS: [&str; MAX_PLAYERS] = [
+    "player1", "player2", "player3", "player4", "player5", "player6",
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RecordedShot {
+    pub angle: f32,
+    pub velocity: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u32,
+    pub player_names: Vec<String>,
+    /// Each player's team, or empty for a free-for-all.
+    pub teams: Vec<usize>,
+    pub friendly_fire: FriendlyFire,
+    pub num_games: i32,
+    pub gravity: f32,
+    pub min_fairness: Option<f32>,
+    pub city: CitySizing,
+    pub shots: Vec<RecordedShot>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    NotAReplay,
+    UnsupportedVersion(u32),
+    Malformed { line: usize, reason: String },
+    MissingField(&'static str),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "could not read replay: {}", err),
+            ReplayError::NotAReplay => write!(f, "not a {} file", MAGIC),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {} is not supported (expected {} to {})",
+                version, OLDEST_VERSION, REPLAY_VERSION
+            ),
+            ReplayError::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+            ReplayError::MissingField(field) => write!(f, "replay has no '{}' line", field),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl Replay {
+    /// Starts a recording of a match set up like `state`.
+    pub fn new(state: &GameState) -> Self {
+        Replay {
+            seed: state.seed,
+            player_names: state.player_names.clone(),
+            teams: if state.is_team_match() {
+                state.teams.clone()
+            } else {
+                Vec::new()
+            },
+            friendly_fire: state.friendly_fire,
+            num_games: state.num_games,
+            gravity: state.gravity,
+            min_fairness: state.min_fairness,
+            city: state.city,
+            shots: Vec::new(),
+        }
+    }
+
+    pub fn push_shot(&mut self, angle: f32, velocity: f32) {
+        self.shots.push(RecordedShot { angle, velocity });
+    }
+
+    /// Copies the recorded settings into `state`, ready for the first round.
+    pub fn apply_settings(&self, state: &mut GameState) {
+        state.seed = self.seed;
+        state.set_num_players(self.player_names.len());
+        state.player_names = self.player_names.clone();
+        if !self.teams.is_empty() {
+            state.set_teams(&self.teams);
+        }
+        state.friendly_fire = self.friendly_fire;
+        state.num_games = self.num_games;
+        state.gravity = self.gravity;
+        state.min_fairness = self.min_fairness;
+        state.city = self.city;
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        Replay::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, ReplayError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+
+        let (_, header) = lines.next().ok_or(ReplayError::NotAReplay)?;
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version.parse().map_err(|_| ReplayError::NotAReplay)?,
+            _ => return Err(ReplayError::NotAReplay),
+        };
+        if !(OLDEST_VERSION..=REPLAY_VERSION).contains(&version) {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let mut seed = None;
+        let mut player_names: Vec<Option<String>> = Vec::new();
+        let mut num_games = None;
+        let mut gravity = None;
+        let mut min_fairness = None;
+        let mut city = CitySizing::default();
+        let mut teams = Vec::new();
+        let mut friendly_fire = FriendlyFire::default();
+        let mut shots = Vec::new();
+
+        for (line, content) in lines {
+            if content.is_empty() {
+                continue;
+            }
+            let (key, value) = content.split_once(' ').unwrap_or((content, ""));
+            let malformed = |reason: &str| ReplayError::Malformed {
+                line,
+                reason: reason.to_string(),
+            };
+            match key {
+                "seed" => seed = Some(value.parse().map_err(|_| malformed("bad seed"))?),
+                _ if PLAYER_FIELDS.contains(&key) => {
+                    let index = PLAYER_FIELDS
+                        .iter()
+                        .position(|&field| field == key)
+                        .unwrap();
+                    if player_names.len() <= index {
+                        player_names.resize(index + 1, None);
+                    }
+                    player_names[index] = Some(value.to_string());
+                }
+                "games" => num_games = Some(value.parse().map_err(|_| malformed("bad games"))?),
+                "gravity" => gravity = Some(value.parse().map_err(|_| malformed("bad gravity"))?),
+                "teams" => {
+                    teams = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| malformed("bad teams"))?
+                }
+                "friendlyfire" => {
+                    friendly_fire = match value {
+                        "on" => FriendlyFire::Penalty,
+                        "off" => FriendlyFire::Off,
+                        _ => return Err(malformed("expected 'friendlyfire on' or 'off'")),
+                    }
+                }
+                "fairness" => {
+                    min_fairness = Some(value.parse().map_err(|_| malformed("bad fairness"))?)
+                }
+                "city" => {
+                    city = CitySizing::from_name(value)
+                        .ok_or_else(|| malformed("expected 'city ega' or 'cga'"))?
+                }
+                "shot" => {
+                    let mut parts = value.split_whitespace().map(str::parse::<f32>);
+                    match (parts.next(), parts.next(), parts.next()) {
+                        (Some(Ok(angle)), Some(Ok(velocity)), None) => {
+                            shots.push(RecordedShot { angle, velocity })
+                        }
+                        _ => return Err(malformed("expected 'shot <angle> <velocity>'")),
+                    }
+                }
+                _ => return Err(malformed(&format!("unknown entry '{}'", key))),
+            }
+        }
+
+        if player_names.len() < MIN_PLAYERS {
+            player_names.resize(MIN_PLAYERS, None);
+        }
+        let player_names = player_names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| name.ok_or(ReplayError::MissingField(PLAYER_FIELDS[i])))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Replay {
+            seed: seed.ok_or(ReplayError::MissingField("seed"))?,
+            player_names,
+            teams,
+            friendly_fire,
+            num_games: num_games.ok_or(ReplayError::MissingField("games"))?,
+            gravity: gravity.ok_or(ReplayError::MissingField("gravity"))?,
+            min_fairness,
+            city,
+            shots,
+        })
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", MAGIC, REPLAY_VERSION)?;
+        writeln!(f, "seed {}", self.seed)?;
+        for (field, name) in PLAYER_FIELDS.iter().zip(&self.player_names) {
+            writeln!(f, "{} {}", field, name)?;
+        }
+        if !self.teams.is_empty() {
+            let teams: Vec<String> = self.teams.iter().map(usize::to_string).collect();
+            writeln!(f, "teams {}", teams.join(" "))?;
+            let friendly_fire = match self.friendly_fire {
+                FriendlyFire::Penalty => "on",
+                FriendlyFire::Off => "off",
+            };
+            writeln!(f, "friendlyfire {}", friendly_fire)?;
+        }
+        writeln!(f, "games {}", self.num_games)?;
+        writeln!(f, "gravity {}", self.gravity)?;
+        if let Some(min_fairness) = self.min_fairness {
+            writeln!(f, "fairness {}", min_fairness)?;
+        }
+        if self.city != CitySizing::Ega {
+            writeln!(f, "city {}", self.city.name())?;
+        }
+        for shot in &self.shots {
+            writeln!(f, "shot {} {}", shot.angle, shot.velocity)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+//! Seedable random number generator for everything the simulation rolls.
+//!
+//! The original seeded QBasic's generator from `TIMER`, so a map could never
+//! be seen twice. Here the city, the wind and the gorilla positions all come
+//! from one `Rng` owned by `GameState`, and a map seed reproduces them
+//! exactly on every platform.
+
+/// SplitMix64. Tiny, fast and good enough for dice rolls.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// The original `FnRan`: a random integer from 1 to `x` inclusive.
+    pub fn fn_ran(&mut self, x: i32) -> i32 {
+        1 + (self.next_u64() % x.max(1) as u64) as i32
+    }
+}
@@ -0,0 +1,151 @@
+//! A test harness for the window's screens, from the intro to "Play
+//! Again?", without the window.
+//!
+//! `Session` walks the setup screen, the match and "Play Again?" the way the
+//! front-end does and reads its input the same way, through an
+//! `InputSource` once a frame. The setup questions and the angle and
+//! velocity prompts are handled by the same code, `Setup` and `type_throw`.
+//! The phase transitions are not: they are a copy of the ones in `main.rs`,
+//! which has its own loop, and are not kept in sync with it. A change to
+//! when the window moves from one screen to the next has to be made in both.
+//!
+//! Nothing is drawn or animated: bananas land the frame they are thrown,
+//! computer players throw without typing and `V` starts the match without
+//! the gorillas' dance. Networked, play-by-file and bot matches, recordings
+//! and replay playback are left out. Driven by a `Script`, it plays whole
+//! sessions in tests.
+
+use crate::ai::AiPlayer;
+use crate::game::Match;
+use crate::input::{type_throw, InputSource, Key};
+use crate::replay::Replay;
+use crate::setup::Setup;
+use crate::{GameState, ShotState};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Phase {
+    Intro,
+    GetInputs,
+    /// "Your Choice?": `V` or `P` starts the match.
+    GorillaIntro,
+    Playing,
+    GameOver,
+    PlayAgain,
+    /// Escape was pressed, or `N` to playing again.
+    Quit,
+}
+
+pub struct Session {
+    pub phase: Phase,
+    /// The match being set up.
+    pub state: GameState,
+    pub setup: Setup,
+    /// The match being played, or the last one once it is over.
+    pub game: Option<Match>,
+    /// Settings and throws of `game`, as they would be recorded.
+    pub replay: Option<Replay>,
+    pub shot_state: ShotState,
+    /// Matches played to the end.
+    pub matches: usize,
+    ai_players: Vec<Option<AiPlayer>>,
+    // Each new setup starts from this.
+    blank: GameState,
+}
+
+impl Session {
+    /// Starts at the intro. Every match is set up from `state`, and players
+    /// who leave the map seed blank get `fallback_seed`.
+    pub fn new(state: GameState, fallback_seed: u32) -> Self {
+        Session {
+            phase: Phase::Intro,
+            setup: Setup::new(&state, fallback_seed),
+            state: state.clone(),
+            game: None,
+            replay: None,
+            shot_state: ShotState::new(),
+            matches: 0,
+            ai_players: Vec::new(),
+            blank: state,
+        }
+    }
+
+    /// Plays one frame of input.
+    pub fn update(&mut self, input: &mut dyn InputSource) {
+        if input.is_key_pressed(Key::Escape) {
+            self.phase = Phase::Quit;
+        }
+
+        match self.phase {
+            Phase::Intro => {
+                if input.any_pressed() {
+                    self.phase = Phase::GetInputs;
+                }
+            }
+            Phase::GetInputs => {
+                if self.setup.update(input, &mut self.state) {
+                    self.phase = Phase::GorillaIntro;
+                }
+            }
+            Phase::GorillaIntro => {
+                if matches!(input.last_key_pressed(), Some(Key::Char('V' | 'P'))) {
+                    self.start_match();
+                }
+            }
+            Phase::Playing => self.play(input),
+            Phase::GameOver => {
+                if input.any_pressed() {
+                    self.phase = Phase::PlayAgain;
+                }
+            }
+            Phase::PlayAgain => match input.last_key_pressed() {
+                Some(Key::Char('Y')) => {
+                    self.state = self.blank.clone();
+                    self.setup = Setup::new(&self.state, self.setup.fallback_seed);
+                    self.phase = Phase::GetInputs;
+                }
+                Some(Key::Char('N')) => self.phase = Phase::Quit,
+                _ => {}
+            },
+            Phase::Quit => {}
+        }
+    }
+
+    fn start_match(&mut self) {
+        self.ai_players = self.setup.computer_players(self.state.seed);
+        self.replay = Some(Replay::new(&self.state));
+        self.game = Some(Match::new(self.state.clone()));
+        self.shot_state = ShotState::new();
+        self.phase = Phase::Playing;
+    }
+
+    // Takes the next throw, typed or chosen by the computer.
+    fn play(&mut self, input: &mut dyn InputSource) {
+        let Some(game) = &mut self.game else {
+            return;
+        };
+        game.deal();
+        let thrower = game.current_player;
+        let throw = match self.ai_players.get_mut(thrower) {
+            Some(Some(ai)) => Some(ai.choose_throw(&game.state, thrower)),
+            _ => type_throw(&mut self.shot_state, input)
+                .map(|velocity| (self.shot_state.angle, velocity)),
+        };
+        let Some((angle, velocity)) = throw else {
+            return;
+        };
+
+        if let Some((shot_state, _)) = game.throw(angle, velocity) {
+            if let Some(Some(ai)) = self.ai_players.get_mut(thrower) {
+                ai.observe(&game.state, thrower, &shot_state);
+            }
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.push_shot(angle, velocity);
+        }
+        self.shot_state = ShotState::new();
+        if game.is_over() {
+            self.matches += 1;
+            self.phase = Phase::GameOver;
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! The setup screen's questions, from the number of gorillas to the game
+//! speed.
+//!
+//! `Setup` reads the answers from an `InputSource` a frame at a time and
+//! writes each into the `GameState` being set up as it is confirmed. A blank
+//! or unusable answer gets the default its prompt offers.
+
+use crate::ai::{AiPlayer, Difficulty};
+use crate::fairness::DEFAULT_MIN_FAIRNESS;
+use crate::input::{InputSource, Key};
+use crate::{FriendlyFire, GameState, MIN_PLAYERS};
+
+/// Longest answer that can be typed; names keep the first ten characters.
+const MAX_ANSWER_LEN: usize = 20;
+const MAX_NAME_LEN: usize = 10;
+
+/// What the player typed on the setup screen, shown once a field is
+/// confirmed.
+#[derive(Default, Clone, Debug)]
+pub struct Answers {
+    pub players: String,
+    pub team_play: String,
+    pub names: Vec<String>,
+    pub computers: Vec<String>,
+    pub teams: Vec<String>,
+    pub friendly_fire: String,
+    pub games: String,
+    pub gravity: String,
+    pub seed: String,
+    pub fair_maps: String,
+    pub speed: String,
+}
+
+/// The questions in the order they are asked. Team questions are only
+/// asked of team matches.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Field {
+    NumPlayers,
+    TeamPlay,
+    Name(usize),
+    Computer(usize),
+    Team(usize),
+    FriendlyFire,
+    NumGames,
+    Gravity,
+    Seed,
+    FairMaps,
+    GameSpeed,
+    /// Every question is answered.
+    Done,
+}
+
+pub struct Setup {
+    pub field: Field,
+    /// What has been typed for `field` so far.
+    pub buffer: String,
+    pub answers: Answers,
+    /// Each player's computer skill, or `None` for a human.
+    pub computer: Vec<Option<Difficulty>>,
+    pub team_play: bool,
+    team_choice: Vec<usize>,
+    /// Game speed for the `SimClock`.
+    pub speed: f64,
+    /// The map seed if the player leaves it blank.
+    pub fallback_seed: u32,
+}
+
+impl Setup {
+    /// Starts asking the questions for a match set up like `state`.
+    pub fn new(state: &GameState, fallback_seed: u32) -> Self {
+        Setup {
+            field: Field::NumPlayers,
+            buffer: String::new(),
+            answers: Answers::default(),
+            computer: vec![None; state.num_players()],
+            team_play: false,
+            team_choice: Vec::new(),
+            speed: 1.0,
+            fallback_seed,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.field == Field::Done
+    }
+
+    /// Reads this frame's keys and characters into the current answer.
+    /// Returns whether that confirmed the last one.
+    pub fn update(&mut self, input: &mut dyn InputSource, state: &mut GameState) -> bool {
+        match input.last_key_pressed() {
+            Some(Key::Enter) => self.confirm(state),
+            Some(Key::Backspace) => {
+                self.buffer.pop();
+            }
+            _ => {}
+        }
+
+        while let Some(c) = input.char_pressed() {
+            if (c.is_alphanumeric() || c == '.' || c == ' ') && self.buffer.len() < MAX_ANSWER_LEN {
+                self.buffer.push(c);
+            }
+        }
+        self.is_done()
+    }
+
+    /// Computer players for the seats given to the computer, seeded from
+    /// the map seed.
+    pub fn computer_players(&self, seed: u32) -> Vec<Option<AiPlayer>> {
+        self.computer
+            .iter()
+            .enumerate()
+            .map(|(i, skill)| {
+                skill.map(|difficulty| AiPlayer::new(difficulty, seed as u64 + i as u64))
+            })
+            .collect()
+    }
+
+    // Takes the typed answer for the current field and moves on to the next.
+    fn confirm(&mut self, state: &mut GameState) {
+        let answer = std::mem::take(&mut self.buffer);
+        self.field = match self.field {
+            Field::NumPlayers => {
+                let count = answer.trim().parse().unwrap_or(MIN_PLAYERS);
+                state.set_num_players(count);
+                self.answers.players = state.num_players().to_string();
+                self.computer = vec![None; state.num_players()];
+                self.team_play = false;
+                self.team_choice.clear();
+                if offers_teams(state.num_players()) {
+                    Field::TeamPlay
+                } else {
+                    Field::Name(0)
+                }
+            }
+            Field::TeamPlay => {
+                self.team_play = answer.trim().eq_ignore_ascii_case("y");
+                self.answers.team_play = if self.team_play { "Yes" } else { "No" }.to_string();
+                Field::Name(0)
+            }
+            Field::Name(i) => {
+                let name = if answer.is_empty() {
+                    format!("Player {}", i + 1)
+                } else {
+                    answer.chars().take(MAX_NAME_LEN).collect()
+                };
+                state.player_names[i] = name.clone();
+                self.answers.names.push(name);
+                Field::Computer(i)
+            }
+            Field::Computer(i) => {
+                self.computer[i] = parse_computer(&answer);
+                self.answers
+                    .computers
+                    .push(computer_label(self.computer[i]));
+                if self.team_play {
+                    Field::Team(i)
+                } else if i + 1 < state.num_players() {
+                    Field::Name(i + 1)
+                } else {
+                    Field::NumGames
+                }
+            }
+            Field::Team(i) => {
+                let count = state.num_players();
+                // Default to the left half against the right.
+                let team = match answer.trim().to_ascii_uppercase().as_str() {
+                    "A" | "1" => 0,
+                    "B" | "2" => 1,
+                    _ => usize::from(i >= count / 2),
+                };
+                self.team_choice.push(team);
+                self.answers.teams.push(team_letter(team).to_string());
+                if i + 1 < count {
+                    Field::Name(i + 1)
+                } else {
+                    // Nobody on one side: fall back to the default split.
+                    if !(self.team_choice.contains(&0) && self.team_choice.contains(&1)) {
+                        self.team_choice =
+                            (0..count).map(|p| usize::from(p >= count / 2)).collect();
+                        self.answers.teams = self
+                            .team_choice
+                            .iter()
+                            .map(|&team| team_letter(team).to_string())
+                            .collect();
+                    }
+                    state.set_teams(&self.team_choice);
+                    Field::NumGames
+                }
+            }
+            Field::NumGames => {
+                let num: i32 = answer.parse().unwrap_or(3);
+                state.num_games = if num > 0 { num } else { 3 };
+                self.answers.games = answer;
+                if self.team_play {
+                    Field::FriendlyFire
+                } else {
+                    Field::Gravity
+                }
+            }
+            Field::FriendlyFire => {
+                let off = answer.trim().eq_ignore_ascii_case("n");
+                state.friendly_fire = if off {
+                    FriendlyFire::Off
+                } else {
+                    FriendlyFire::Penalty
+                };
+                self.answers.friendly_fire = if off { "Off" } else { "On" }.to_string();
+                Field::Gravity
+            }
+            Field::Gravity => {
+                let grav: f32 = answer.parse().unwrap_or(9.8);
+                state.gravity = if grav > 0.0 { grav } else { 9.8 };
+                self.answers.gravity = answer;
+                Field::Seed
+            }
+            Field::Seed => {
+                state.seed = answer.parse().unwrap_or(self.fallback_seed);
+                self.answers.seed = state.seed.to_string();
+                Field::FairMaps
+            }
+            Field::FairMaps => {
+                let fair = answer.trim().eq_ignore_ascii_case("y");
+                state.min_fairness = fair.then_some(DEFAULT_MIN_FAIRNESS);
+                self.answers.fair_maps = if fair { "Yes" } else { "No" }.to_string();
+                Field::GameSpeed
+            }
+            Field::GameSpeed => {
+                let speed: f64 = answer.parse().unwrap_or(1.0);
+                self.speed = if speed > 0.0 { speed } else { 1.0 };
+                self.answers.speed = answer;
+                Field::Done
+            }
+            Field::Done => Field::Done,
+        };
+    }
+}
+
+/// Team play needs an even split, so it's only offered for 2v2 and 3v3.
+pub fn offers_teams(num_players: usize) -> bool {
+    num_players == 4 || num_players == 6
+}
+
+pub fn team_letter(team: usize) -> char {
+    (b'A' + team as u8) as char
+}
+
+pub fn parse_computer(input: &str) -> Option<Difficulty> {
+    input.trim().parse().ok().and_then(Difficulty::from_level)
+}
+
+pub fn computer_label(difficulty: Option<Difficulty>) -> String {
+    difficulty.map_or("Human", Difficulty::name).to_string()
+}
@@ -0,0 +1,155 @@
+//! Sound effects synthesized from noise and sine waves rather than loaded
+//! from files.
+//!
+//! Each function returns mono samples at `mml::SAMPLE_RATE`, between -1.0
+//! and 1.0. The same arguments always give the same sound.
+
+use crate::mml::{wav, SAMPLE_RATE};
+use crate::Rng;
+use std::f32::consts::{PI, TAU};
+
+/// Throws at or above this velocity whoosh at the highest pitch.
+pub const WHOOSH_TOP_VELOCITY: f32 = 150.0;
+
+/// Length of the whoosh, which is made to be looped for as long as the
+/// banana flies.
+pub const WHOOSH_SECS: f32 = 0.5;
+
+fn sample_count(secs: f32) -> usize {
+    (secs * SAMPLE_RATE as f32).round() as usize
+}
+
+// White noise from -1.0 to 1.0.
+fn noise(rng: &mut Rng) -> f32 {
+    rng.next_f32() * 2.0 - 1.0
+}
+
+// A Chamberlin state-variable filter, good enough for shaping noise.
+struct Filter {
+    low: f32,
+    band: f32,
+}
+
+impl Filter {
+    fn new() -> Self {
+        Filter {
+            low: 0.0,
+            band: 0.0,
+        }
+    }
+
+    // Returns the low-pass and band-pass outputs for `cutoff` in hertz and a
+    // damping from 0.0 (ringing) to 2.0 (none).
+    fn step(&mut self, input: f32, cutoff: f32, damping: f32) -> (f32, f32) {
+        let f = 2.0 * (PI * cutoff.min(SAMPLE_RATE as f32 / 6.0) / SAMPLE_RATE as f32).sin();
+        self.low += f * self.band;
+        let high = input - self.low - damping * self.band;
+        self.band += f * high;
+        (self.low, self.band)
+    }
+}
+
+// Scales the samples so the loudest is at `peak`.
+fn normalize(samples: &mut [f32], peak: f32) {
+    let loudest = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if loudest > 0.0 {
+        for sample in samples {
+            *sample *= peak / loudest;
+        }
+    }
+}
+
+/// Air rushing past a spinning banana. Faster throws whoosh higher and the
+/// banana spins quicker. The sound loops without a seam.
+pub fn whoosh(velocity: f32) -> Vec<f32> {
+    let speed = (velocity / WHOOSH_TOP_VELOCITY).clamp(0.0, 1.0);
+    let pitch = 250.0 + 1250.0 * speed;
+    // A whole number of turns per loop, so the seam falls on a quiet spot.
+    let turns = (3.0 + 5.0 * speed).round();
+    let mut rng = Rng::new(0x5748_4f4f_5348);
+    let mut filter = Filter::new();
+
+    let count = sample_count(WHOOSH_SECS);
+    let mut samples: Vec<f32> = (0..count)
+        .map(|i| {
+            let phase = i as f32 / count as f32 * turns * PI;
+            let spin = 0.3 + 0.7 * phase.sin().powi(2);
+            let (_, band) = filter.step(noise(&mut rng), pitch, 0.35);
+            band * spin
+        })
+        .collect();
+    normalize(&mut samples, 0.6);
+    samples
+}
+
+/// A bang that grows longer, deeper and louder with `size`, from 0.0 for a
+/// glancing knock to 1.0 for the biggest blast.
+pub fn explosion(size: f32) -> Vec<f32> {
+    let size = size.clamp(0.0, 1.0);
+    let secs = 0.35 + 1.0 * size;
+    let decay = 0.08 + 0.3 * size;
+    let bright = 1500.0 + 2500.0 * size;
+    let mut rng = Rng::new(0x424f_4f4d);
+    let mut filter = Filter::new();
+    let mut thump_phase = 0.0;
+
+    let count = sample_count(secs);
+    let mut samples: Vec<f32> = (0..count)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let attack = (t / 0.005).min(1.0);
+            let envelope = attack * (-t / decay).exp();
+            let cutoff = 150.0 + bright * (-t / (decay * 0.7)).exp();
+            let (low, _) = filter.step(noise(&mut rng), cutoff, 1.2);
+            // The low boom under the crackle drops in pitch as it fades.
+            thump_phase += (40.0 + 50.0 * (-t / 0.1).exp()) / SAMPLE_RATE as f32;
+            let thump = (thump_phase * TAU).sin() * (-t / (decay * 1.5)).exp();
+            low * envelope + thump * (0.3 + 0.5 * size) * attack
+        })
+        .collect();
+    normalize(&mut samples, 0.5 + 0.4 * size);
+    samples
+}
+
+/// The sun saying "ouch": a quick yelp that slides down, with a wobble.
+pub fn ouch() -> Vec<f32> {
+    let secs = 0.45;
+    let count = sample_count(secs);
+    let mut phase = 0.0;
+    let mut samples: Vec<f32> = (0..count)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let progress = t / secs;
+            let wobble = 1.0 + 0.03 * (t * 7.0 * TAU).sin();
+            phase += (700.0 - 350.0 * progress) * wobble / SAMPLE_RATE as f32;
+            // A few harmonics make it sound more like a voice than a whistle.
+            let voice = (phase * TAU).sin()
+                + 0.5 * (phase * 2.0 * TAU).sin()
+                + 0.25 * (phase * 3.0 * TAU).sin();
+            let envelope = (t / 0.03).min(1.0) * (1.0 - progress).powf(0.7);
+            voice * envelope
+        })
+        .collect();
+    normalize(&mut samples, 0.5);
+    samples
+}
+
+/// A short tick for a key press.
+pub fn click() -> Vec<f32> {
+    (0..sample_count(0.015))
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            (t * 1800.0 * TAU).sin() * (-t / 0.003).exp() * 0.4
+        })
+        .collect()
+}
+
+/// Packs mono samples into a stereo WAV file, scaled by `left` and `right`
+/// for the two speakers.
+pub fn stereo_wav(samples: &[f32], left: f32, right: f32) -> Vec<u8> {
+    let interleaved: Vec<f32> = samples
+        .iter()
+        .flat_map(|&sample| [sample * left, sample * right])
+        .collect();
+    wav(&interleaved, 2, SAMPLE_RATE)
+}
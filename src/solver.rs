@@ -0,0 +1,220 @@
+//! Finds the throws that hit.
+//!
+//! For each angle, the throw that passes through the middle of the target
+//! follows in closed form from the trajectory `plot_shot` uses:
+//!
+//! ```text
+//! x(t) = x0 + v cos(a) t + wind / 10 * t^2
+//! y(t) = y0 - v sin(a) t + gravity / 2 * t^2
+//! ```
+//!
+//! Setting both to the target and eliminating `v` gives `t^2`, and from it
+//! `v`. That ignores buildings and the simulation's fixed steps, so every
+//! candidate is then flown through `plot_shot` itself. Only throws that reach
+//! the target's hitbox before anything else are reported, together with
+//! the range of velocities around them that also connect.
+
+use crate::{fly_shot, GameState, VIRTUAL_HEIGHT};
+use std::fmt;
+
+/// A throw that hits, as it would be typed into the prompts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Solution {
+    pub angle: f32,
+    pub velocity: f32,
+    /// Slowest and fastest throw at this angle that still hit.
+    pub min_velocity: f32,
+    pub max_velocity: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SolverOptions {
+    pub min_angle: f32,
+    pub max_angle: f32,
+    pub angle_step: f32,
+    /// Throws faster than this are not considered.
+    pub max_velocity: f32,
+    /// Resolution of the reported velocities.
+    pub velocity_step: f32,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        SolverOptions {
+            min_angle: 1.0,
+            max_angle: 89.0,
+            angle_step: 1.0,
+            max_velocity: 200.0,
+            velocity_step: 0.1,
+        }
+    }
+}
+
+/// No throw within the solver's bounds reaches the target.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NoSolution {
+    pub max_velocity: f32,
+}
+
+impl fmt::Display for NoSolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "no throw at up to velocity {} reaches the target",
+            self.max_velocity
+        )
+    }
+}
+
+impl std::error::Error for NoSolution {}
+
+/// Every hitting throw for `shooter` against the nearest other gorilla, with
+/// the default bounds.
+pub fn solve(state: &GameState, shooter: usize) -> Result<Vec<Solution>, NoSolution> {
+    let target = state.nearest_opponent(shooter);
+    solve_with(state, shooter, target, &SolverOptions::default())
+}
+
+pub fn solve_with(
+    state: &GameState,
+    shooter: usize,
+    target: usize,
+    options: &SolverOptions,
+) -> Result<Vec<Solution>, NoSolution> {
+    let mut search = Search::new(state, shooter, target, options);
+    while search.step() {}
+    search.finish()
+}
+
+/// `solve_with` one angle at a time, for callers that can't wait for all of
+/// them at once, like a game that has frames to draw.
+#[derive(Clone)]
+pub struct Search {
+    state: GameState,
+    sim: GameState,
+    shooter: usize,
+    target: usize,
+    options: SolverOptions,
+    step: i32,
+    steps: i32,
+    solutions: Vec<Solution>,
+}
+
+impl Search {
+    pub fn new(state: &GameState, shooter: usize, target: usize, options: &SolverOptions) -> Self {
+        Search {
+            state: state.clone(),
+            sim: state.clone(),
+            shooter,
+            target,
+            options: *options,
+            step: 0,
+            steps: ((options.max_angle - options.min_angle) / options.angle_step).floor() as i32,
+            solutions: Vec::new(),
+        }
+    }
+
+    /// Tries the next angle. False once every angle has been tried.
+    pub fn step(&mut self) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        let angle = self.options.min_angle + self.step as f32 * self.options.angle_step;
+        self.step += 1;
+        let (state, shooter, target) = (&self.state, self.shooter, self.target);
+        if let Some(guess) = aim_velocity(state, shooter, target, angle) {
+            let options = &self.options;
+            if let Some(solution) = confirm(&mut self.sim, shooter, target, angle, guess, options) {
+                self.solutions.push(solution);
+            }
+        }
+        true
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.step > self.steps
+    }
+
+    /// What the angles tried so far turned up.
+    pub fn finish(self) -> Result<Vec<Solution>, NoSolution> {
+        if self.solutions.is_empty() {
+            Err(NoSolution {
+                max_velocity: self.options.max_velocity,
+            })
+        } else {
+            Ok(self.solutions)
+        }
+    }
+}
+
+/// The velocity that carries a banana thrown at `angle` through the middle of
+/// the target's hitbox, if there is one.
+pub fn aim_velocity(state: &GameState, shooter: usize, target: usize, angle: f32) -> Option<f32> {
+    // Same launch point and mirroring as `plot_shot`.
+    let (start_x, angle) = if state.faces_left(shooter) {
+        (state.gorilla_x[shooter] + 25.0, 180.0 - angle)
+    } else {
+        (state.gorilla_x[shooter], angle)
+    };
+    let start_y = state.gorilla_y[shooter] - 7.0;
+    let dx = state.gorilla_x[target] + 10.0 - start_x;
+    // `plot_shot` scales the vertical motion to the screen height.
+    let dy = (start_y - (state.gorilla_y[target] + 12.5)) / (VIRTUAL_HEIGHT / 350.0);
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let wind = state.wind as f32 / 5.0;
+    let gravity = state.gravity;
+
+    // v cos(a) t = dx - wind/2 t^2 and v sin(a) t = dy + gravity/2 t^2
+    let t_squared = 2.0 * (sin * dx - cos * dy) / (cos * gravity + sin * wind);
+    if !t_squared.is_finite() || t_squared <= 0.0 {
+        return None;
+    }
+    let t = t_squared.sqrt();
+    let velocity = if cos.abs() > sin.abs() {
+        (dx - 0.5 * wind * t_squared) / (cos * t)
+    } else {
+        (dy + 0.5 * gravity * t_squared) / (sin * t)
+    };
+    (velocity.is_finite() && velocity > 0.0).then_some(velocity)
+}
+
+// Flies the analytic guess and the throws around it, and reports the band of
+// velocities that hit.
+fn confirm(
+    sim: &mut GameState,
+    shooter: usize,
+    target: usize,
+    angle: f32,
+    guess: f32,
+    options: &SolverOptions,
+) -> Option<Solution> {
+    let step = options.velocity_step;
+    let in_bounds = |v: f32| v > 0.0 && v <= options.max_velocity;
+    let mut hits =
+        |v: f32| in_bounds(v) && fly_shot(sim, shooter, angle, v).player_hit == Some(target);
+
+    let guess = (guess / step).round() * step;
+    // The middle of the target can be hidden behind a roof while its edges
+    // are not, so look a little either side before giving up.
+    let span = (guess * 0.1 / step).ceil() as i32;
+    let velocity = (0..=span)
+        .flat_map(|i| [guess + i as f32 * step, guess - i as f32 * step])
+        .find(|&v| hits(v))?;
+
+    let mut min_velocity = velocity;
+    while hits(min_velocity - step) {
+        min_velocity -= step;
+    }
+    let mut max_velocity = velocity;
+    while hits(max_velocity + step) {
+        max_velocity += step;
+    }
+
+    Some(Solution {
+        angle,
+        velocity,
+        min_velocity,
+        max_velocity,
+    })
+}
@@ -0,0 +1,91 @@
+//! Settings that survive between sessions.
+//!
+//! Each setting is a small text document under a name. Desktop builds keep
+//! them as files in the user's configuration directory (`~/.config/gorillas`
+//! on Linux, `~/Library/Application Support/gorillas` on macOS and
+//! `%APPDATA%\gorillas` on Windows). The browser build keeps them in the
+//! page's local storage, through the `gorillas_storage` plugin in
+//! `index.html`.
+
+use std::io;
+
+/// The document saved under `name`, if there is one.
+pub fn load(name: &str) -> Option<String> {
+    platform::load(name)
+}
+
+pub fn save(name: &str, text: &str) -> io::Result<()> {
+    platform::save(name, text)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::io;
+    use std::path::PathBuf;
+
+    fn directory() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        let base = if cfg!(windows) {
+            PathBuf::from(var("APPDATA")?)
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from(var("HOME")?).join("Library/Application Support")
+        } else {
+            var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| Some(PathBuf::from(var("HOME")?).join(".config")))?
+        };
+        Some(base.join("gorillas"))
+    }
+
+    pub fn load(name: &str) -> Option<String> {
+        std::fs::read_to_string(directory()?.join(name)).ok()
+    }
+
+    pub fn save(name: &str, text: &str) -> io::Result<()> {
+        let directory = directory()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+        std::fs::create_dir_all(&directory)?;
+        std::fs::write(directory.join(name), text)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use std::io;
+
+    mod js {
+        extern "C" {
+            // Length of the stored value, or -1 if there is none.
+            pub fn gorillas_storage_len(key: *const u8, key_len: usize) -> i32;
+            pub fn gorillas_storage_get(key: *const u8, key_len: usize, buf: *mut u8, len: usize);
+            pub fn gorillas_storage_set(
+                key: *const u8,
+                key_len: usize,
+                value: *const u8,
+                value_len: usize,
+            ) -> i32;
+        }
+    }
+
+    // Lets the loader check that `index.html` carries the matching plugin.
+    #[no_mangle]
+    pub extern "C" fn gorillas_storage_crate_version() -> u32 {
+        1
+    }
+
+    pub fn load(name: &str) -> Option<String> {
+        let len = unsafe { js::gorillas_storage_len(name.as_ptr(), name.len()) };
+        let mut buf = vec![0; usize::try_from(len).ok()?];
+        unsafe { js::gorillas_storage_get(name.as_ptr(), name.len(), buf.as_mut_ptr(), buf.len()) };
+        String::from_utf8(buf).ok()
+    }
+
+    pub fn save(name: &str, text: &str) -> io::Result<()> {
+        match unsafe {
+            js::gorillas_storage_set(name.as_ptr(), name.len(), text.as_ptr(), text.len())
+        } {
+            0 => Ok(()),
+            _ => Err(io::Error::other("the browser refused to store it")),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Pictures of the playfield for text terminals.
+//!
+//! Every character cell shows two pixels, one above the other: an upper half
+//! block with the top pixel as its foreground colour and the bottom one as
+//! its background, in the 256-colour ANSI palette. The picture is sampled
+//! from the game state at whatever size it is asked for, so it follows the
+//! terminal as it is resized and needs no window or GPU. `gorillas-term`
+//! plays on it.
+
+use crate::display::{DisplayMode, Rgb, Scheme};
+use crate::{GameState, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+/// What to draw besides the city and its gorillas.
+pub struct Scene<'a> {
+    pub state: &'a GameState,
+    pub mode: DisplayMode,
+    /// Where the banana is, if one is in the air.
+    pub banana: Option<(f32, f32)>,
+    /// Centre and radius of an explosion going off.
+    pub explosion: Option<(f32, f32, f32)>,
+}
+
+// A gorilla standing with its arms down, as rectangles around the point the
+// game places it at: head, body, legs and arms.
+const GORILLA: [(f32, f32, f32, f32); 6] = [
+    (-4.0, 0.0, 7.0, 7.0),
+    (-8.0, 7.0, 15.0, 13.0),
+    (-8.0, 20.0, 6.0, 10.0),
+    (1.0, 20.0, 6.0, 10.0),
+    (-11.0, 9.0, 3.0, 11.0),
+    (7.0, 9.0, 3.0, 11.0),
+];
+
+const SUN_RADIUS: f32 = 14.0;
+const SUN_Y: f32 = 25.0;
+
+/// The scene as `rows` lines of `columns` cells, with the escapes to colour
+/// them. Each line ends by resetting the colours.
+pub fn render(scene: &Scene, columns: usize, rows: usize) -> Vec<String> {
+    let scheme = scene.mode.scheme();
+    // Size of a terminal pixel in the playfield.
+    let width = VIRTUAL_WIDTH / columns.max(1) as f32;
+    let height = VIRTUAL_HEIGHT / (rows.max(1) * 2) as f32;
+    let pixel = |column: usize, row: usize| {
+        let rgb = color_at(scene, scheme, column, row, width, height);
+        ansi256(scene.mode.nearest(rgb))
+    };
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            let mut last = None;
+            for column in 0..columns {
+                let colors = (pixel(column, row * 2), pixel(column, row * 2 + 1));
+                if last != Some(colors) {
+                    line.push_str(&format!("\x1b[38;5;{}m\x1b[48;5;{}m", colors.0, colors.1));
+                    last = Some(colors);
+                }
+                line.push('\u{2580}');
+            }
+            line.push_str("\x1b[0m");
+            line
+        })
+        .collect()
+}
+
+// The colour of the terminal pixel at `column` and `row`, each `width` by
+// `height` playfield pixels. Large things are sampled at the pixel's centre;
+// the banana and the windows are smaller than a pixel in most terminals, so
+// they show wherever their centre falls.
+fn color_at(
+    scene: &Scene,
+    scheme: &Scheme,
+    column: usize,
+    row: usize,
+    width: f32,
+    height: f32,
+) -> Rgb {
+    let x = (column as f32 + 0.5) * width;
+    let y = (row as f32 + 0.5) * height;
+    let covers = |cx: f32, cy: f32| (cx - x).abs() <= width / 2.0 && (cy - y).abs() <= height / 2.0;
+    let state = scene.state;
+
+    if let Some((ex, ey, radius)) = scene.explosion {
+        if (x - ex).hypot(y - ey) <= radius.max(width / 2.0) {
+            return scheme.explosion;
+        }
+    }
+    if let Some((bx, by)) = scene.banana {
+        // The banana sprite's middle is a few pixels in from where it's put.
+        if covers(bx + 3.0, by + 3.0) || (x - bx - 3.0).hypot(y - by - 3.0) <= 3.0 {
+            return scheme.banana;
+        }
+    }
+    for (&gx, &gy) in state.gorilla_x.iter().zip(&state.gorilla_y) {
+        let inside = GORILLA.iter().any(|&(left, top, w, h)| {
+            (gx + left..gx + left + w).contains(&x) && (gy + top..gy + top + h).contains(&y)
+        });
+        if inside {
+            return scheme.object;
+        }
+    }
+    if (x - VIRTUAL_WIDTH / 2.0).hypot(y - SUN_Y) <= SUN_RADIUS {
+        return scheme.sun;
+    }
+
+    for building in &state.buildings {
+        if x < building.x || x >= building.x + building.width || y < building.y {
+            continue;
+        }
+        if building.is_cratered(x, y) {
+            break;
+        }
+        for window in &building.windows {
+            if covers(
+                window.x + window.width / 2.0,
+                window.y + window.height / 2.0,
+            ) {
+                return if window.lit {
+                    scheme.window_lit
+                } else {
+                    scheme.window_dark
+                };
+            }
+        }
+        return scheme.buildings[building.color];
+    }
+    scheme.sky
+}
+
+/// The nearest colour in the 256-colour ANSI palette: its 6x6x6 cube or
+/// its ramp of greys.
+pub fn ansi256(rgb: Rgb) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let distance = |a: Rgb| -> u32 {
+        (0..3)
+            .map(|i| (a[i] as i32 - rgb[i] as i32).pow(2) as u32)
+            .sum()
+    };
+    let level = |channel: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| (LEVELS[i] as i32 - channel as i32).abs())
+            .unwrap()
+    };
+    let (r, g, b) = (level(rgb[0]), level(rgb[1]), level(rgb[2]));
+    let cube = [LEVELS[r], LEVELS[g], LEVELS[b]];
+
+    let average = rgb.iter().map(|&c| c as u32).sum::<u32>() / 3;
+    let step = (average.saturating_sub(8) / 10).min(23) as u8;
+    let grey = 8 + step * 10;
+
+    if distance([grey; 3]) < distance(cube) {
+        232 + step
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
@@ -0,0 +1,320 @@
+//! Play-by-file matches in the `.gorturn` format.
+//!
+//! For players who can't be online at the same time. After each throw the
+//! game writes a turn file and the player sends it to whoever is up next,
+//! who loads it, watches the throw and makes their own. A turn file is a
+//! replay of the match so far with the checksum of the resulting state
+//! appended:
+//!
+//! ```text
+//! GORTURN 1
+//! seed 1234567
+//! player1 Alice
+//! player2 Bob
+//! games 3
+//! gravity 9.8
+//! shot 45 60
+//! shot 50 62.5
+//! hash 2 9e3779b97f4a7c15
+//! ```
+//!
+//! The `hash` line gives the number of throws and `GameState::checksum`
+//! after the last of them. Loading a file replays every throw and compares,
+//! so a file whose throws were edited or cut short is turned away. The
+//! checksum is not secret, so this stops accidents and casual edits rather
+//! than a determined cheat.
+//!
+//! Each machine also keeps a `Ledger` of the last turn it sent in every
+//! match. An incoming file must be newer than that turn and agree with it,
+//! which catches a file sent twice, an old file loaded by mistake, or a
+//! file from a different copy of the match.
+
+use crate::game::Match;
+use crate::replay::{Replay, ReplayError, REPLAY_VERSION};
+use crate::GameState;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const TURN_EXTENSION: &str = "gorturn";
+pub const TURN_VERSION: u32 = 1;
+/// Where the front-end keeps its ledger, next to the turn files.
+pub const DEFAULT_LEDGER: &str = "gorillas-turns.ledger";
+
+const MAGIC: &str = "GORTURN";
+/// The replay version turn files of this `TURN_VERSION` are written in.
+const BODY_VERSION: u32 = 2;
+
+// A turn file's body is written by `Replay`, so a new replay format must come
+// with a new turn file format, or files would load as the wrong version.
+const _: () = assert!(
+    REPLAY_VERSION == BODY_VERSION,
+    "bump TURN_VERSION and BODY_VERSION together with REPLAY_VERSION"
+);
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TurnFile {
+    /// Settings and every throw so far.
+    pub replay: Replay,
+    /// `GameState::checksum` after the last throw.
+    pub hash: u64,
+}
+
+#[derive(Debug)]
+pub enum TurnError {
+    Io(std::io::Error),
+    NotATurnFile,
+    UnsupportedVersion(u32),
+    Malformed(ReplayError),
+    MissingHash,
+    /// The throws don't lead to the recorded state.
+    Tampered(String),
+    /// Not newer than the turn this machine last sent in the match.
+    OutOfOrder {
+        throws: usize,
+        sent: usize,
+    },
+    /// The file's history differs from the turn this machine sent.
+    WrongHistory {
+        sent: usize,
+    },
+}
+
+impl fmt::Display for TurnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TurnError::Io(err) => write!(f, "could not read turn file: {}", err),
+            TurnError::NotATurnFile => write!(f, "not a {} file", MAGIC),
+            TurnError::UnsupportedVersion(version) => write!(
+                f,
+                "turn file version {} is not supported (expected {})",
+                version, TURN_VERSION
+            ),
+            TurnError::Malformed(err) => write!(f, "{}", err),
+            TurnError::MissingHash => write!(f, "turn file has no 'hash' line at the end"),
+            TurnError::Tampered(reason) => {
+                write!(f, "turn file has been changed since it was saved: {}", reason)
+            }
+            TurnError::OutOfOrder { throws, sent } => write!(
+                f,
+                "turn file is out of order: it ends at throw {}, but you already sent throw {}",
+                throws, sent
+            ),
+            TurnError::WrongHistory { sent } => write!(
+                f,
+                "turn file does not continue your match: its first {} throws differ from the ones you sent",
+                sent
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TurnError {}
+
+impl From<std::io::Error> for TurnError {
+    fn from(err: std::io::Error) -> Self {
+        TurnError::Io(err)
+    }
+}
+
+impl From<ReplayError> for TurnError {
+    fn from(err: ReplayError) -> Self {
+        TurnError::Malformed(err)
+    }
+}
+
+impl TurnFile {
+    /// The turn after the throws in `replay`, which must have been played
+    /// out to `state`.
+    pub fn new(replay: Replay, state: &GameState) -> Self {
+        TurnFile {
+            replay,
+            hash: state.checksum(),
+        }
+    }
+
+    /// Identifies the match across turns: a hash of its settings and seed.
+    pub fn match_id(&self) -> String {
+        let settings = Replay {
+            shots: Vec::new(),
+            ..self.replay.clone()
+        };
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in settings.to_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    pub fn throws(&self) -> usize {
+        self.replay.shots.len()
+    }
+
+    /// `<match id>-<throws>.gorturn`, so turns sort in order.
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}-{:03}.{}",
+            self.match_id(),
+            self.throws(),
+            TURN_EXTENSION
+        )
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<TurnFile, TurnError> {
+        TurnFile::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Result<TurnFile, TurnError> {
+        let mut lines: Vec<&str> = text.lines().collect();
+
+        let header = lines.first().ok_or(TurnError::NotATurnFile)?;
+        let version = match header.trim().split_once(' ') {
+            Some((MAGIC, version)) => version.parse().map_err(|_| TurnError::NotATurnFile)?,
+            _ => return Err(TurnError::NotATurnFile),
+        };
+        if version != TURN_VERSION {
+            return Err(TurnError::UnsupportedVersion(version));
+        }
+
+        let last = lines
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .ok_or(TurnError::MissingHash)?;
+        let mut hash_line = lines[last].split_whitespace();
+        let (throws, hash) = match (
+            hash_line.next(),
+            hash_line.next(),
+            hash_line.next(),
+            hash_line.next(),
+        ) {
+            (Some("hash"), Some(throws), Some(hash), None) => (
+                throws.parse::<usize>().ok(),
+                u64::from_str_radix(hash, 16).ok(),
+            ),
+            _ => return Err(TurnError::MissingHash),
+        };
+        let (Some(throws), Some(hash)) = (throws, hash) else {
+            return Err(TurnError::Tampered("the hash line is damaged".to_string()));
+        };
+
+        // What is left is a replay. Swapping the header and blanking the hash
+        // line keeps the line numbers in its errors right.
+        let replay_header = format!("GORREPLAY {}", BODY_VERSION);
+        lines[0] = &replay_header;
+        lines[last] = "";
+        let replay = Replay::parse(&lines.join("\n"))?;
+        if replay.shots.len() != throws {
+            return Err(TurnError::Tampered(format!(
+                "it holds {} throws but was saved after {}",
+                replay.shots.len(),
+                throws
+            )));
+        }
+        Ok(TurnFile { replay, hash })
+    }
+
+    /// Replays every throw and checks that they lead to the recorded state.
+    /// Returns the match as it stands, ready for the next throw.
+    pub fn verify(&self) -> Result<Match, TurnError> {
+        let game = self.play(self.throws())?;
+        let ours = game.state.checksum();
+        if ours != self.hash {
+            return Err(TurnError::Tampered(format!(
+                "its throws lead to state {:016x}, not the recorded {:016x}",
+                ours, self.hash
+            )));
+        }
+        Ok(game)
+    }
+
+    // The match after its first `throws` throws.
+    fn play(&self, throws: usize) -> Result<Match, TurnError> {
+        let mut state = GameState::new();
+        self.replay.apply_settings(&mut state);
+        let mut game = Match::new(state);
+        for (i, shot) in self.replay.shots.iter().take(throws).enumerate() {
+            if game.throw(shot.angle, shot.velocity).is_none() {
+                return Err(TurnError::Tampered(format!(
+                    "throw {} comes after the match ended",
+                    i + 1
+                )));
+            }
+        }
+        Ok(game)
+    }
+}
+
+impl fmt::Display for TurnFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let replay = self.replay.to_string();
+        let (_, body) = replay.split_once('\n').unwrap_or(("", ""));
+        writeln!(f, "{} {}", MAGIC, TURN_VERSION)?;
+        write!(f, "{}", body)?;
+        writeln!(f, "hash {} {:016x}", self.throws(), self.hash)
+    }
+}
+
+/// The last turn this machine sent in each match, by match id. Stored as
+/// `<match id> <throws> <hash>` lines.
+pub struct Ledger {
+    path: PathBuf,
+    sent: BTreeMap<String, (usize, u64)>,
+}
+
+impl Ledger {
+    /// Reads the ledger at `path`. A missing file is an empty ledger.
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let sent = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let id = fields.next()?.to_string();
+                let throws = fields.next()?.parse().ok()?;
+                let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+                Some((id, (throws, hash)))
+            })
+            .collect();
+        Ok(Ledger { path, sent })
+    }
+
+    /// Checks that `turn` follows on from what this machine last sent in
+    /// its match. Files from matches it has not played in pass.
+    pub fn check(&self, turn: &TurnFile) -> Result<(), TurnError> {
+        let Some(&(sent, hash)) = self.sent.get(&turn.match_id()) else {
+            return Ok(());
+        };
+        if turn.throws() <= sent {
+            return Err(TurnError::OutOfOrder {
+                throws: turn.throws(),
+                sent,
+            });
+        }
+        if turn.play(sent)?.state.checksum() != hash {
+            return Err(TurnError::WrongHistory { sent });
+        }
+        Ok(())
+    }
+
+    /// Notes that `turn` was sent, and saves the ledger.
+    pub fn record(&mut self, turn: &TurnFile) -> std::io::Result<()> {
+        self.sent
+            .insert(turn.match_id(), (turn.throws(), turn.hash));
+        let text: String = self
+            .sent
+            .iter()
+            .map(|(id, (throws, hash))| format!("{} {} {:016x}\n", id, throws, hash))
+            .collect();
+        std::fs::write(&self.path, text)
+    }
+}
@@ -0,0 +1,129 @@
+//! Computer players left to throw at a gorilla that doesn't throw back.
+
+mod common;
+
+use common::dealt;
+use gorillas::ai::{AiPlayer, Difficulty};
+use gorillas::{fly_shot, resolve_shot};
+
+// Throws an Expert may take to find the target.
+const EXPERT_THROWS: usize = 3;
+// Throws after which a round is given up on.
+const PATIENCE: usize = 30;
+
+// How a computer player fared against a target that stays put.
+struct Attempt {
+    // Throws it took to hit, if it did within the limit.
+    throws: Option<usize>,
+    // Whether any misses came down short of the target, and past it.
+    short: bool,
+    long: bool,
+}
+
+impl Attempt {
+    fn misses(&self) -> usize {
+        self.throws.map_or(PATIENCE, |throws| throws - 1)
+    }
+}
+
+// Throws until `shooter` hits, learning from each miss, for up to `limit`
+// throws.
+fn attempt(difficulty: Difficulty, seed: u32, shooter: usize, limit: usize) -> Attempt {
+    let mut state = dealt(seed);
+    let target = state.nearest_opponent(shooter);
+    let mut ai = AiPlayer::new(difficulty, seed as u64);
+    let target_x = state.gorilla_x[target] + 10.0;
+    let towards = (target_x - state.gorilla_x[shooter]).signum();
+    let mut result = Attempt {
+        throws: None,
+        short: false,
+        long: false,
+    };
+
+    for throw in 1..=limit {
+        let (angle, velocity) = ai.choose_throw(&state, shooter);
+        let shot = fly_shot(&mut state, shooter, angle, velocity);
+        if shot.player_hit == Some(target) {
+            result.throws = Some(throw);
+            break;
+        }
+        if (shot.x - target_x) * towards < 0.0 {
+            result.short = true;
+        } else {
+            result.long = true;
+        }
+        resolve_shot(&mut state, &shot, shooter);
+        ai.observe(&state, shooter, &shot);
+    }
+    result
+}
+
+// Both players' attempts on the first 20 maps.
+fn attempts(difficulty: Difficulty, limit: usize) -> Vec<Attempt> {
+    (0..20)
+        .flat_map(|seed| [0, 1].map(|shooter| attempt(difficulty, seed, shooter, limit)))
+        .collect()
+}
+
+#[test]
+fn experts_hit_within_a_few_throws() {
+    for seed in 0..20 {
+        for shooter in [0, 1] {
+            assert!(
+                attempt(Difficulty::Expert, seed, shooter, EXPERT_THROWS)
+                    .throws
+                    .is_some(),
+                "player {} missed {} times on seed {}",
+                shooter + 1,
+                EXPERT_THROWS,
+                seed
+            );
+        }
+    }
+}
+
+#[test]
+fn weaker_players_bracket_the_target() {
+    for difficulty in [Difficulty::Beginner, Difficulty::Advanced] {
+        let attempts = attempts(difficulty, PATIENCE);
+        // Nearly every round is won in the end...
+        let hits = attempts.iter().filter(|a| a.throws.is_some()).count();
+        assert!(hits >= 34, "{:?} hit in {} of 40 rounds", difficulty, hits);
+        // ...but not straight away...
+        let first = attempts.iter().filter(|a| a.throws == Some(1)).count();
+        assert!(
+            first <= 20,
+            "{:?} hit first time {} times",
+            difficulty,
+            first
+        );
+        // ...and a player that keeps missing lands on both sides of the
+        // target rather than creeping up from one.
+        let missing: Vec<&Attempt> = attempts.iter().filter(|a| a.misses() >= 2).collect();
+        let bracketed = missing.iter().filter(|a| a.short && a.long).count();
+        assert!(
+            bracketed * 4 >= missing.len() * 3,
+            "{:?} bracketed in {} of {} rounds",
+            difficulty,
+            bracketed,
+            missing.len()
+        );
+    }
+}
+
+#[test]
+fn beginners_miss_more_than_advanced_players() {
+    let misses = |difficulty| {
+        attempts(difficulty, PATIENCE)
+            .iter()
+            .map(Attempt::misses)
+            .sum::<usize>()
+    };
+    let (beginner, advanced) = (misses(Difficulty::Beginner), misses(Difficulty::Advanced));
+    assert!(
+        beginner > advanced * 2,
+        "beginners missed {} times, advanced players {}",
+        beginner,
+        advanced
+    );
+}
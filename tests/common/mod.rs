@@ -0,0 +1,20 @@
+//! Fixtures shared by the integration tests.
+
+// Each test crate uses only some of these.
+#![allow(dead_code)]
+
+use gorillas::GameState;
+
+/// The first map of a fresh match, dealt from `seed`.
+pub fn dealt(seed: u32) -> GameState {
+    dealt_with(seed, |_| {})
+}
+
+/// Like `dealt`, with the match set up by `setup` before the map is dealt.
+pub fn dealt_with(seed: u32, setup: impl FnOnce(&mut GameState)) -> GameState {
+    let mut state = GameState::new();
+    setup(&mut state);
+    state.seed = seed;
+    state.new_round();
+    state
+}
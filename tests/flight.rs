@@ -0,0 +1,61 @@
+//! Every front end flies bananas a few steps at a time through `step_shot`,
+//! and headless code flies them whole through `fly_shot`. Both must end a
+//! throw the same way, including throws that would never come down.
+
+mod common;
+
+use common::{dealt, dealt_with};
+use gorillas::{fly_shot, step_shot, GameState, ShotPhase, ShotState, MAX_FLIGHT_TIME};
+
+// Steps past which a flight is taken to have run away.
+const RUNAWAY_STEPS: u32 = (MAX_FLIGHT_TIME / 0.1) as u32 * 2;
+
+// Flies a throw as the front ends do, a tick per frame, until `step_shot`
+// marks it done.
+fn stepped(state: &mut GameState, angle: f32, velocity: f32) -> (ShotState, u32) {
+    let mut shot_state = ShotState::new();
+    shot_state.aim(state.faces_left(0), angle, velocity);
+    let mut steps = 0;
+    while shot_state.phase == ShotPhase::Animating {
+        step_shot(state, &mut shot_state, 0, 1);
+        steps += 1;
+        assert!(steps < RUNAWAY_STEPS, "the banana never came down");
+    }
+    (shot_state, steps)
+}
+
+#[test]
+fn throws_that_never_land_are_given_up() {
+    let max_steps = (MAX_FLIGHT_TIME / 0.1) as u32 + 1;
+    for (gravity, angle, velocity) in [
+        (0.0, 90.0, 0.0),
+        (9.8, 45.0, f32::NAN),
+        (9.8, f32::NAN, 60.0),
+        (f32::NAN, 45.0, 60.0),
+    ] {
+        let mut state = dealt_with(42, |state| state.gravity = gravity);
+        // Nothing to blow it back down either.
+        state.wind = 0;
+        let (shot_state, steps) = stepped(&mut state, angle, velocity);
+        assert!(steps <= max_steps, "{} steps", steps);
+        assert!(!shot_state.on_screen);
+        assert!(!shot_state.impact);
+        assert_eq!(shot_state.player_hit, None);
+    }
+}
+
+#[test]
+fn stepped_and_whole_flights_agree() {
+    for seed in 0..20 {
+        for (angle, velocity) in [(45.0, 60.0), (70.0, 40.0), (30.0, 90.0), (89.0, 5.0)] {
+            let mut whole = dealt(seed);
+            let mut step = whole.clone();
+            let flown = fly_shot(&mut whole, 0, angle, velocity);
+            let (stepped, _) = stepped(&mut step, angle, velocity);
+            assert_eq!((flown.x, flown.y), (stepped.x, stepped.y));
+            assert_eq!(flown.impact, stepped.impact);
+            assert_eq!(flown.player_hit, stepped.player_hit);
+            assert_eq!(flown.building_hit, stepped.building_hit);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Snapshots of the playfield drawn in software, compared with the images in
+//! `tests/golden`.
+//!
+//! Each scene is dealt from a seed, drawn into an `Rgba` with the same
+//! painters the window uses, and compared pixel by pixel. After a deliberate
+//! change to the drawing, look over the new pictures and store them with:
+//!
+//! ```bash
+//! GORILLAS_BLESS=1 cargo test --test golden
+//! ```
+
+mod common;
+
+use common::dealt_with;
+use gorillas::render::{
+    paint_banana, paint_cityscape, paint_gorilla, paint_sun, Rgba, ARMS_DOWN, LEFT_UP,
+};
+use gorillas::{
+    explosion_point, resolve_shot, trace_shot, DisplayMode, GameState, VIRTUAL_HEIGHT,
+    VIRTUAL_WIDTH,
+};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+// A channel may be this far off before the pixel counts as different...
+const CHANNEL_TOLERANCE: u8 = 16;
+// ...and this share of the pixels may differ, for floating point that
+// rounds a little differently elsewhere.
+const PIXEL_TOLERANCE: f64 = 0.0001;
+
+fn dealt(mode: DisplayMode, seed: u32) -> GameState {
+    dealt_with(seed, |state| state.city = mode.city())
+}
+
+// The city with its gorillas, `thrower` with an arm raised.
+fn paint_city(
+    mode: DisplayMode,
+    state: &GameState,
+    thrower: Option<usize>,
+    sun_shocked: bool,
+) -> Rgba {
+    let scheme = mode.scheme();
+    let mut image = Rgba::new(VIRTUAL_WIDTH as usize, VIRTUAL_HEIGHT as usize, scheme.sky);
+    paint_cityscape(&mut image, scheme, state);
+    paint_sun(&mut image, scheme, sun_shocked);
+    for player in 0..state.num_players() {
+        let arms = if Some(player) == thrower {
+            LEFT_UP
+        } else {
+            ARMS_DOWN
+        };
+        let (x, y) = (state.gorilla_x[player], state.gorilla_y[player]);
+        paint_gorilla(&mut image, scheme, x, y, arms);
+    }
+    image
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn write_png(path: &Path, image: &Rgba) {
+    let file = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&image.pixels).unwrap();
+}
+
+fn read_png(path: &Path) -> Result<Rgba, png::DecodingError> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut pixels)?;
+    pixels.truncate(info.buffer_size());
+    Ok(Rgba {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+fn check(name: &str, image: &Rgba) {
+    let path = golden_path(name);
+    if std::env::var_os("GORILLAS_BLESS").is_some() {
+        write_png(&path, image);
+        return;
+    }
+    let golden = read_png(&path).unwrap_or_else(|err| {
+        panic!(
+            "cannot read {}: {} (GORILLAS_BLESS=1 stores a new one)",
+            path.display(),
+            err
+        )
+    });
+    assert_eq!(
+        (golden.width, golden.height),
+        (image.width, image.height),
+        "{} is the wrong size",
+        name
+    );
+
+    let differing = golden
+        .pixels
+        .chunks(4)
+        .zip(image.pixels.chunks(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(*b)
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let allowed = (PIXEL_TOLERANCE * (image.width * image.height) as f64) as usize;
+    if differing > allowed {
+        let actual = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+        write_png(&actual, image);
+        panic!(
+            "{} differs from its golden image in {} pixels (at most {} allowed); \
+             the new picture is at {}",
+            name,
+            differing,
+            allowed,
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn explosions_stand_out_from_the_city() {
+    for mode in DisplayMode::ALL {
+        let scheme = mode.scheme();
+        assert_ne!(scheme.explosion, scheme.sky, "{}", mode.name());
+        assert!(
+            !scheme.buildings.contains(&scheme.explosion),
+            "{}",
+            mode.name()
+        );
+    }
+}
+
+#[test]
+fn ega_city() {
+    let state = dealt(DisplayMode::Ega, 42);
+    check(
+        "ega_city",
+        &paint_city(DisplayMode::Ega, &state, None, false),
+    );
+}
+
+#[test]
+fn cga_banana_in_flight() {
+    let mode = DisplayMode::Cga;
+    let mut state = dealt(mode, 7);
+    let mut steps = Vec::new();
+    trace_shot(&mut state, 0, 60.0, 55.0, |_, shot| {
+        steps.push((shot.x, shot.y, shot.banana_rotation()))
+    });
+
+    let mut image = paint_city(mode, &state, Some(0), state.sun_hit);
+    let (x, y, rotation) = steps[steps.len() / 2];
+    paint_banana(&mut image, mode.scheme(), x, y, rotation);
+    check("cga_banana_in_flight", &image);
+}
+
+#[test]
+fn mono_crater() {
+    let mode = DisplayMode::Mono;
+    let mut state = dealt(mode, 42);
+    let shot = trace_shot(&mut state, 0, 45.0, 40.0, |_, _| {});
+    let (x, y) = explosion_point(&shot).expect("the banana hits the city");
+    let (x, y) = (x as usize, y as usize);
+    let sky = mode.scheme().sky;
+    assert_ne!(paint_city(mode, &state, None, false).pixel(x, y), sky);
+
+    // The city once the explosion has cleared, with the hole it left.
+    resolve_shot(&mut state, &shot, 0);
+    let image = paint_city(mode, &state, None, false);
+    assert_eq!(image.pixel(x, y), sky, "no crater at ({}, {})", x, y);
+    check("mono_crater", &image);
+}
@@ -0,0 +1,66 @@
+//! Episodes of the reinforcement-learning environment, played to the end.
+
+use gorillas::ai::Difficulty;
+use gorillas::gym::{Action, Env, EnvConfig, OBSERVATION_LEN};
+use gorillas::solver::solve;
+
+const SEED: u32 = 42;
+
+// Steep and far too hard: the banana flies off the screen.
+const WILD: Action = Action {
+    angle: 80.0,
+    velocity: 200.0,
+};
+
+#[test]
+fn a_hit_wins_the_episode() {
+    let mut env = Env::new(EnvConfig::default());
+    let observation = env.reset(SEED);
+    assert_eq!(observation.to_vec().len(), OBSERVATION_LEN);
+    assert!(!env.is_done());
+
+    let hit = solve(env.state(), 0).expect("seed 42 can be won")[0];
+    let (_, reward, done) = env.step(Action {
+        angle: hit.angle,
+        velocity: hit.velocity,
+    });
+    assert_eq!((reward, done), (1.0, true));
+    assert!(env.is_done());
+
+    // Nothing more happens until the next reset.
+    assert_eq!(env.step(WILD).1, 0.0);
+    env.reset(SEED);
+    assert!(!env.is_done());
+}
+
+#[test]
+fn being_hit_back_loses_the_episode() {
+    let mut env = Env::new(EnvConfig {
+        opponent: Some(Difficulty::Expert),
+        ..EnvConfig::default()
+    });
+    env.reset(SEED);
+    let (_, reward, done) = env.step(WILD);
+    assert_eq!((reward, done), (-1.0, true));
+}
+
+#[test]
+fn misses_cost_a_little_until_the_episode_is_cut_off() {
+    let config = EnvConfig {
+        max_throws: 4,
+        ..EnvConfig::default()
+    };
+    let penalty = config.miss_penalty;
+    let mut env = Env::new(config);
+    env.reset(SEED);
+    for throw in 1..=4 {
+        let (_, reward, done) = env.step(WILD);
+        assert!(
+            (-penalty..0.0).contains(&reward),
+            "throw {} earned {}",
+            throw,
+            reward
+        );
+        assert_eq!(done, throw == 4);
+    }
+}
@@ -0,0 +1,44 @@
+//! `PLAY` strings parsed into tones.
+
+use gorillas::mml::Tune;
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 1e-4,
+        "{} is not {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn notes_last_as_long_as_tempo_and_length_say() {
+    let tune = Tune::parse("T120L4C").unwrap();
+    assert_eq!(tune.tones.len(), 1);
+    assert_close(tune.duration(), 0.5);
+    // Normal notes sound for seven eighths of their length.
+    assert_close(tune.tones[0].sounding, 0.5 * 7.0 / 8.0);
+
+    assert_close(Tune::parse("T120 C8.").unwrap().duration(), 0.375);
+    assert_close(Tune::parse("T120 C4..").unwrap().duration(), 0.875);
+    assert_close(Tune::parse("t60 l2 p4 c").unwrap().duration(), 3.0);
+}
+
+#[test]
+fn octave_three_holds_middle_c() {
+    let tune = Tune::parse("O3 A C").unwrap();
+    assert_close(tune.tones[0].frequency.unwrap(), 440.0);
+    assert_close(tune.tones[1].frequency.unwrap(), 261.6256);
+    assert_eq!(Tune::parse("N0").unwrap().tones[0].frequency, None);
+}
+
+#[test]
+fn errors_point_at_the_bad_argument() {
+    let err = Tune::parse("O9").unwrap_err();
+    assert_eq!(err.position, 1);
+    assert!(err.reason.contains("range"), "{}", err.reason);
+
+    assert_eq!(Tune::parse("T120C99").unwrap_err().position, 5);
+    assert_eq!(Tune::parse("CDX").unwrap_err().position, 2);
+    assert_eq!(Tune::parse("MQ").unwrap_err().position, 1);
+}
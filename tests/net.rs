@@ -0,0 +1,122 @@
+//! Two peers on the loopback interface, playing the protocol from HELLO to
+//! HASH as two machines would.
+
+use gorillas::game::Match;
+use gorillas::net::{Listener, NetError, Peer, Role, PROTOCOL_VERSION};
+use gorillas::GameState;
+use std::io::Write;
+use std::net::TcpStream;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Calls `step` until it gives an answer, failing the test if none comes.
+fn wait_for<T>(what: &str, mut step: impl FnMut() -> Option<T>) -> T {
+    let started = Instant::now();
+    loop {
+        if let Some(answer) = step() {
+            return answer;
+        }
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "timed out waiting for {}",
+            what
+        );
+        sleep(Duration::from_millis(5));
+    }
+}
+
+fn accept(listener: &Listener) -> Peer {
+    wait_for("the guest", || listener.try_accept().unwrap())
+}
+
+// A host and a guest that have greeted each other and agreed on a match.
+fn connected() -> (Peer, Match, Peer, Match) {
+    let listener = Listener::bind(0).unwrap();
+    let mut guest = Peer::join(&format!("127.0.0.1:{}", listener.port())).unwrap();
+    let mut host = accept(&listener);
+    assert_eq!(host.role, Role::Host);
+    assert_eq!(guest.role, Role::Guest);
+
+    let mut state = GameState::new();
+    state.seed = 1234;
+    state.player_names = vec!["Alice".to_string(), "Bob".to_string()];
+    host.send_settings(&state).unwrap();
+    let settings = wait_for("the settings", || {
+        guest.poll().unwrap();
+        guest.take_settings()
+    });
+    host.poll().unwrap();
+
+    let mut guest_state = GameState::new();
+    settings.apply_settings(&mut guest_state);
+    assert_eq!(guest_state.seed, 1234);
+    assert_eq!(guest_state.player_names, ["Alice", "Bob"]);
+    (host, Match::new(state), guest, Match::new(guest_state))
+}
+
+// The host throws for the first player and both sides fly it.
+fn first_throw(host: &mut Peer, host_game: &mut Match, guest: &mut Peer, guest_game: &mut Match) {
+    host.send_throw(45.0, 60.0).unwrap();
+    host_game.throw(45.0, 60.0);
+    let (angle, velocity) = wait_for("the throw", || {
+        guest.poll().unwrap();
+        guest.next_throw()
+    });
+    assert_eq!((angle, velocity), (45.0, 60.0));
+    guest_game.throw(angle, velocity);
+}
+
+#[test]
+fn peers_agree_over_loopback() {
+    let (mut host, mut host_game, mut guest, mut guest_game) = connected();
+    first_throw(&mut host, &mut host_game, &mut guest, &mut guest_game);
+    assert_eq!(host_game.state.checksum(), guest_game.state.checksum());
+
+    host.throw_resolved(&host_game.state).unwrap();
+    guest.throw_resolved(&guest_game.state).unwrap();
+    // Give each side's HASH time to arrive and be checked.
+    sleep(Duration::from_millis(100));
+    host.poll().unwrap();
+    guest.poll().unwrap();
+    host.close();
+    assert!(matches!(
+        wait_for("the goodbye", || guest.poll().err()),
+        NetError::Left
+    ));
+}
+
+#[test]
+fn a_different_hash_is_a_desync() {
+    let (mut host, mut host_game, mut guest, mut guest_game) = connected();
+    first_throw(&mut host, &mut host_game, &mut guest, &mut guest_game);
+
+    host.throw_resolved(&host_game.state).unwrap();
+    // The guest's game goes its own way.
+    guest_game.state.total_wins[1] += 1;
+    guest.throw_resolved(&guest_game.state).unwrap();
+
+    match wait_for("the guest's hash", || host.poll().err()) {
+        NetError::Desync { turn, ours, theirs } => {
+            assert_eq!(turn, 0);
+            assert_eq!(ours, host_game.state.checksum());
+            assert_eq!(theirs, guest_game.state.checksum());
+        }
+        err => panic!("expected a desync, got {}", err),
+    }
+}
+
+#[test]
+fn throws_that_are_not_numbers_are_refused() {
+    for throw in ["THROW 0 NaN 60", "THROW 0 45 inf", "THROW 0 -inf 60"] {
+        let listener = Listener::bind(0).unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", listener.port())).unwrap();
+        let mut host = accept(&listener);
+        write!(stream, "HELLO {}\n{}\n", PROTOCOL_VERSION, throw).unwrap();
+
+        let err = wait_for("the bad throw", || host.poll().err());
+        assert!(matches!(err, NetError::Protocol(_)), "{}: {}", throw, err);
+        assert_eq!(host.next_throw(), None);
+    }
+}
@@ -0,0 +1,53 @@
+//! Where gorillas are put on the skyline.
+
+mod common;
+
+use common::dealt_with;
+use gorillas::{CitySizing, GameState, MAX_PLAYERS};
+
+// Half a gorilla's width; gorillas are placed this far left of the middle of
+// their roof.
+const HALF_GORILLA: f32 = 14.0;
+
+// The building under each gorilla, by index into `GameState::buildings`.
+fn roofs(state: &GameState) -> Vec<usize> {
+    state
+        .gorilla_x
+        .iter()
+        .map(|&x| {
+            let middle = x + HALF_GORILLA;
+            state
+                .buildings
+                .iter()
+                .position(|b| b.x <= middle && middle < b.x + b.width)
+                .unwrap_or_else(|| panic!("no building under a gorilla at {}", x))
+        })
+        .collect()
+}
+
+#[test]
+fn gorillas_never_share_a_building() {
+    for city in [CitySizing::Cga, CitySizing::Ega] {
+        for teams in [vec![0, 1, 2, 3, 4, 5], vec![0, 1, 0, 1, 0, 1]] {
+            for seed in 0..500 {
+                let state = dealt_with(seed, |state| {
+                    state.city = city;
+                    state.set_num_players(MAX_PLAYERS);
+                    state.set_teams(&teams);
+                });
+
+                let mut roofs = roofs(&state);
+                roofs.sort_unstable();
+                roofs.dedup();
+                assert_eq!(
+                    roofs.len(),
+                    MAX_PLAYERS,
+                    "seed {} in {} with teams {:?}",
+                    seed,
+                    city.name(),
+                    teams
+                );
+            }
+        }
+    }
+}
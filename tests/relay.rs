@@ -0,0 +1,129 @@
+//! The relay binary on the loopback interface, with two tabs in a room and
+//! a third turned away.
+
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::net::TcpStream;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::Duration;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Error, Message, WebSocket};
+
+type Tab = WebSocket<MaybeTlsStream<TcpStream>>;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+// How long a tab listens to be sure nothing is coming.
+const QUIET: Duration = Duration::from_millis(200);
+
+// Kills the relay when the test ends, passed or not.
+struct Relay {
+    child: Child,
+    // Held open: the relay logs each pairing and would panic on a closed pipe.
+    _log: BufReader<ChildStdout>,
+    port: u16,
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn start_relay() -> Relay {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gorillas-relay"))
+        .arg("0")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("relay starts");
+    let mut log = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    log.read_line(&mut line).unwrap();
+    let port = line
+        .trim()
+        .strip_prefix("Relaying on port ")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or_else(|| panic!("unexpected banner {:?}", line));
+    Relay {
+        child,
+        _log: log,
+        port,
+    }
+}
+
+fn join(relay: &Relay, code: &str) -> Tab {
+    let (mut tab, _) = tungstenite::connect(format!("ws://127.0.0.1:{}", relay.port)).unwrap();
+    if let MaybeTlsStream::Plain(stream) = tab.get_mut() {
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    }
+    tab.send(Message::text(format!("ROOM {}", code))).unwrap();
+    tab
+}
+
+// Joins two tabs to a room and returns them as host and guest. Whichever
+// the relay saw first is the host, which needn't be the first to connect.
+fn pair(relay: &Relay, code: &str, other_code: &str) -> (Tab, Tab) {
+    let mut first = join(relay, code);
+    let mut second = join(relay, other_code);
+    match (read(&mut first).as_str(), read(&mut second).as_str()) {
+        ("PAIRED HOST", "PAIRED GUEST") => (first, second),
+        ("PAIRED GUEST", "PAIRED HOST") => (second, first),
+        other => panic!("expected one host and one guest, got {:?}", other),
+    }
+}
+
+fn read(tab: &mut Tab) -> String {
+    match tab.read().unwrap() {
+        Message::Text(text) => text.to_string(),
+        other => panic!("expected text, got {:?}", other),
+    }
+}
+
+// Fails if anything arrives within `QUIET`.
+fn assert_quiet(tab: &mut Tab) {
+    if let MaybeTlsStream::Plain(stream) = tab.get_mut() {
+        stream.set_read_timeout(Some(QUIET)).unwrap();
+    }
+    match tab.read() {
+        Err(Error::Io(err))
+            if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+        other => panic!("expected nothing, got {:?}", other),
+    }
+    if let MaybeTlsStream::Plain(stream) = tab.get_mut() {
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    }
+}
+
+#[test]
+fn messages_reach_the_other_tab_only() {
+    let relay = start_relay();
+    let (mut host, mut guest) = pair(&relay, "abc123", "ABC123");
+
+    for (text, from_host) in [
+        ("HELLO 1", true),
+        ("HELLO 1", false),
+        ("THROW 1 45 60", true),
+        ("HASH 1 9e3779b97f4a7c15", false),
+    ] {
+        let (sender, receiver) = if from_host {
+            (&mut host, &mut guest)
+        } else {
+            (&mut guest, &mut host)
+        };
+        sender.send(Message::text(text)).unwrap();
+        assert_eq!(read(receiver), text);
+        assert_quiet(sender);
+    }
+}
+
+#[test]
+fn a_third_tab_finds_the_room_full() {
+    let relay = start_relay();
+    let (mut host, mut guest) = pair(&relay, "FULLUP", "FULLUP");
+
+    let mut third = join(&relay, "FULLUP");
+    assert_eq!(read(&mut third), "FULL");
+
+    // The pair carries on undisturbed.
+    host.send(Message::text("THROW 1 45 60")).unwrap();
+    assert_eq!(read(&mut guest), "THROW 1 45 60");
+}
@@ -0,0 +1,97 @@
+//! Matches recorded, written out, read back and played again, which must end
+//! in the same state.
+
+use gorillas::ai::{AiPlayer, Difficulty};
+use gorillas::fairness::DEFAULT_MIN_FAIRNESS;
+use gorillas::game::Match;
+use gorillas::replay::{Replay, ReplayError};
+use gorillas::{CitySizing, FriendlyFire, GameState};
+
+// Longer than any match the computer plays.
+const MAX_THROWS: usize = 500;
+
+// Plays `state` to the end with the computer throwing for everyone, and
+// returns the recording and how the match ended.
+fn record(state: GameState) -> (Replay, GameState) {
+    let mut replay = Replay::new(&state);
+    let mut ai: Vec<AiPlayer> = (0..state.num_players())
+        .map(|i| AiPlayer::new(Difficulty::Advanced, state.seed as u64 + i as u64))
+        .collect();
+    let mut game = Match::new(state);
+    while !game.is_over() {
+        assert!(replay.shots.len() < MAX_THROWS, "the match never ended");
+        game.deal();
+        let thrower = game.next_thrower();
+        let (angle, velocity) = ai[thrower].choose_throw(&game.state, thrower);
+        let (shot_state, _) = game.throw(angle, velocity).unwrap();
+        ai[thrower].observe(&game.state, thrower, &shot_state);
+        replay.push_shot(angle, velocity);
+    }
+    (replay, game.state)
+}
+
+// Reads `text` back and plays its throws.
+fn play_back(text: &str) -> GameState {
+    let replay = Replay::parse(text).unwrap();
+    let mut state = GameState::new();
+    replay.apply_settings(&mut state);
+    let mut game = Match::new(state);
+    for shot in &replay.shots {
+        game.throw(shot.angle, shot.velocity)
+            .expect("no throws after the match ended");
+    }
+    assert!(game.is_over());
+    game.state
+}
+
+#[test]
+fn a_two_player_match_plays_back_the_same() {
+    let mut state = GameState::new();
+    state.seed = 1234;
+    state.player_names = vec!["Alice".to_string(), "Bob".to_string()];
+    let (replay, ended) = record(state);
+
+    let text = replay.to_string();
+    assert!(text.starts_with("GORREPLAY 2\n"));
+    assert_eq!(Replay::parse(&text).unwrap(), replay);
+    let replayed = play_back(&text);
+    assert_eq!(replayed.total_wins, ended.total_wins);
+    assert_eq!(replayed.checksum(), ended.checksum());
+}
+
+#[test]
+fn a_team_match_in_cga_plays_back_the_same() {
+    let mut state = GameState::new();
+    state.seed = 99;
+    state.set_num_players(4);
+    state.set_teams(&[0, 1, 0, 1]);
+    state.friendly_fire = FriendlyFire::Off;
+    state.num_games = 2;
+    state.gravity = 12.5;
+    state.min_fairness = Some(DEFAULT_MIN_FAIRNESS);
+    state.city = CitySizing::Cga;
+    let (replay, ended) = record(state);
+
+    let text = replay.to_string();
+    for line in ["teams 0 1 0 1", "friendlyfire off", "fairness", "city cga"] {
+        assert!(text.contains(line), "no '{}' in\n{}", line, text);
+    }
+    let replayed = play_back(&text);
+    assert_eq!(replayed.teams, ended.teams);
+    assert_eq!(replayed.checksum(), ended.checksum());
+}
+
+#[test]
+fn version_one_loads_and_unknown_versions_are_turned_away() {
+    let body = "seed 1\nplayer1 A\nplayer2 B\ngames 3\ngravity 9.8\nshot 45 60\n";
+    let replay = Replay::parse(&format!("GORREPLAY 1\n{}", body)).unwrap();
+    assert_eq!(replay.player_names, ["A", "B"]);
+    assert_eq!(replay.shots.len(), 1);
+
+    for version in [0, 3] {
+        assert!(matches!(
+            Replay::parse(&format!("GORREPLAY {}\n{}", version, body)),
+            Err(ReplayError::UnsupportedVersion(v)) if v == version
+        ));
+    }
+}
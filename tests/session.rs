@@ -0,0 +1,114 @@
+//! Whole sessions, from the intro screen on, played from the input scripts
+//! in `tests/sessions`.
+//!
+//! Each script is fed to a `Session` at 60 frames a second until it runs
+//! out or the session quits, and the test looks at where it ended up.
+
+use gorillas::ai::Difficulty;
+use gorillas::input::{InputSource, Script};
+use gorillas::replay::RecordedShot;
+use gorillas::session::{Phase, Session};
+use gorillas::setup::Field;
+use gorillas::GameState;
+use std::path::Path;
+
+const FRAMES_PER_SECOND: f64 = 60.0;
+// Seed for setups that leave it blank.
+const FALLBACK_SEED: u32 = 99;
+// Longer than any script runs.
+const MAX_FRAMES: u32 = 60 * 60 * 5;
+
+fn play(name: &str) -> Session {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/sessions")
+        .join(name);
+    let mut input = Script::load(&path).unwrap_or_else(|err| panic!("{}: {}", name, err));
+    let mut session = Session::new(GameState::new(), FALLBACK_SEED);
+    let mut frame = 0;
+    while !input.is_finished() && session.phase != Phase::Quit {
+        assert!(frame < MAX_FRAMES, "{} never finished", name);
+        input.next_frame(frame as f64 / FRAMES_PER_SECOND);
+        session.update(&mut input);
+        frame += 1;
+    }
+    session
+}
+
+fn shot(angle: f32, velocity: f32) -> RecordedShot {
+    RecordedShot { angle, velocity }
+}
+
+#[test]
+fn blank_answers_get_the_defaults() {
+    let session = play("defaults.txt");
+    assert_eq!(session.phase, Phase::Quit);
+
+    let state = &session.game.as_ref().expect("the match started").state;
+    assert_eq!(state.player_names, ["Player 1", "Player 2"]);
+    assert_eq!(state.num_games, 3);
+    assert_eq!(state.gravity, 9.8);
+    assert_eq!(state.seed, FALLBACK_SEED);
+    assert_eq!(state.min_fairness, None);
+    assert!(!state.is_team_match());
+    assert_eq!(session.setup.computer, [None, None]);
+    assert_eq!(session.setup.speed, 1.0);
+    assert_eq!(session.setup.answers.computers, ["Human", "Human"]);
+    assert!(session.replay.unwrap().shots.is_empty());
+}
+
+#[test]
+fn names_are_typed_corrected_and_cut_short() {
+    let session = play("name_entry.txt");
+    assert_eq!(session.phase, Phase::Quit);
+
+    let state = &session.game.as_ref().expect("the match started").state;
+    assert_eq!(state.player_names, ["Alice", "Bartholome", "Player 3"]);
+    assert_eq!(
+        session.setup.computer,
+        [None, Some(Difficulty::Intermediate), None]
+    );
+    assert_eq!(state.num_games, 5);
+    assert!(state.min_fairness.is_some());
+    // Rerolling a lopsided map moves the seed on, so look at the one typed.
+    assert_eq!(session.replay.unwrap().seed, 1234);
+    assert_eq!(session.setup.speed, 2.5);
+}
+
+#[test]
+fn invalid_numbers_fall_back_to_the_defaults() {
+    let session = play("invalid_numbers.txt");
+    assert_eq!(session.phase, Phase::Quit);
+    assert_eq!(session.setup.field, Field::Done);
+
+    let answers = &session.setup.answers;
+    assert_eq!(answers.players, "6");
+    assert_eq!(answers.games, "zero");
+    assert_eq!(answers.gravity, "0");
+    assert_eq!(answers.seed, FALLBACK_SEED.to_string());
+
+    let replay = session.replay.expect("the match started");
+    assert_eq!(replay.player_names.len(), 6);
+    assert_eq!(replay.num_games, 3);
+    assert_eq!(replay.gravity, 9.8);
+    assert_eq!(replay.seed, FALLBACK_SEED);
+    assert_eq!(session.setup.speed, 1.0);
+    assert_eq!(replay.shots, [shot(45.0, 50.0), shot(60.0, 3.0)]);
+}
+
+#[test]
+fn playing_again_sets_up_a_new_match() {
+    let session = play("play_again.txt");
+    assert_eq!(session.phase, Phase::Quit);
+    assert_eq!(session.matches, 2);
+
+    let game = session.game.expect("a match was played");
+    assert!(game.is_over());
+    assert_eq!(game.state.player_names, ["Cheeta", "Bonzo"]);
+    assert_eq!(game.state.seed, 7);
+    assert_eq!(game.state.total_wins.iter().sum::<i32>(), 1);
+    assert_eq!(
+        session.setup.computer,
+        [Some(Difficulty::Advanced), Some(Difficulty::Advanced)]
+    );
+    assert!(!session.replay.unwrap().shots.is_empty());
+}
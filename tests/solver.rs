@@ -0,0 +1,34 @@
+//! The solver's throws, flown for real.
+
+mod common;
+
+use common::dealt;
+use gorillas::fly_shot;
+use gorillas::solver::solve;
+
+#[test]
+fn solutions_hit_the_target() {
+    let state = dealt(42);
+    let target = state.nearest_opponent(0);
+    let solutions = solve(&state, 0).expect("seed 42 can be won from the left");
+    assert!(!solutions.is_empty());
+
+    for solution in solutions {
+        assert!(solution.min_velocity <= solution.velocity);
+        assert!(solution.velocity <= solution.max_velocity);
+        for velocity in [
+            solution.min_velocity,
+            solution.velocity,
+            solution.max_velocity,
+        ] {
+            let shot = fly_shot(&mut state.clone(), 0, solution.angle, velocity);
+            assert_eq!(
+                shot.player_hit,
+                Some(target),
+                "angle {} velocity {} misses",
+                solution.angle,
+                velocity
+            );
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Turn files written, read back and checked, and the ledger that keeps
+//! them in order.
+
+use gorillas::game::Match;
+use gorillas::replay::Replay;
+use gorillas::turnfile::{Ledger, TurnError, TurnFile};
+use gorillas::GameState;
+use std::path::PathBuf;
+
+const THROWS: [(f32, f32); 4] = [(45.0, 30.0), (50.0, 35.0), (60.0, 25.0), (40.0, 45.0)];
+
+// The turn file saved after `throws`, in a match on seed 1234.
+fn turn_after(throws: &[(f32, f32)]) -> TurnFile {
+    let mut state = GameState::new();
+    state.seed = 1234;
+    state.player_names = vec!["Alice".to_string(), "Bob".to_string()];
+    let mut replay = Replay::new(&state);
+    let mut game = Match::new(state);
+    for &(angle, velocity) in throws {
+        game.throw(angle, velocity).expect("the match is still on");
+        replay.push_shot(angle, velocity);
+    }
+    TurnFile::new(replay, &game.state)
+}
+
+// A ledger of its own for each test, starting empty.
+fn ledger(name: &str) -> (Ledger, PathBuf) {
+    let path =
+        std::env::temp_dir().join(format!("gorillas-{}-{}.ledger", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    (Ledger::open(&path).unwrap(), path)
+}
+
+#[test]
+fn turn_files_read_back_as_written() {
+    let turn = turn_after(&THROWS);
+    let text = turn.to_string();
+    assert!(text.starts_with("GORTURN 1\n"));
+    assert!(text.ends_with(&format!("hash 4 {:016x}\n", turn.hash)));
+
+    let read = TurnFile::parse(&text).unwrap();
+    assert_eq!(read, turn);
+    assert_eq!(read.file_name(), turn.file_name());
+    let game = read.verify().unwrap();
+    assert_eq!(game.state.checksum(), turn.hash);
+}
+
+#[test]
+fn an_edited_throw_is_caught() {
+    let text = turn_after(&THROWS).to_string();
+    assert!(text.contains("\nshot 50 35\n"));
+    let edited = text.replace("\nshot 50 35\n", "\nshot 50 55\n");
+
+    let turn = TurnFile::parse(&edited).unwrap();
+    assert!(matches!(turn.verify(), Err(TurnError::Tampered(_))));
+
+    // Dropping the throw altogether no longer matches the hash line.
+    let cut = text.replace("shot 50 35\n", "");
+    assert!(matches!(TurnFile::parse(&cut), Err(TurnError::Tampered(_))));
+}
+
+#[test]
+fn the_ledger_refuses_turns_it_has_already_sent() {
+    let (mut ledger, path) = ledger("out-of-order");
+    let sent = turn_after(&THROWS[..2]);
+    ledger.record(&sent).unwrap();
+
+    // Read back from disk, as the next session would.
+    let ledger = Ledger::open(&path).unwrap();
+    for throws in [1, 2] {
+        match ledger.check(&turn_after(&THROWS[..throws])) {
+            Err(TurnError::OutOfOrder { throws: t, sent: 2 }) => assert_eq!(t, throws),
+            other => panic!("{} throws: expected out of order, got {:?}", throws, other),
+        }
+    }
+    ledger.check(&turn_after(&THROWS[..3])).unwrap();
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn the_ledger_refuses_a_different_history() {
+    let (mut ledger, path) = ledger("wrong-history");
+    ledger.record(&turn_after(&THROWS[..2])).unwrap();
+
+    let mut other = THROWS;
+    other[1] = (50.0, 55.0);
+    let turn = turn_after(&other[..3]);
+    turn.verify().unwrap();
+    assert!(matches!(
+        ledger.check(&turn),
+        Err(TurnError::WrongHistory { sent: 2 })
+    ));
+    let _ = std::fs::remove_file(path);
+}